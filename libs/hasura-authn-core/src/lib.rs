@@ -1,13 +1,10 @@
-// use axum::middleware::Next;
-// use axum::response::IntoResponse;
-// use axum::Extension;
-// use axum::{http::Request, http::StatusCode};
-// use lang_graphql::http::Response;
+use http::{HeaderMap, StatusCode};
+use lang_graphql::http::Response;
 use schemars::JsonSchema;
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
-    // str::FromStr,
+    str::FromStr,
 };
 
 /// The architecture is as follows:
@@ -136,77 +133,58 @@ impl Identity {
 }
 
 // Error when resolving a session
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum SessionError {
     // The requested role isn't allowed
+    #[error("cannot be authorized as role: {0}")]
     Unauthorized(Role),
     // Default role information is not present in allowed_roles
+    #[error("internal: RoleAuthorization of role: {0} not found")]
     InternalRoleNotFound(Role),
+    #[error("the value of the header '{header_name}' isn't a valid string: '{error}'")]
     InvalidHeaderValue { header_name: String, error: String },
 }
 
-// impl IntoResponse for SessionError {
-//     fn into_response(self) -> axum::response::Response {
-//         match self {
-//             SessionError::Unauthorized(role) => Response::error_message_with_status(
-//                 StatusCode::UNAUTHORIZED,
-//                 format!("cannot be authorized as role: {}", role),
-//             )
-//             .into_response(),
-//             SessionError::InternalRoleNotFound(role) => Response::error_message_with_status(
-//                 StatusCode::INTERNAL_SERVER_ERROR,
-//                 format!("internal: RoleAuthorization of role: {} not found", role),
-//             )
-//             .into_response(),
-//             SessionError::InvalidHeaderValue { header_name, error } => {
-//                 Response::error_message_with_status(
-//                     StatusCode::BAD_REQUEST,
-//                     format!(
-//                         "the value of the header '{}' isn't a valid string: '{}'",
-//                         header_name, error
-//                     ),
-//                 )
-//                 .into_response()
-//             }
-//         }
-//     }
-// }
+impl From<SessionError> for Response {
+    fn from(error: SessionError) -> Self {
+        let status_code = match error {
+            SessionError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            SessionError::InternalRoleNotFound(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            SessionError::InvalidHeaderValue { .. } => StatusCode::BAD_REQUEST,
+        };
+        Response::error_message_with_status(status_code, error.to_string())
+    }
+}
 
 // Using the x-hasura-* headers of the request and the identity set by the authn system,
 // this layer resolves a 'session' which is then used by the execution engine
-// pub async fn resolve_session<'a, B>(
-//     Extension(identity): Extension<Identity>,
-//     mut request: Request<B>,
-//     next: Next<B>,
-// ) -> axum::response::Result<axum::response::Response> {
-//     let mut session_variables = HashMap::new();
-//     let mut role = None;
-//     // traverse through the headers and collect role and session variables
-//     for (header_name, header_value) in request.headers() {
-//         if let Ok(session_variable) = SessionVariable::from_str(header_name.as_str()) {
-//             let variable_value = match header_value.to_str() {
-//                 Err(e) => Err(SessionError::InvalidHeaderValue {
-//                     header_name: header_name.to_string(),
-//                     error: e.to_string(),
-//                 })?,
-//                 Ok(h) => SessionVariableValue::new(h),
-//             };
-
-//             if session_variable == SESSION_VARIABLE_ROLE.to_owned() {
-//                 role = Some(Role::new(&variable_value.0))
-//             } else {
-//                 // TODO: Handle the duplicate case?
-//                 session_variables.insert(session_variable, variable_value);
-//             }
-//         }
-//     }
-//     let session = identity
-//         .get_role_authorization(role.as_ref())?
-//         .build_session(session_variables);
-//     request.extensions_mut().insert(session);
-//     let response = next.run(request).await;
-//     Ok(response)
-// }
+pub fn resolve_session(identity: &Identity, headers: &HeaderMap) -> Result<Session, SessionError> {
+    let mut session_variables = HashMap::new();
+    let mut role = None;
+    // traverse through the headers and collect role and session variables
+    for (header_name, header_value) in headers {
+        if let Ok(session_variable) = SessionVariable::from_str(header_name.as_str()) {
+            let variable_value = match header_value.to_str() {
+                Err(e) => Err(SessionError::InvalidHeaderValue {
+                    header_name: header_name.to_string(),
+                    error: e.to_string(),
+                })?,
+                Ok(h) => SessionVariableValue::new(h),
+            };
+
+            if session_variable == SESSION_VARIABLE_ROLE.to_owned() {
+                role = Some(Role::new(&variable_value.0))
+            } else {
+                // TODO: Handle the duplicate case?
+                session_variables.insert(session_variable, variable_value);
+            }
+        }
+    }
+    let session = identity
+        .get_role_authorization(role.as_ref())?
+        .build_session(session_variables);
+    Ok(session)
+}
 
 #[cfg(test)]
 mod tests {
@@ -350,4 +328,45 @@ mod tests {
             session
         );
     }
+
+    #[test]
+    fn test_resolve_session_with_role_header() {
+        let mut allowed_roles = HashMap::new();
+        allowed_roles.insert(
+            Role::new("user"),
+            RoleAuthorization {
+                role: Role::new("user"),
+                session_variables: HashMap::new(),
+                allowed_session_variables_from_request: SessionVariableList::All,
+            },
+        );
+        let identity = Identity::Specific {
+            default_role: Role::new("user"),
+            allowed_roles,
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hasura-role", "user".parse().unwrap());
+        headers.insert("x-hasura-user-id", "1".parse().unwrap());
+        let session = resolve_session(&identity, &headers).unwrap();
+
+        let mut expected_session_variables = HashMap::new();
+        expected_session_variables.insert(
+            SessionVariable::from_str("x-hasura-user-id").unwrap(),
+            SessionVariableValue::new("1"),
+        );
+        pa::assert_eq!(
+            Session {
+                role: Role::new("user"),
+                variables: SessionVariables(expected_session_variables),
+            },
+            session
+        );
+
+        headers.insert("x-hasura-role", "admin".parse().unwrap());
+        assert!(matches!(
+            resolve_session(&identity, &headers),
+            Err(SessionError::Unauthorized(role)) if role == Role::new("admin")
+        ));
+    }
 }
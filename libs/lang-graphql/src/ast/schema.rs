@@ -1,3 +1,8 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
 use super::common::*;
 use super::spanning::*;
 use super::value::*;
@@ -33,7 +38,18 @@ pub struct ConstDirective {
     /// The name of the directive.
     pub name: Spanning<Name>,
     /// The arguments to the directive.
-    pub arguments: Option<Spanning<Vec<Spanning<InputValueDefinition>>>>,
+    pub arguments: Option<Spanning<Vec<Spanning<KeyValue<ConstValue>>>>>,
+}
+
+impl ConstDirective {
+    /// The value of the argument of the given name, if it is provided
+    pub fn argument(&self, name: &str) -> Option<&ConstValue> {
+        self.arguments
+            .iter()
+            .flat_map(|arguments| &arguments.item)
+            .find(|argument| argument.item.key.item.as_str() == name)
+            .map(|argument| &argument.item.value.item)
+    }
 }
 
 /// The definition of the schema in a GraphQL service.
@@ -236,6 +252,8 @@ pub struct DirectiveDefinition {
     pub name: Spanning<Name>,
     /// The arguments of the directive.
     pub arguments: Vec<Spanning<InputValueDefinition>>,
+    /// Whether the directive can be used more than once at a location.
+    pub repeatable: bool,
     /// The locations the directive applies to.
     pub locations: Vec<Spanning<DirectiveLocation>>,
 }
@@ -243,7 +261,8 @@ pub struct DirectiveDefinition {
 /// Where a directive can apply to.
 ///
 /// [Reference](https://spec.graphql.org/October2021/#DirectiveLocation).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum DirectiveLocation {
     /// A [query](enum.OperationType.html#variant.Query)
     /// [operation](struct.OperationDefinition.html).
@@ -289,3 +308,75 @@ pub enum DirectiveLocation {
     /// An [variable definition](struct.VariableDefinition.html).
     VariableDefinition,
 }
+
+impl DirectiveLocation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DirectiveLocation::Query => "QUERY",
+            DirectiveLocation::Mutation => "MUTATION",
+            DirectiveLocation::Subscription => "SUBSCRIPTION",
+            DirectiveLocation::Field => "FIELD",
+            DirectiveLocation::FragmentDefinition => "FRAGMENT_DEFINITION",
+            DirectiveLocation::FragmentSpread => "FRAGMENT_SPREAD",
+            DirectiveLocation::InlineFragment => "INLINE_FRAGMENT",
+            DirectiveLocation::Schema => "SCHEMA",
+            DirectiveLocation::Scalar => "SCALAR",
+            DirectiveLocation::Object => "OBJECT",
+            DirectiveLocation::FieldDefinition => "FIELD_DEFINITION",
+            DirectiveLocation::ArgumentDefinition => "ARGUMENT_DEFINITION",
+            DirectiveLocation::Interface => "INTERFACE",
+            DirectiveLocation::Union => "UNION",
+            DirectiveLocation::Enum => "ENUM",
+            DirectiveLocation::EnumValue => "ENUM_VALUE",
+            DirectiveLocation::InputObject => "INPUT_OBJECT",
+            DirectiveLocation::InputFieldDefinition => "INPUT_FIELD_DEFINITION",
+            DirectiveLocation::VariableDefinition => "VARIABLE_DEFINITION",
+        }
+    }
+}
+
+impl From<OperationType> for DirectiveLocation {
+    fn from(operation_type: OperationType) -> Self {
+        match operation_type {
+            OperationType::Query => DirectiveLocation::Query,
+            OperationType::Mutation => DirectiveLocation::Mutation,
+            OperationType::Subscription => DirectiveLocation::Subscription,
+        }
+    }
+}
+
+impl Display for DirectiveLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl FromStr for DirectiveLocation {
+    type Err = ();
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        [
+            DirectiveLocation::Query,
+            DirectiveLocation::Mutation,
+            DirectiveLocation::Subscription,
+            DirectiveLocation::Field,
+            DirectiveLocation::FragmentDefinition,
+            DirectiveLocation::FragmentSpread,
+            DirectiveLocation::InlineFragment,
+            DirectiveLocation::Schema,
+            DirectiveLocation::Scalar,
+            DirectiveLocation::Object,
+            DirectiveLocation::FieldDefinition,
+            DirectiveLocation::ArgumentDefinition,
+            DirectiveLocation::Interface,
+            DirectiveLocation::Union,
+            DirectiveLocation::Enum,
+            DirectiveLocation::EnumValue,
+            DirectiveLocation::InputObject,
+            DirectiveLocation::InputFieldDefinition,
+            DirectiveLocation::VariableDefinition,
+        ]
+        .into_iter()
+        .find(|location| location.as_str() == s)
+        .ok_or(())
+    }
+}
@@ -26,7 +26,23 @@ pub fn build_namespace_schemas<S: crate::schema::SchemaContext>(
     schema: &crate::schema::Schema<S>,
 ) -> Result<HashMap<&S::Namespace, serde_json::Value>, Error> {
     let mut response = HashMap::new();
-    let request = crate::http::Request {
+    let request = introspection_request()?;
+    for ns in &schema.namespaces {
+        response.insert(ns, build_namespace_schema(ns, schema, &request)?);
+    }
+    Ok(response)
+}
+
+/// Generate GraphQL schema for a single namespace from given schema.
+pub fn build_schema_for_namespace<S: crate::schema::SchemaContext>(
+    ns: &S::Namespace,
+    schema: &crate::schema::Schema<S>,
+) -> Result<serde_json::Value, Error> {
+    build_namespace_schema(ns, schema, &introspection_request()?)
+}
+
+fn introspection_request() -> Result<crate::http::Request, Error> {
+    Ok(crate::http::Request {
         operation_name: None,
         query: {
             let query_str = include_str!("introspection_query.graphql");
@@ -35,11 +51,7 @@ pub fn build_namespace_schemas<S: crate::schema::SchemaContext>(
                 .map_err(|e| Error::ParseIntrospectionQuery(e.to_string()))?
         },
         variables: HashMap::new(),
-    };
-    for ns in &schema.namespaces {
-        response.insert(ns, build_namespace_schema(ns, schema, &request)?);
-    }
-    Ok(response)
+    })
 }
 
 /// Generate GraphQL schema for a given namespace
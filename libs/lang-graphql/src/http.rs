@@ -1,5 +1,6 @@
 use crate::ast::common as ast;
 use crate::ast::executable;
+use crate::ast::spanning::SourcePosition;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -12,8 +13,35 @@ use std::collections::HashMap;
 #[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
 pub struct RawRequest {
     pub operation_name: Option<ast::Name>,
-    pub query: String,
+    /// Absent if the request refers to a persisted query
+    pub query: Option<String>,
     pub variables: Option<HashMap<ast::Name, serde_json::Value>>,
+    pub extensions: Option<RequestExtensions>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
+pub struct RequestExtensions {
+    pub persisted_query: Option<PersistedQuery>,
+}
+
+/// A reference to a persisted query, as sent by Apollo clients, e.g.
+/// `{ "version": 1, "sha256Hash": "ecf4edb4..." }`
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
+pub struct PersistedQuery {
+    /// The hex encoded SHA-256 hash of the query
+    pub sha256_hash: String,
+}
+
+/// The body of a request, either a single request or a batch of requests sent
+/// as a JSON array. The requests are kept as JSON until each of them is
+/// parsed, so that a malformed request of a batch only fails itself.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum BatchRequest {
+    Batch(Vec<serde_json::Value>),
+    Single(serde_json::Value),
 }
 
 pub struct Request {
@@ -26,14 +54,36 @@ pub type VariableValues = HashMap<ast::Name, serde_json::Value>;
 
 #[derive(Serialize, Debug)]
 pub struct Extensions {
+    /// A machine readable code of the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
     /// Details of any error
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
     pub details: serde_json::Value,
 }
 
+/// A location in the GraphQL document that an error refers to
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<SourcePosition> for Location {
+    fn from(position: SourcePosition) -> Self {
+        Location {
+            line: position.line(),
+            column: position.column(),
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct GraphQLError {
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub locations: Option<Vec<Location>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extensions: Option<Extensions>,
@@ -43,10 +93,22 @@ pub struct GraphQLError {
 pub struct Response {
     #[serde(skip_serializing)]
     pub status_code: http::status::StatusCode,
+    /// Absent if an error was raised before execution began
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<IndexMap<ast::Alias, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub errors: Option<Vec<GraphQLError>>,
 }
 
+/// The response to a `BatchRequest`: the responses to the requests of a batch,
+/// in the order of the requests, or the response to a single request
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BatchResponse {
+    Batch(Vec<Response>),
+    Single(Response),
+}
+
 impl Response {
     pub fn ok(data: IndexMap<ast::Alias, serde_json::Value>) -> Self {
         Self {
@@ -83,6 +145,7 @@ impl Response {
             data: None,
             errors: Some(vec![GraphQLError {
                 message,
+                locations: None,
                 path: None,
                 extensions: None,
             }]),
@@ -116,6 +179,10 @@ impl Response {
         }
     }
 
+    pub fn error_message(message: String) -> Self {
+        Self::error_message_with_status(http::status::StatusCode::OK, message)
+    }
+
     pub fn does_contains_error(&self) -> bool {
         self.errors.is_some()
     }
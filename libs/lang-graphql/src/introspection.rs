@@ -143,8 +143,12 @@ pub fn schema_type<'s, S: schema::SchemaContext>(
                     ))
                 },
             ),
-            // TODO
-            "directives" => Ok(json::Value::Array(vec![])),
+            "directives" => {
+                let directives = schema.directives.values().collect::<Vec<_>>();
+                array_response(&directives, |directive| {
+                    directive_type(schema, namespace, directive, &field.selection_set)
+                })
+            }
             _ => Ok(json::Value::Null),
         }
     })
@@ -173,8 +177,7 @@ fn scalar_type<'s, S: schema::SchemaContext>(
             "kind" => Ok(json::to_value("SCALAR")?),
             "name" => Ok(json::to_value(&scalar.name)?),
             "description" => Ok(json::to_value(&scalar.description)?),
-            // TODO
-            "specifiedByURL" => Ok(json::Value::Null),
+            "specifiedByURL" => Ok(json::to_value(&scalar.specified_by_url)?),
             _ => Ok(json::Value::Null),
         }
     })
@@ -506,6 +509,49 @@ fn input_value<'s, S: schema::SchemaContext>(
     })
 }
 
+fn directive_type<'s, S: schema::SchemaContext>(
+    schema: &'s schema::Schema<S>,
+    namespace: &S::Namespace,
+    directive: &'s schema::Directive<S>,
+    selection_set: &normalized::SelectionSet<'s, S>,
+) -> Result<IndexMap<ast::Alias, json::Value>> {
+    selection_set.as_object_selection_set(|type_name, field, field_call| {
+        match field_call.name.as_str() {
+            "__typename" => Ok(json::to_value(type_name)?),
+            "name" => Ok(json::to_value(&directive.name)?),
+            "description" => Ok(json::to_value(&directive.description)?),
+            "locations" => Ok(json::to_value(&directive.locations)?),
+            "args" => {
+                let include_deprecated_name = mk_name!("includeDeprecated");
+                let include_deprecated = field_call
+                    .expected_argument(&include_deprecated_name)?
+                    .value
+                    .as_boolean()?;
+                let mut allowed_arguments = directive
+                    .arguments
+                    .values()
+                    .filter_map(|namespaced_input_field| {
+                        namespaced_input_field
+                            .get(namespace)
+                            .map(|v| v.0)
+                            .filter(|input_field| {
+                                let is_argument_deprecated =
+                                    input_field.deprecation_status.is_deprecated();
+                                !is_argument_deprecated || include_deprecated
+                            })
+                    })
+                    .collect::<Vec<_>>();
+                allowed_arguments.sort_by(|a1, a2| a1.name.cmp(&a2.name));
+                array_response(&allowed_arguments, |input_field| {
+                    input_value(schema, namespace, input_field, &field.selection_set)
+                })
+            }
+            "isRepeatable" => Ok(json::to_value(directive.is_repeatable)?),
+            _ => Ok(json::Value::Null),
+        }
+    })
+}
+
 fn enum_value<'s, S: schema::SchemaContext>(
     enum_value: &'s schema::EnumValue<S>,
     selection_set: &normalized::SelectionSet<'s, S>,
@@ -593,6 +639,23 @@ fn collect_accessible_types<S: schema::SchemaContext>(
         accessible_types.insert(mutation_type.clone());
         collect_accessible_types_(namespace, schema, mutation_type.clone(), accessible_types);
     }
+
+    // the types of the arguments of directives are accessible too
+    for directive in schema.directives.values() {
+        for namespaced_input_fields in directive.arguments.values() {
+            if let Some((input_field, _)) = namespaced_input_fields.get(namespace) {
+                let input_field_type_name = input_field.field_type.underlying_type();
+                if accessible_types.insert(input_field_type_name.clone()) {
+                    collect_accessible_types_(
+                        namespace,
+                        schema,
+                        input_field_type_name.clone(),
+                        accessible_types,
+                    )
+                }
+            }
+        }
+    }
 }
 
 // Recursively collect types available/accessible to a given `Namespace`.
@@ -271,13 +271,19 @@ impl<'a> Lexer<'a> {
                     b'}' => return Some(Ok(self.consume_punctuation_token(Punctuation::BraceR))),
                     b'.' => return Some(self.parse_spread()),
                     _ => {
-                        let parse_error = unsafe { from_utf8_unchecked(&self.bytes[self.ix..]) }
-                            .chars()
-                            .next()
-                            .map_or(Error::UnexpectedEndOfFile, |c| {
-                                Error::UnexpectedCharacter(c)
-                            });
-                        return Some(Err(Positioned::new(&self.get_position(), parse_error)));
+                        let position = self.get_position();
+                        let parse_error =
+                            match unsafe { from_utf8_unchecked(&self.bytes[self.ix..]) }
+                                .chars()
+                                .next()
+                            {
+                                None => Error::UnexpectedEndOfFile,
+                                Some(c) => {
+                                    self.skip_to(self.ix + c.len_utf8());
+                                    Error::UnexpectedCharacter(c)
+                                }
+                            };
+                        return Some(Err(Positioned::new(&position, parse_error)));
                     }
                 },
                 None => {
@@ -357,14 +363,24 @@ impl<'a> Lexer<'a> {
                 ))
             }
             Err((e, consumed)) => {
+                let (line, column) = (self.line, self.column);
                 self.seek(
                     consumed.line_breaks,
                     consumed.chars_without_further_line_break,
                 );
-                Err(Positioned::new(
-                    &self.get_position(),
-                    Error::InvalidString(e),
-                ))
+                let position = self.get_position();
+                // the rest of the invalid string is skipped: a block string
+                // can only be invalid when it isn't terminated, and a single
+                // line string ends with its line at the latest
+                (self.line, self.column) = (line, column);
+                let end = if self.bytes[self.ix..].starts_with(b"\"\"\"") {
+                    self.bytes.len()
+                } else {
+                    self.ix
+                        + consume_ascii_chars(&self.bytes[self.ix..], |c| c != b'\r' && c != b'\n')
+                };
+                self.skip_to(end);
+                Err(Positioned::new(&position, Error::InvalidString(e)))
             }
         }
     }
@@ -384,10 +400,14 @@ impl<'a> Lexer<'a> {
             }
             Err(e) => {
                 // TODO: need the accurate position from parse_number
-                Err(Positioned::new(
-                    &self.get_position(),
-                    Error::InvalidNumber(e),
-                ))
+                let position = self.get_position();
+                // the rest of the invalid number is skipped, along with the
+                // name that may follow it
+                let length = consume_ascii_chars(&self.bytes[self.ix..], |c| {
+                    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'-' | b'+')
+                });
+                self.skip_to(self.ix + length.max(1));
+                Err(Positioned::new(&position, Error::InvalidNumber(e)))
             }
         }
     }
@@ -408,6 +428,28 @@ impl<'a> Lexer<'a> {
             self.ix += 1;
         }
     }
+
+    /// Skips the input up to the given index, so that lexing resumes after an
+    /// invalid token
+    fn skip_to(&mut self, end: usize) {
+        let end = end.min(self.bytes.len());
+        while self.ix < end {
+            match self.bytes[self.ix] {
+                b'\n' => self.seek_line(),
+                b'\r' => {
+                    // line is incremented only if the next character is not '\n'
+                    if let Some(&b'\n') = self.bytes.get(self.ix + 1) {
+                    } else {
+                        self.seek_line()
+                    }
+                }
+                // the continuation bytes of a character
+                c if c & 0xC0 == 0x80 => {}
+                _ => self.seek_column(),
+            }
+            self.ix += 1;
+        }
+    }
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -550,6 +592,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resumes_after_errors() {
+        let mut lexer = Lexer::new("~ foo\n\"bar\nbaz 1.x qux");
+        assert_eq!(
+            lexer.read_next_token(),
+            Some(Err(positioned(
+                1,
+                1,
+                super::Error::UnexpectedCharacter('~')
+            )))
+        );
+        assert_eq!(
+            lexer.read_next_token(),
+            Some(Ok(spanned_token(1, 3, 1, 5, Token::from(mk_name!("foo")))))
+        );
+        // an unterminated string is skipped up to the end of its line
+        assert!(matches!(lexer.read_next_token(), Some(Err(_))));
+        assert_eq!(
+            lexer.read_next_token(),
+            Some(Ok(spanned_token(3, 1, 3, 3, Token::from(mk_name!("baz")))))
+        );
+        // an invalid number is skipped along with the characters it runs into
+        assert!(matches!(lexer.read_next_token(), Some(Err(_))));
+        assert_eq!(
+            lexer.read_next_token(),
+            Some(Ok(spanned_token(3, 9, 3, 11, Token::from(mk_name!("qux")))))
+        );
+        assert_eq!(lexer.read_next_token(), None);
+    }
+
     #[test]
     fn lexes_strings() {
         // ""
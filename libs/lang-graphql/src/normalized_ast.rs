@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use thiserror::Error;
 
 use crate::ast::common::{self as ast, TypeContainer, TypeName};
+use crate::ast::spanning::SourcePosition;
 use crate::schema::{NodeInfo, SchemaContext};
 use indexmap::IndexMap;
 
@@ -226,8 +227,11 @@ pub struct FieldCall<'s, S: SchemaContext> {
     pub info: NodeInfo<'s, S>,
     /// The arguments to the field, empty if no arguments are provided.
     pub arguments: IndexMap<ast::Name, InputField<'s, S>>,
-    /// The directives in the field selector.
-    pub directives: IndexMap<ast::Name, Directive<'s, S>>,
+    /// The directives in the field selector, in the order in which they are used.
+    pub directives: Vec<Directive<'s, S>>,
+    /// The position of the field selector in the document. When several
+    /// selectors are merged, this is the position of the first one.
+    pub location: SourcePosition,
 }
 
 impl<'s, S: SchemaContext> FieldCall<'s, S> {
@@ -357,6 +361,6 @@ impl<'s, S: SchemaContext> SelectionSet<'s, S> {
 pub struct Operation<'s, S: SchemaContext> {
     pub ty: ast::OperationType,
     pub name: Option<ast::Name>,
-    pub directives: IndexMap<ast::Name, Directive<'s, S>>,
+    pub directives: Vec<Directive<'s, S>>,
     pub selection_set: SelectionSet<'s, S>,
 }
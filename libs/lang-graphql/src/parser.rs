@@ -14,6 +14,9 @@ mod value;
 pub struct Parser<'a> {
     lexer: lexer::Lexer<'a>,
     next_token: Option<lexer::Result>,
+    /// The errors that parsing has recovered from so far, when parsing in
+    /// recovering mode
+    recovered_errors: Option<Vec<Positioned<Error>>>,
 }
 
 #[derive(Error, Debug, PartialEq, Clone)]
@@ -104,6 +107,8 @@ pub enum Keyword {
     Interface,
     Union,
     Schema,
+    Directive,
+    Repeatable,
 }
 
 impl Display for Keyword {
@@ -132,6 +137,8 @@ impl FromStr for Keyword {
             "interface" => Ok(Keyword::Interface),
             "union" => Ok(Keyword::Union),
             "schema" => Ok(Keyword::Schema),
+            "directive" => Ok(Keyword::Directive),
+            "repeatable" => Ok(Keyword::Repeatable),
             _ => Err(()),
         }
     }
@@ -156,6 +163,8 @@ impl Keyword {
             Keyword::Union => "union",
             Keyword::Schema => "schema",
             Keyword::Implements => "implements",
+            Keyword::Directive => "directive",
+            Keyword::Repeatable => "repeatable",
         }
     }
     pub fn expected_tokens(&self) -> &'static [ExpectedToken] {
@@ -176,6 +185,8 @@ impl Keyword {
             Keyword::Interface => &[ExpectedToken::Keyword(Keyword::Interface)],
             Keyword::Union => &[ExpectedToken::Keyword(Keyword::Union)],
             Keyword::Schema => &[ExpectedToken::Keyword(Keyword::Schema)],
+            Keyword::Directive => &[ExpectedToken::Keyword(Keyword::Directive)],
+            Keyword::Repeatable => &[ExpectedToken::Keyword(Keyword::Repeatable)],
         }
     }
 }
@@ -219,6 +230,7 @@ impl<'a> Parser<'a> {
         let mut parser = Parser {
             lexer,
             next_token: None,
+            recovered_errors: None,
         };
         parser.next_token();
         parser
@@ -253,6 +265,73 @@ impl<'a> Parser<'a> {
         current_token
     }
 
+    fn is_recovering(&self) -> bool {
+        self.recovered_errors.is_some()
+    }
+
+    /// Records an error that parsing recovers from. Errors which follow from
+    /// the previous one at the same position, e.g. the end of the file for
+    /// each of the selection sets which aren't closed, are left out.
+    fn recover_from(&mut self, error: Positioned<Error>) {
+        if let Some(errors) = &mut self.recovered_errors {
+            if errors
+                .last()
+                .map_or(true, |last_error| last_error.position != error.position)
+            {
+                errors.push(error);
+            }
+        }
+    }
+
+    /// Skips the tokens up to the next one in the current block which can
+    /// start a selection, i.e. a name or `...`. Returns `false`, having
+    /// skipped nothing past the `}` which closes the block, if there is none.
+    fn skip_to_selection(&mut self) -> bool {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return false,
+                Some(Ok(token)) => match token.item {
+                    lexer::Token::Name(_)
+                    | lexer::Token::Punctuation(lexer::Punctuation::Spread)
+                        if depth == 0 =>
+                    {
+                        return true
+                    }
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceL) => depth += 1,
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) if depth == 0 => {
+                        return false
+                    }
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) => depth -= 1,
+                    _ => {}
+                },
+                Some(Err(_)) => {}
+            }
+            self.next_token();
+        }
+    }
+
+    /// Skips the tokens up to the `}` which closes the current block, and
+    /// returns its position, or the position of the end of the file if the
+    /// block isn't closed
+    fn skip_block(&mut self) -> SourcePosition {
+        let mut depth = 0;
+        loop {
+            match self.next_token() {
+                None => return self.lexer.get_position(),
+                Some(Ok(token)) => match token.item {
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceL) => depth += 1,
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) if depth == 0 => {
+                        return token.end
+                    }
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) => depth -= 1,
+                    _ => {}
+                },
+                Some(Err(_)) => {}
+            }
+        }
+    }
+
     fn eof<T>(&self, expected_tokens: &'static [ExpectedToken]) -> Result<T> {
         Err(Positioned::new(
             &self.lexer.get_position(),
@@ -247,4 +247,75 @@ impl<'a> Parser<'a> {
         }
         Ok(ExecutableDocument { items })
     }
+
+    /// Parses an executable document, recovering from the errors in it, for
+    /// editor tooling. A definition which can't be parsed is skipped up to the
+    /// end of its block or up to the next definition, and a selection set is
+    /// cut short at its first invalid selection. Returns the document made of
+    /// whatever could be parsed, along with all the errors in the order of the
+    /// document.
+    pub fn parse_executable_document_with_recovery(
+        &mut self,
+    ) -> (ExecutableDocument, Vec<Positioned<super::Error>>) {
+        self.recovered_errors = Some(Vec::new());
+        let mut items = vec![];
+        while self.peek().is_some() {
+            match self.parse_executable_definition() {
+                Ok(definition) => items.push(definition),
+                Err(error) => {
+                    let depth = self.skipped_depth(&error);
+                    self.recover_from(error);
+                    self.skip_definition(depth);
+                }
+            }
+        }
+        let errors = self.recovered_errors.take().unwrap_or_default();
+        (ExecutableDocument { items }, errors)
+    }
+
+    /// The depth of the blocks that parsing is in after an error, when the
+    /// unexpected token was a brace which has been consumed already
+    fn skipped_depth(&mut self, error: &Positioned<super::Error>) -> i32 {
+        if matches!(self.peek(), Some(Ok(token)) if token.start == error.position) {
+            return 0;
+        }
+        match error.item.found {
+            super::TokenFound::Token(lexer::Token::Punctuation(lexer::Punctuation::BraceL)) => 1,
+            super::TokenFound::Token(lexer::Token::Punctuation(lexer::Punctuation::BraceR)) => -1,
+            _ => 0,
+        }
+    }
+
+    /// Skips the tokens of a definition which can't be parsed, starting at the
+    /// given depth of blocks, up to the end of its block, or up to the keyword
+    /// which starts the next definition
+    fn skip_definition(&mut self, mut depth: i32) {
+        loop {
+            if depth < 0 {
+                return;
+            }
+            if depth == 0
+                && [
+                    super::Keyword::Query,
+                    super::Keyword::Mutation,
+                    super::Keyword::Subscription,
+                    super::Keyword::Fragment,
+                ]
+                .iter()
+                .any(|keyword| self.is_next_token_keyword(keyword))
+            {
+                return;
+            }
+            match self.next_token() {
+                None => return,
+                Some(Ok(token)) => match token.item {
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceL) => depth += 1,
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) if depth <= 1 => return,
+                    lexer::Token::Punctuation(lexer::Punctuation::BraceR) => depth -= 1,
+                    _ => {}
+                },
+                Some(Err(_)) => {}
+            }
+        }
+    }
 }
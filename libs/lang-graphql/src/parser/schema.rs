@@ -1,6 +1,6 @@
 use super::Parser;
 use crate::{
-    ast::{common::Name, schema::*, spanning::*},
+    ast::{common::Name, schema::*, spanning::*, value::*},
     lexer,
 };
 
@@ -102,6 +102,16 @@ impl<'a> Parser<'a> {
 
     fn parse_const_arguments(
         &mut self,
+    ) -> super::Result<Option<Spanning<Vec<Spanning<KeyValue<ConstValue>>>>>> {
+        self.parse_optional_delimited_list(
+            lexer::Punctuation::ParenL,
+            lexer::Punctuation::ParenR,
+            |s| s.parse_key_value(|r| r.parse_const_value()),
+        )
+    }
+
+    fn parse_arguments_definition(
+        &mut self,
     ) -> super::Result<Option<Spanning<Vec<Spanning<InputValueDefinition>>>>> {
         self.parse_optional_delimited_list(
             lexer::Punctuation::ParenL,
@@ -143,7 +153,7 @@ impl<'a> Parser<'a> {
     fn parse_field_definition(&mut self) -> super::Result<Spanning<FieldDefinition>> {
         let description = self.parse_optional_string()?;
         let name = self.parse_name()?;
-        let arguments = self.parse_const_arguments()?;
+        let arguments = self.parse_arguments_definition()?;
         self.parse_punctuation(lexer::Punctuation::Colon)?;
         let field_type = self.parse_type()?;
         let directives = self.parse_const_directives()?;
@@ -404,6 +414,66 @@ impl<'a> Parser<'a> {
         ))
     }
 
+    /// Description? directive @ Name ArgumentsDefinition? repeatable? on DirectiveLocations
+    fn parse_directive_definition(
+        &mut self,
+        description: Option<Spanning<String>>,
+    ) -> super::Result<Spanning<DirectiveDefinition>> {
+        let start_position = get_start_position(
+            &description,
+            &self.parse_keyword(&super::Keyword::Directive)?,
+        );
+        self.parse_punctuation(lexer::Punctuation::At)?;
+        let name = self.parse_name()?;
+        let arguments = self.parse_arguments_definition()?;
+        let repeatable = self
+            .parse_optional(
+                |token| matches!(token, lexer::Token::Name(name) if name.is_keyword(&super::Keyword::Repeatable)),
+                |parser| parser.parse_keyword(&super::Keyword::Repeatable),
+            )?
+            .is_some();
+        let on = self.parse_keyword(&super::Keyword::On)?;
+        // The first location may be preceded by a pipe
+        self.parse_optional(
+            |token| token.is_punctuation(lexer::Punctuation::Pipe),
+            |parser| parser.parse_punctuation(lexer::Punctuation::Pipe),
+        )?;
+        let mut locations = vec![self.parse_directive_location()?];
+        locations.extend(self.parse_list(
+            |s| s.is_next_token(&lexer::Token::Punctuation(lexer::Punctuation::Pipe)),
+            |s| {
+                s.parse_punctuation(lexer::Punctuation::Pipe)?;
+                s.parse_directive_location()
+            },
+        )?);
+        Ok(Spanning::start_end(
+            start_position,
+            get_end_position(&on, &locations),
+            DirectiveDefinition {
+                description,
+                name,
+                arguments: arguments.map_or(Vec::new(), |v| v.item),
+                repeatable,
+                locations,
+            },
+        ))
+    }
+
+    fn parse_directive_location(&mut self) -> super::Result<Spanning<DirectiveLocation>> {
+        static EXPECTED_TOKENS: &[super::ExpectedToken] = &[super::ExpectedToken::Name];
+        let name = self.parse_name()?;
+        match name.item.as_str().parse() {
+            Ok(location) => Ok(Spanning::start_end(name.start, name.end, location)),
+            Err(()) => Err(Positioned::new(
+                &name.start,
+                super::Error::new(
+                    EXPECTED_TOKENS,
+                    super::TokenFound::Token(lexer::Token::Name(name.item)),
+                ),
+            )),
+        }
+    }
+
     fn parse_type_system_definition(&mut self) -> super::Result<Spanning<TypeSystemDefinition>> {
         static EXPECTED_TOKENS: &[super::ExpectedToken] = &[
             super::ExpectedToken::Keyword(super::Keyword::Schema),
@@ -413,6 +483,7 @@ impl<'a> Parser<'a> {
             super::ExpectedToken::Keyword(super::Keyword::Union),
             super::ExpectedToken::Keyword(super::Keyword::Enum),
             super::ExpectedToken::Keyword(super::Keyword::Input),
+            super::ExpectedToken::Keyword(super::Keyword::Directive),
         ];
         let description = self.parse_optional_string()?;
         match self.peek() {
@@ -445,6 +516,9 @@ impl<'a> Parser<'a> {
                             s.map(|d| TypeSystemDefinition::Type(TypeDefinition::InputObject(d)))
                         })
                     }
+                    Ok(super::Keyword::Directive) => self
+                        .parse_directive_definition(description)
+                        .map(|s| s.map(TypeSystemDefinition::Directive)),
                     _ => Err(Positioned::new(
                         &token.start,
                         super::Error::new(
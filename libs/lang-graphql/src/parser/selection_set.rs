@@ -38,6 +38,9 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_selection_set(&mut self) -> super::Result<Spanning<SelectionSet>> {
+        if self.is_recovering() {
+            return self.parse_selection_set_with_recovery();
+        }
         self.parse_delimited_list(
             lexer::Punctuation::BraceL,
             lexer::Punctuation::BraceR,
@@ -46,6 +49,54 @@ impl<'a> Parser<'a> {
         .map(|r| r.map(|l| SelectionSet { items: l }))
     }
 
+    /// Parses a selection set, skipping each invalid selection up to the next
+    /// token which can start one
+    fn parse_selection_set_with_recovery(&mut self) -> super::Result<Spanning<SelectionSet>> {
+        let start = self.parse_punctuation(lexer::Punctuation::BraceL)?;
+        let mut items = vec![];
+        let end = loop {
+            if self.is_next_token(&lexer::Token::Punctuation(lexer::Punctuation::BraceR)) {
+                break self.parse_punctuation(lexer::Punctuation::BraceR)?.end;
+            }
+            match self.parse_selection() {
+                Ok(selection) => items.push(selection),
+                Err(error) => {
+                    let position = error.position;
+                    let found_brace = matches!(
+                        error.item.found,
+                        super::TokenFound::Token(lexer::Token::Punctuation(
+                            lexer::Punctuation::BraceR
+                        ))
+                    );
+                    self.recover_from(error);
+                    let consumed = match self.peek() {
+                        Some(Ok(token)) => token.start != position,
+                        Some(Err(error)) => error.position != position,
+                        None => true,
+                    };
+                    // the unexpected token may be the `}` which closes the
+                    // selection set, if it is consumed already
+                    if found_brace && consumed {
+                        break position;
+                    }
+                    // the unexpected token is skipped, so that parsing
+                    // resumes after it
+                    if !found_brace && !consumed {
+                        self.next_token();
+                    }
+                    if !self.skip_to_selection() {
+                        break self.skip_block();
+                    }
+                }
+            }
+        };
+        Ok(Spanning::start_end(
+            start.start,
+            end,
+            SelectionSet { items },
+        ))
+    }
+
     // These 'inline' attributes contribute to about 10% improvement in parse times
     #[inline(always)]
     fn parse_field(&mut self) -> super::Result<Spanning<Field>> {
@@ -68,10 +119,17 @@ impl<'a> Parser<'a> {
             } else {
                 None
             };
+        // the field spans from its alias, if any, to the last of its parts
+        let start = alias.as_ref().map_or(name.start, |alias| alias.start);
+        let end = selection_set
+            .as_ref()
+            .map(|selection_set| selection_set.end)
+            .or_else(|| directives.last().map(|directive| directive.end))
+            .or_else(|| arguments.as_ref().map(|arguments| arguments.end))
+            .unwrap_or(name.end);
         Ok(Spanning::start_end(
-            // TODO
-            name.start,
-            name.end,
+            start,
+            end,
             Field {
                 alias,
                 name,
@@ -1,5 +1,6 @@
 use crate::ast::common as ast;
 use crate::ast::common::TypeName;
+use crate::ast::schema::DirectiveLocation;
 use crate::ast::value as gql;
 use crate::mk_name;
 
@@ -108,6 +109,16 @@ pub trait SchemaContext: std::fmt::Debug + Clone + PartialEq + Serialize {
 
     fn get_schema_entry_point(&self) -> EntryPoint<Self>;
 
+    // Builds the directives that the schema defines in addition to the ones in the spec
+    // (@skip, @include, @deprecated and @specifiedBy). Any types referenced by the arguments
+    // of the directives have to be registered with the builder.
+    fn build_directives(
+        &self,
+        _builder: &mut Builder<Self>,
+    ) -> std::result::Result<Vec<Directive<Self>>, Self::SchemaError> {
+        Ok(Vec::new())
+    }
+
     // type ScalarValue: std::fmt::Debug;
 }
 
@@ -366,6 +377,9 @@ impl<S: SchemaContext> InputField<S> {
 pub struct Scalar {
     pub name: ast::TypeName,
     pub description: Option<String>,
+    /// The URL of the specification of the scalar, set with @specifiedBy
+    #[serde(default)]
+    pub specified_by_url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -450,6 +464,39 @@ impl<S: SchemaContext> Interface<S> {
     }
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Directive<S: SchemaContext> {
+    pub name: ast::Name,
+    pub description: Option<String>,
+    /// The locations in a document where the directive can be used
+    pub locations: Vec<DirectiveLocation>,
+    pub arguments: HashMap<ast::Name, Namespaced<S, InputField<S>>>,
+    /// Whether the directive can be used more than once at a location
+    pub is_repeatable: bool,
+}
+
+impl<S: SchemaContext> Directive<S> {
+    pub fn new(
+        name: ast::Name,
+        description: Option<String>,
+        locations: Vec<DirectiveLocation>,
+        arguments: HashMap<ast::Name, Namespaced<S, InputField<S>>>,
+        is_repeatable: bool,
+    ) -> Self {
+        Directive {
+            name,
+            description,
+            locations,
+            arguments,
+            is_repeatable,
+        }
+    }
+
+    pub fn is_allowed_on(&self, location: DirectiveLocation) -> bool {
+        self.locations.contains(&location)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum TypeInfo<S: SchemaContext> {
     Scalar(Scalar),
@@ -519,6 +566,7 @@ pub struct Schema<S: SchemaContext> {
     pub mutation_type: Option<ast::TypeName>,
     pub subscription_type: Option<ast::TypeName>,
     pub namespaces: HashSet<S::Namespace>,
+    pub directives: BTreeMap<ast::Name, Directive<S>>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -530,6 +578,10 @@ impl<S: SchemaContext> Schema<S> {
     pub fn get_type(&self, type_name: &ast::TypeName) -> Option<&TypeInfo<S>> {
         self.types.get(type_name)
     }
+
+    pub fn get_directive(&self, directive_name: &ast::Name) -> Option<&Directive<S>> {
+        self.directives.get(directive_name)
+    }
 }
 
 impl<S: SchemaContext> SchemaWithVersion<S> {
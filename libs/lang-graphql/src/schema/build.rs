@@ -4,7 +4,7 @@ use thiserror::Error;
 
 use crate::ast::common as ast;
 use crate::ast::schema as sdl;
-use crate::ast::spanning::Positioned;
+use crate::ast::spanning::{Positioned, Spanning};
 use crate::parser;
 
 #[derive(Error, Debug, Clone)]
@@ -17,6 +17,9 @@ pub enum Error {
 
     #[error("multiple definitions of graphql type: {0:}")]
     ConflictingGraphQlType(ast::TypeName),
+
+    #[error("multiple definitions of directive: {0:}")]
+    ConflictingDirective(ast::Name),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -30,6 +33,7 @@ where
         .parse_schema_document()
         .map_err(Error::InternalParseError)?;
     let mut types = BTreeMap::new();
+    let mut directives = BTreeMap::new();
     let mut introspection_root_fields = HashMap::new();
     let mut builder = Builder {
         registered_types: HashSet::new(),
@@ -55,7 +59,14 @@ where
                 }
             }
             sdl::TypeSystemDefinition::Schema(_) => {}
-            sdl::TypeSystemDefinition::Directive(_) => {}
+            sdl::TypeSystemDefinition::Directive(directive_definition) => {
+                let directive = convert_directive_definition(
+                    &mut builder,
+                    |_, t| RegisteredTypeName(t),
+                    &directive_definition,
+                )?;
+                directives.insert(directive.name.clone(), directive);
+            }
         }
     }
 
@@ -71,6 +82,14 @@ where
         .subscription
         .map(|type_id| builder.register_type(type_id));
 
+    // the directives of the schema can't shadow the ones in the spec
+    for directive in s.build_directives(&mut builder)? {
+        if directives.contains_key(&directive.name) {
+            return Err(Error::ConflictingDirective(directive.name).into());
+        }
+        directives.insert(directive.name.clone(), directive);
+    }
+
     while !builder.registered_types.is_empty() {
         let types_to_be_generated = builder
             .registered_types
@@ -106,9 +125,74 @@ where
         mutation_type: mutation_root_name.map(|v| v.0),
         subscription_type: subscription_root_name.map(|v| v.0),
         namespaces: builder.registered_namespaces,
+        directives,
     })
 }
 
+pub fn convert_directive_definition<S, F>(
+    builder: &mut Builder<S>,
+    mut register_type_name: F,
+    definition: &sdl::DirectiveDefinition,
+) -> Result<Directive<S>>
+where
+    S: SchemaContext,
+    F: FnMut(&mut Builder<S>, ast::TypeName) -> RegisteredTypeName,
+{
+    let mut arguments = HashMap::new();
+    for argument_definition in &definition.arguments {
+        let argument_name = &argument_definition.item.name.item;
+        let normalized_argument_definition = convert_input_value_definition(
+            builder,
+            &mut register_type_name,
+            &argument_definition.item,
+        )?;
+        if arguments
+            .insert(
+                argument_name.clone(),
+                builder.allow_all_namespaced(
+                    normalized_argument_definition,
+                    S::introspection_namespace_node(),
+                ),
+            )
+            .is_some()
+        {
+            // TODO, throw an error
+        }
+    }
+    Ok(Directive::new(
+        definition.name.item.clone(),
+        definition
+            .description
+            .as_ref()
+            .map(|description| description.item.clone()),
+        definition
+            .locations
+            .iter()
+            .map(|location| location.item)
+            .collect(),
+        arguments,
+        definition.repeatable,
+    ))
+}
+
+/// The deprecation status set with @deprecated in the definition of a field, an argument, an
+/// input field or an enum value
+fn deprecation_status(directives: &[Spanning<sdl::ConstDirective>]) -> DeprecationStatus {
+    match directives
+        .iter()
+        .find(|directive| directive.item.name.item.as_str() == "deprecated")
+    {
+        None => DeprecationStatus::NotDeprecated,
+        Some(directive) => match directive.item.argument("reason") {
+            Some(gql::ConstValue::SimpleValue(gql::SimpleValue::String(reason))) => {
+                DeprecationStatus::new_deprecated(Some(reason))
+            }
+            // the default value of the `reason` argument
+            _ => DeprecationStatus::new_deprecated(Some("No longer supported")),
+        },
+    }
+}
+
 pub fn convert_type_definition<S, F>(
     builder: &mut Builder<S>,
     register_type_name: F,
@@ -145,12 +229,21 @@ where
 }
 
 fn convert_scalar_type_definition(definition: &sdl::ScalarTypeDefinition) -> Result<Scalar> {
+    let specified_by_url = definition
+        .directives
+        .iter()
+        .find(|directive| directive.item.name.item.as_str() == "specifiedBy")
+        .and_then(|directive| match directive.item.argument("url") {
+            Some(gql::ConstValue::SimpleValue(gql::SimpleValue::String(url))) => Some(url.clone()),
+            _ => None,
+        });
     Ok(Scalar {
         name: ast::TypeName(definition.name.item.clone()),
         description: definition
             .description
             .as_ref()
             .map(|description| description.item.clone()),
+        specified_by_url,
     })
 }
 
@@ -168,7 +261,7 @@ fn convert_enum_type_definition<S: SchemaContext>(
                 .description
                 .as_ref()
                 .map(|d| d.item.clone()),
-            deprecation_status: DeprecationStatus::default(),
+            deprecation_status: deprecation_status(&enum_value_definition.directives),
             info: S::introspection_node(),
         };
         // TODO: throw error
@@ -235,7 +328,7 @@ where
             .clone()
             .map(|t| register_type_name(builder, t)),
         arguments,
-        DeprecationStatus::default(),
+        deprecation_status(&definition.directives),
     ))
 }
 
@@ -402,7 +495,7 @@ where
             .default_value
             .as_ref()
             .map(|default_value| default_value.item.clone()),
-        DeprecationStatus::default(),
+        deprecation_status(&definition.directives),
     ))
 }
 
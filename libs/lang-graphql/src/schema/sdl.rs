@@ -11,6 +11,7 @@ use crate::parser;
 #[derive(Debug, PartialEq, Clone)]
 pub struct SDL {
     types: HashMap<ast::Name, sdl::TypeDefinition>,
+    directives: HashMap<ast::Name, sdl::DirectiveDefinition>,
     query: ast::Name,
     mutation: Option<ast::Name>,
     subscription: Option<ast::Name>,
@@ -44,6 +45,7 @@ impl SDL {
             .parse_schema_document()
             .map_err(SDLError::ParseFailure)?;
         let mut type_definitions = HashMap::new();
+        let mut directive_definitions = HashMap::new();
         let mut schema_definition = None;
         for definition in document.definitions {
             match definition.item {
@@ -61,7 +63,15 @@ impl SDL {
                         return Err(SDLError::DuplicateDefinitions(type_name));
                     };
                 }
-                sdl::TypeSystemDefinition::Directive(_) => {}
+                sdl::TypeSystemDefinition::Directive(directive_definition) => {
+                    let directive_name = directive_definition.name.item.clone();
+                    if directive_definitions
+                        .insert(directive_name.clone(), directive_definition)
+                        .is_some()
+                    {
+                        return Err(SDLError::DuplicateDirectiveDefinitions(directive_name));
+                    };
+                }
             }
         }
         let mut root_definitions = HashMap::new();
@@ -105,6 +115,7 @@ impl SDL {
         };
         Ok(SDL {
             types: type_definitions,
+            directives: directive_definitions,
             query: query_root,
             mutation: mutation_root,
             subscription: subscription_root,
@@ -122,6 +133,7 @@ pub enum SDLError {
     Internal(build::Error),
     TypeNotDefined(ast::Name),
     DuplicateDefinitions(ast::Name),
+    DuplicateDirectiveDefinitions(ast::Name),
     MultipleSchemaDefinitions,
     ExpectedObjectDefinition(ast::Name),
 }
@@ -169,18 +181,26 @@ impl SchemaContext for SDL {
             .ok_or_else(|| SDLError::TypeNotDefined(type_id.clone()))?;
         Ok(build::convert_type_definition(
             builder,
-            |builder_in_closure, type_name| match type_name.as_str() {
-                "String" => RegisteredTypeName::string(),
-                "Int" => RegisteredTypeName::int(),
-                "Float" => RegisteredTypeName::float(),
-                "Boolean" => RegisteredTypeName::boolean(),
-                "ID" => RegisteredTypeName::id(),
-                _ => builder_in_closure.register_type(type_name.0),
-            },
+            register_type_name,
             definition,
         )?)
     }
 
+    fn build_directives(
+        &self,
+        builder: &mut Builder<Self>,
+    ) -> std::result::Result<Vec<Directive<Self>>, SDLError> {
+        let mut directives = Vec::new();
+        for definition in self.directives.values() {
+            directives.push(build::convert_directive_definition(
+                builder,
+                register_type_name,
+                definition,
+            )?);
+        }
+        Ok(directives)
+    }
+
     fn get_schema_entry_point(&self) -> EntryPoint<Self> {
         EntryPoint {
             query: self.query.clone(),
@@ -189,3 +209,14 @@ impl SchemaContext for SDL {
         }
     }
 }
+
+fn register_type_name(builder: &mut Builder<SDL>, type_name: ast::TypeName) -> RegisteredTypeName {
+    match type_name.as_str() {
+        "String" => RegisteredTypeName::string(),
+        "Int" => RegisteredTypeName::int(),
+        "Float" => RegisteredTypeName::float(),
+        "Boolean" => RegisteredTypeName::boolean(),
+        "ID" => RegisteredTypeName::id(),
+        _ => builder.register_type(type_name.0),
+    }
+}
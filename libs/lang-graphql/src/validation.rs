@@ -1,3 +1,4 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -8,43 +9,56 @@ use crate::normalized_ast as normalized;
 use crate::{http, schema};
 
 mod collect;
+mod directives;
 mod error;
 pub mod input;
 pub mod selection_set;
+mod usages;
 
 pub use error::*;
-use indexmap::IndexMap;
 use indexmap::IndexSet;
 
+/// Validates the operation of the request against the schema and normalizes
+/// it. All the errors found in the document are returned, in the order of
+/// their locations. The operation is only validated when the document itself,
+/// that is its definitions, fragments and variables, is valid.
 pub fn normalize_request<'s, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
     request: &http::Request,
-) -> Result<normalized::Operation<'s, S>> {
+) -> core::result::Result<normalized::Operation<'s, S>, Errors> {
+    let mut errors = Vec::new();
     let mut fragments = HashMap::new();
     let mut operations = HashMap::new();
     for definition in &request.query.items {
         match &definition.item {
             executable::ExecutableDefinition::Operation(operation) => {
-                if operations
-                    .insert(operation.name.as_ref().map(|n| &n.item), operation)
-                    .is_some()
-                {
-                    match &operation.name {
-                        Some(operation_name) => {
-                            return Err(Error::DuplicateOperationDefinitions {
+                match operations.entry(operation.name.as_ref().map(|n| &n.item)) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(operation);
+                    }
+                    Entry::Occupied(_) => {
+                        let error = match &operation.name {
+                            Some(operation_name) => Error::DuplicateOperationDefinitions {
                                 operation_name: operation_name.item.clone(),
-                            })
-                        }
-                        None => return Err(Error::AnonymousOperationMustBeUnique),
+                            },
+                            None => Error::AnonymousOperationMustBeUnique,
+                        };
+                        errors.push(error.at(definition.start));
                     }
                 }
             }
             executable::ExecutableDefinition::Fragment(fragment) => {
-                if fragments.insert(&fragment.name.item, fragment).is_some() {
-                    return Err(Error::DuplicateFragmentDefinitions {
-                        fragment_name: fragment.name.item.clone(),
-                    });
+                match fragments.entry(&fragment.name.item) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(fragment);
+                    }
+                    Entry::Occupied(_) => errors.push(
+                        Error::DuplicateFragmentDefinitions {
+                            fragment_name: fragment.name.item.clone(),
+                        }
+                        .at(definition.start),
+                    ),
                 }
             }
         }
@@ -52,25 +66,34 @@ pub fn normalize_request<'s, S: schema::SchemaContext>(
     {
         let mut visited_fragments = HashSet::new();
         let mut fragment_path = IndexSet::new();
-        for (fragment_name, fragment_definition) in &fragments {
-            if !visited_fragments.contains(fragment_name) {
-                check_fragment_cycles(
-                    &mut visited_fragments,
-                    &mut fragment_path,
-                    &fragments,
-                    &fragment_definition.selection_set.item,
-                )?
+        // in the order of the document, so that the same cycle is reported
+        // each time
+        for definition in &request.query.items {
+            if let executable::ExecutableDefinition::Fragment(fragment) = &definition.item {
+                if !visited_fragments.contains(&fragment.name.item) {
+                    check_fragment_cycles(
+                        &mut visited_fragments,
+                        &mut fragment_path,
+                        &fragments,
+                        &fragment.selection_set.item,
+                        &mut errors,
+                    )
+                }
             }
         }
     }
-    // TODO, lots of validation cases to be handled here
+    usages::check_usages(&request.query, &fragments, &mut errors);
+    if !errors.is_empty() {
+        return Err(Errors::new(errors));
+    }
     let operation_name = request.operation_name.as_ref();
     if let Some(&operation) = operations.get(&operation_name) {
         normalize_operation(namespace, schema, &fragments, operation, &request.variables)
     } else if let Some(operation_name) = operation_name {
-        Err(Error::OperationNotFound {
+        Err(Errors::new(vec![Error::OperationNotFound {
             operation_name: operation_name.clone(),
-        })
+        }
+        .at_locations(vec![])]))
     } else if operations.len() == 1 {
         normalize_operation(
             namespace,
@@ -80,16 +103,21 @@ pub fn normalize_request<'s, S: schema::SchemaContext>(
             &request.variables,
         )
     } else {
-        Err(Error::AnonymousOperationNotFound)
+        Err(Errors::new(vec![
+            Error::AnonymousOperationNotFound.at_locations(vec![])
+        ]))
     }
 }
 
+/// Reports the fragment spreads which form a cycle, and the ones of fragments
+/// which aren't defined. Fragments are only visited once.
 pub fn check_fragment_cycles<'q>(
     all_referenced_fragments: &mut HashSet<&'q ast::Name>,
     fragment_path: &mut IndexSet<&'q ast::Name>,
     fragments: &HashMap<&'q ast::Name, &'q executable::FragmentDefinition>,
     current_selection_set: &'q executable::SelectionSet,
-) -> Result<()> {
+    errors: &mut Vec<PositionedError>,
+) {
     for selection in &current_selection_set.items {
         match &selection.item {
             executable::Selection::Field(field) => {
@@ -99,7 +127,8 @@ pub fn check_fragment_cycles<'q>(
                         fragment_path,
                         fragments,
                         &field_selection_set.item,
-                    )?
+                        errors,
+                    )
                 }
             }
             executable::Selection::FragmentSpread(spread) => {
@@ -107,19 +136,26 @@ pub fn check_fragment_cycles<'q>(
                 if fragment_path.contains(fragment_name) {
                     let mut path = fragment_path.iter().cloned().cloned().collect::<Vec<_>>();
                     path.push(fragment_name.clone());
-                    return Err(Error::CycleDetected(path));
+                    errors.push(Error::CycleDetected(path).at(selection.start));
+                    continue;
                 }
+                // the cycles through a fragment which is already visited are
+                // already reported
+                if !all_referenced_fragments.insert(fragment_name) {
+                    continue;
+                }
+                let Some(fragment_definition) = fragments.get(&spread.fragment_name.item) else {
+                    errors.push(Error::UnknownFragment(fragment_name.clone()).at(selection.start));
+                    continue;
+                };
                 fragment_path.insert(fragment_name);
-                all_referenced_fragments.insert(fragment_name);
-                let fragment_definition = fragments
-                    .get(&spread.fragment_name.item)
-                    .ok_or_else(|| Error::UnknownFragment(fragment_name.clone()))?;
                 check_fragment_cycles(
                     all_referenced_fragments,
                     fragment_path,
                     fragments,
                     &fragment_definition.selection_set.item,
-                )?;
+                    errors,
+                );
                 fragment_path.pop();
             }
             executable::Selection::InlineFragment(inline_fragment) => check_fragment_cycles(
@@ -127,10 +163,10 @@ pub fn check_fragment_cycles<'q>(
                 fragment_path,
                 fragments,
                 &inline_fragment.selection_set.item,
-            )?,
+                errors,
+            ),
         }
     }
-    Ok(())
 }
 
 pub fn normalize_operation<'q, 's, S: schema::SchemaContext>(
@@ -139,55 +175,89 @@ pub fn normalize_operation<'q, 's, S: schema::SchemaContext>(
     fragments: &HashMap<&'q ast::Name, &'q executable::FragmentDefinition>,
     operation: &'q executable::OperationDefinition,
     variable_values: &'q VariableValues,
-) -> Result<normalized::Operation<'s, S>> {
+) -> core::result::Result<normalized::Operation<'s, S>, Errors> {
+    let mut errors = Vec::new();
     let mut variables = HashMap::new();
     if let Some(variable_definitions) = &operation.variable_definitions {
         for definition in &variable_definitions.item {
+            let location = definition.start;
             let definition = &definition.item;
             let variable_name = &definition.name.item;
             let variable_base_type = definition.var_type.item.underlying_type();
             let type_info = schema
                 .get_type(variable_base_type)
-                .ok_or_else(|| Error::UnknownType(variable_base_type.clone()))?
-                .as_input_type()
-                .ok_or_else(|| Error::NotInputType {
-                    variable_name: variable_name.clone(),
-                    type_name: variable_base_type.clone(),
-                })?;
-            if variables
-                .insert(variable_name, (definition, type_info))
-                .is_some()
-            {
-                return Err(Error::DuplicateVariableDeclarations {
-                    variable_name: variable_name.clone(),
+                .ok_or_else(|| Error::UnknownType(variable_base_type.clone()))
+                .and_then(|type_info| {
+                    type_info
+                        .as_input_type()
+                        .ok_or_else(|| Error::NotInputType {
+                            variable_name: variable_name.clone(),
+                            type_name: variable_base_type.clone(),
+                        })
                 });
+            match type_info {
+                Ok(type_info) => {
+                    if variables
+                        .insert(variable_name, (definition, type_info))
+                        .is_some()
+                    {
+                        errors.push(
+                            Error::DuplicateVariableDeclarations {
+                                variable_name: variable_name.clone(),
+                            }
+                            .at(location),
+                        );
+                    }
+                }
+                Err(error) => errors.push(error.at(location)),
             }
         }
     }
+    // the usages of the variables can't be validated without their definitions
+    if !errors.is_empty() {
+        return Err(Errors::new(errors));
+    }
     let variables_context = input::value::Variables {
         definitions: &variables,
         values: variable_values,
     };
+    let operation_directives = directives::normalize_directives(
+        namespace,
+        schema,
+        &variables_context,
+        operation.ty.into(),
+        &operation.directives,
+        &mut errors,
+    );
 
+    let location = operation.selection_set.start;
     let selection_set_type_name = match operation.ty {
-        ast::OperationType::Query => &schema.query_type,
+        ast::OperationType::Query => Ok(&schema.query_type),
         ast::OperationType::Mutation => schema
             .mutation_type
             .as_ref()
-            .ok_or(Error::NoMutationsAreDefined)?,
+            .ok_or(Error::NoMutationsAreDefined),
         ast::OperationType::Subscription => schema
-            .mutation_type
+            .subscription_type
             .as_ref()
-            .ok_or(Error::NoSubscriptionsAreDefined)?,
+            .ok_or(Error::NoSubscriptionsAreDefined),
+    };
+    let selection_set_type_info = selection_set_type_name.and_then(|selection_set_type_name| {
+        schema
+            .get_type(selection_set_type_name)
+            .ok_or_else(|| Error::InternalTypeNotFound {
+                type_name: selection_set_type_name.clone(),
+            })?
+            .to_selectable_type()
+            .ok_or(Error::InternalSelectionRootIsNotObject)
+    });
+    let selection_set_type_info = match selection_set_type_info {
+        Ok(selection_set_type_info) => selection_set_type_info,
+        Err(error) => {
+            errors.push(error.at(location));
+            return Err(Errors::new(errors));
+        }
     };
-
-    let selection_set_type_info = schema
-        .get_type(selection_set_type_name)
-        .ok_or_else(|| Error::InternalTypeNotFound {
-            type_name: selection_set_type_name.clone(),
-        })?
-        .to_selectable_type()
-        .ok_or(Error::InternalSelectionRootIsNotObject)?;
 
     let normalized_selection_set = selection_set::normalize_selection_set(
         namespace,
@@ -196,11 +266,32 @@ pub fn normalize_operation<'q, 's, S: schema::SchemaContext>(
         &variables_context,
         &selection_set_type_info,
         &operation.selection_set.item,
-    )?;
+        &mut errors,
+    );
+    if operation.ty == ast::OperationType::Subscription
+        && normalized_selection_set.fields.len() != 1
+    {
+        errors.push(
+            Error::SubscriptionMustHaveSingleRootField {
+                root_field_count: normalized_selection_set.fields.len(),
+            }
+            .at_locations(
+                normalized_selection_set
+                    .fields
+                    .values()
+                    .flat_map(|field| field.field_calls.values())
+                    .map(|field_call| field_call.location)
+                    .collect(),
+            ),
+        );
+    }
+    if !errors.is_empty() {
+        return Err(Errors::new(errors));
+    }
     Ok(normalized::Operation {
         ty: operation.ty,
         name: operation.name.as_ref().map(|name| name.item.clone()),
-        directives: IndexMap::new(),
+        directives: operation_directives,
         selection_set: normalized_selection_set,
     })
 }
@@ -2,10 +2,14 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use super::directives;
 use super::error::*;
+use super::input;
 use crate::ast::common as ast;
 use crate::ast::executable;
+use crate::ast::schema::DirectiveLocation;
 use crate::ast::spanning;
+use crate::normalized_ast as normalized;
 use crate::schema;
 
 // { # vec (typename, field)
@@ -101,6 +105,30 @@ impl<S: schema::SchemaContext> schema::TypeInfo<S> {
     }
 }
 
+/// A field of a scalar or an enum type can't have a selection set, and a field
+/// of an object, interface or union type must have one
+fn check_leaf_field_selection<S: schema::SchemaContext>(
+    schema: &schema::Schema<S>,
+    field: &executable::Field,
+    field_info: &FieldInfo<'_, S>,
+) -> Result<()> {
+    let field_type_name = field_info.generic.field_type.underlying_type();
+    let is_composite = get_type_info(schema, field_type_name)?
+        .to_selectable_type()
+        .is_some();
+    match (is_composite, &field.selection_set) {
+        (false, Some(_)) => Err(Error::SelectionOnNonCompositeType {
+            field_name: field.name.item.clone(),
+            type_name: field_type_name.clone(),
+        }),
+        (true, None) => Err(Error::NoSelectionOnCompositeType {
+            field_name: field.name.item.clone(),
+            type_name: field_type_name.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
 fn get_type_info<'s, S: schema::SchemaContext>(
     schema: &'s schema::Schema<S>,
     type_name: &ast::TypeName,
@@ -121,7 +149,13 @@ pub(super) struct CollectedField<'q, 's, S: schema::SchemaContext> {
     pub info: FieldInfo<'s, S>,
     pub field_path: Vec<&'s ast::TypeName>,
     pub reachable: bool,
+    /// Whether the field is left in by @skip and @include, the fields they
+    /// exclude are still validated but aren't part of the normalized AST
+    pub included: bool,
     pub field: &'q executable::Field,
+    /// The directives of the field, other than the invalid ones
+    pub directives: Vec<normalized::Directive<'s, S>>,
+    pub location: spanning::SourcePosition,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -129,6 +163,7 @@ fn collect_fields_from_fragment<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
     fragments: &HashMap<&'q ast::Name, &'q executable::FragmentDefinition>,
+    variables: &input::value::Variables<'q, 's, S>,
 
     field_path: &Vec<&'s ast::TypeName>,
     selection_type: &SelectableType<'s, S>,
@@ -136,18 +171,25 @@ fn collect_fields_from_fragment<'q, 's, S: schema::SchemaContext>(
     selection_set_reachability: &HashSet<&'s ast::TypeName>,
     fragment_selection_type: &SelectableType<'s, S>,
     fragment_selection_set: &'q executable::SelectionSet,
+    location: spanning::SourcePosition,
+    included: bool,
     fields: &mut Vec<CollectedField<'q, 's, S>>,
-) -> Result<()> {
+    errors: &mut Vec<PositionedError>,
+) {
     let common_types: HashSet<&ast::TypeName> = selection_type
         .possible_types
         .intersection(&fragment_selection_type.possible_types)
         .copied()
         .collect();
     if common_types.is_empty() {
-        return Err(Error::FragmentCannotBeSpread {
-            selection_type: selection_type.type_name.clone(),
-            fragment_type: fragment_selection_type.type_name.clone(),
-        });
+        errors.push(
+            Error::FragmentCannotBeSpread {
+                selection_type: selection_type.type_name.clone(),
+                fragment_type: fragment_selection_type.type_name.clone(),
+            }
+            .at(location),
+        );
+        return;
     }
     let fragment_reachability = selection_set_reachability
         .intersection(&common_types)
@@ -195,26 +237,36 @@ fn collect_fields_from_fragment<'q, 's, S: schema::SchemaContext>(
         namespace,
         schema,
         fragments,
+        variables,
         &fragment_field_path,
         fragment_selection_type,
         &fragment_reachability,
         fragment_to_be_coerced_as,
         &fragment_selection_set.items,
+        included,
         fields,
-    )?;
-    Ok(())
+        errors,
+    );
 }
 
+/// Collects the fields of a selection set, including the ones of the fragments
+/// that it spreads. The errors found along the way are added to `errors`, and
+/// the fields which have them are left out when they can't be collected. The
+/// fields which are skipped, or which are in a selection set that isn't
+/// `included`, are collected as well so that they are validated.
 #[allow(clippy::too_many_arguments)]
 pub(super) fn collect_fields<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
     fragments: &HashMap<&'q ast::Name, &'q executable::FragmentDefinition>,
+    variables: &input::value::Variables<'q, 's, S>,
     field_path: &Vec<&'s ast::TypeName>,
     selection_type: &SelectableType<'s, S>,
     selection_set: &'q [spanning::Spanning<executable::Selection>],
+    included: bool,
     fields: &mut Vec<CollectedField<'q, 's, S>>,
-) -> Result<()> {
+    errors: &mut Vec<PositionedError>,
+) {
     // let selection_set_field_path = SelectionSetfield_path::Unconditional {
     //     root_type: selection_type,
     //     reachable_types: selection_type.possible_types.clone(),
@@ -223,12 +275,15 @@ pub(super) fn collect_fields<'q, 's, S: schema::SchemaContext>(
         namespace,
         schema,
         fragments,
+        variables,
         field_path,
         selection_type,
         &selection_type.possible_types,
         None,
         selection_set,
+        included,
         fields,
+        errors,
     )
 }
 
@@ -237,100 +292,185 @@ fn collect_fields_internal<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
     fragments: &HashMap<&'q ast::Name, &'q executable::FragmentDefinition>,
+    variables: &input::value::Variables<'q, 's, S>,
     field_path: &Vec<&'s ast::TypeName>,
     selection_type: &SelectableType<'s, S>,
     selection_set_reachability: &HashSet<&'s ast::TypeName>,
     selection_sub_type: Option<&SelectableType<'s, S>>,
     selection_set: &'q [spanning::Spanning<executable::Selection>],
+    included: bool,
     fields: &mut Vec<CollectedField<'q, 's, S>>,
-) -> Result<()> {
+    errors: &mut Vec<PositionedError>,
+) {
     for selection in selection_set {
         match &selection.item {
             executable::Selection::Field(field) => {
-                let field_info = selection_type.lookup_field(namespace, &field.name.item)?;
-                let alias = &field
+                let alias = field
                     .alias
                     .as_ref()
                     .map_or(&field.name.item, |alias| &alias.item.0);
-
-                // refine the field info by sub_type if needed
-                let refined_field_info = if let Some(sub_type) = selection_sub_type {
-                    sub_type
-                        .lookup_field(namespace, &field.name.item)
-                        // this is an internal error because the subtype should
-                        // definitely have the field
-                        .map_err(|_| Error::InternalNoFieldOnSubtype {
-                            type_name: sub_type.type_name.clone(),
-                            sub_type_name: selection_type.type_name.clone(),
-                            field_name: field.name.item.clone(),
-                        })?
-                } else {
-                    field_info
-                };
-
-                fields.push(CollectedField {
-                    alias,
-                    field_path: field_path.clone(),
-                    info: refined_field_info,
+                // the errors of the field are found at its path
+                let mut field_errors = Vec::new();
+                let field_directives = directives::normalize_directives(
+                    namespace,
+                    schema,
+                    variables,
+                    DirectiveLocation::Field,
+                    &field.directives,
+                    &mut field_errors,
+                );
+                match lookup_field_info(
+                    namespace,
+                    schema,
+                    selection_type,
+                    selection_sub_type,
                     field,
-                    reachable: !selection_set_reachability.is_empty(),
-                });
+                ) {
+                    Ok(field_info) => fields.push(CollectedField {
+                        alias,
+                        field_path: field_path.clone(),
+                        info: field_info,
+                        field,
+                        reachable: !selection_set_reachability.is_empty(),
+                        included: included && directives::is_included(&field_directives),
+                        directives: field_directives,
+                        location: selection.start,
+                    }),
+                    Err(error) => field_errors.push(error.at(selection.start)),
+                }
+                let alias = ast::Alias(alias.clone());
+                errors.extend(field_errors.into_iter().map(|error| error.in_field(&alias)));
             }
             executable::Selection::FragmentSpread(spread) => {
+                let spread_directives = directives::normalize_directives(
+                    namespace,
+                    schema,
+                    variables,
+                    DirectiveLocation::FragmentSpread,
+                    &spread.directives,
+                    errors,
+                );
+                let spread_included = included && directives::is_included(&spread_directives);
                 let fragment_name = &spread.fragment_name.item;
-                let fragment_definition = fragments
-                    .get(&spread.fragment_name.item)
-                    .ok_or_else(|| Error::UnknownFragment(fragment_name.clone()))?;
-                let fragment_type_name = &fragment_definition.type_condition.item.on.item;
-                let fragment_type_info = get_type_info(schema, fragment_type_name)?;
-                let fragment_selection_type =
-                    fragment_type_info.to_selectable_type().ok_or_else(|| {
-                        Error::FragmentOnNonCompositeType {
-                            fragment_name: Some(fragment_name.clone()),
-                            type_name: selection_type.type_name.clone(),
-                        }
-                    })?;
-                collect_fields_from_fragment(
+                let Some(fragment_definition) = fragments.get(&spread.fragment_name.item) else {
+                    errors.push(Error::UnknownFragment(fragment_name.clone()).at(selection.start));
+                    continue;
+                };
+                // The directives on fragment definitions are only validated,
+                // they aren't part of the normalized AST
+                directives::normalize_directives(
                     namespace,
                     schema,
-                    fragments,
-                    field_path,
-                    selection_type,
-                    selection_sub_type,
-                    selection_set_reachability,
-                    &fragment_selection_type,
-                    &fragment_definition.selection_set.item,
-                    fields,
-                )?;
+                    variables,
+                    DirectiveLocation::FragmentDefinition,
+                    &fragment_definition.directives,
+                    errors,
+                );
+                let fragment_type_name = &fragment_definition.type_condition.item.on.item;
+                let fragment_selection_type =
+                    get_type_info(schema, fragment_type_name).and_then(|fragment_type_info| {
+                        fragment_type_info.to_selectable_type().ok_or_else(|| {
+                            Error::FragmentOnNonCompositeType {
+                                fragment_name: Some(fragment_name.clone()),
+                                type_name: selection_type.type_name.clone(),
+                            }
+                        })
+                    });
+                match fragment_selection_type {
+                    Ok(fragment_selection_type) => collect_fields_from_fragment(
+                        namespace,
+                        schema,
+                        fragments,
+                        variables,
+                        field_path,
+                        selection_type,
+                        selection_sub_type,
+                        selection_set_reachability,
+                        &fragment_selection_type,
+                        &fragment_definition.selection_set.item,
+                        selection.start,
+                        spread_included,
+                        fields,
+                        errors,
+                    ),
+                    Err(error) => errors.push(error.at(selection.start)),
+                }
             }
             executable::Selection::InlineFragment(spread) => {
+                let spread_directives = directives::normalize_directives(
+                    namespace,
+                    schema,
+                    variables,
+                    DirectiveLocation::InlineFragment,
+                    &spread.directives,
+                    errors,
+                );
+                let spread_included = included && directives::is_included(&spread_directives);
                 let fragment_selection_type = match &spread.type_condition {
                     Some(type_condition) => {
                         let fragment_type_name = &type_condition.item.on.item;
-                        let fragment_type_info = get_type_info(schema, fragment_type_name)?;
-                        Ok(Some(fragment_type_info.to_selectable_type().ok_or_else(
-                            || Error::FragmentOnNonCompositeType {
-                                fragment_name: None,
-                                type_name: selection_type.type_name.clone(),
-                            },
-                        )?))
+                        get_type_info(schema, fragment_type_name).and_then(|fragment_type_info| {
+                            fragment_type_info
+                                .to_selectable_type()
+                                .map(Some)
+                                .ok_or_else(|| Error::FragmentOnNonCompositeType {
+                                    fragment_name: None,
+                                    type_name: selection_type.type_name.clone(),
+                                })
+                        })
                     }
                     None => Ok(None),
-                }?;
-                collect_fields_from_fragment(
-                    namespace,
-                    schema,
-                    fragments,
-                    field_path,
-                    selection_type,
-                    selection_sub_type,
-                    selection_set_reachability,
-                    fragment_selection_type.as_ref().unwrap_or(selection_type),
-                    &spread.selection_set.item,
-                    fields,
-                )?;
+                };
+                match fragment_selection_type {
+                    Ok(fragment_selection_type) => collect_fields_from_fragment(
+                        namespace,
+                        schema,
+                        fragments,
+                        variables,
+                        field_path,
+                        selection_type,
+                        selection_sub_type,
+                        selection_set_reachability,
+                        fragment_selection_type.as_ref().unwrap_or(selection_type),
+                        &spread.selection_set.item,
+                        selection.start,
+                        spread_included,
+                        fields,
+                        errors,
+                    ),
+                    Err(error) => errors.push(error.at(selection.start)),
+                }
             }
         }
     }
-    Ok(())
+}
+
+/// Looks up the field of a selection in its selection type, refined by the
+/// sub type that the selection is coerced as, if any
+fn lookup_field_info<'s, S: schema::SchemaContext>(
+    namespace: &S::Namespace,
+    schema: &'s schema::Schema<S>,
+    selection_type: &SelectableType<'s, S>,
+    selection_sub_type: Option<&SelectableType<'s, S>>,
+    field: &executable::Field,
+) -> Result<FieldInfo<'s, S>> {
+    let field_info = selection_type.lookup_field(namespace, &field.name.item)?;
+
+    // refine the field info by sub_type if needed
+    let refined_field_info = if let Some(sub_type) = selection_sub_type {
+        sub_type
+            .lookup_field(namespace, &field.name.item)
+            // this is an internal error because the subtype should
+            // definitely have the field
+            .map_err(|_| Error::InternalNoFieldOnSubtype {
+                type_name: sub_type.type_name.clone(),
+                sub_type_name: selection_type.type_name.clone(),
+                field_name: field.name.item.clone(),
+            })?
+    } else {
+        field_info
+    };
+
+    check_leaf_field_selection(schema, field, &refined_field_info)?;
+    Ok(refined_field_info)
 }
@@ -0,0 +1,250 @@
+use indexmap::IndexMap;
+
+use super::error::*;
+use super::input;
+use crate::ast::common as ast;
+use crate::ast::executable;
+use crate::ast::schema::DirectiveLocation;
+use crate::ast::spanning;
+use crate::ast::value as gql;
+use crate::mk_name;
+use crate::normalized_ast as normalized;
+use crate::schema;
+
+/// Validates the directives used at a location of the document against their
+/// definitions in the schema, and normalizes their arguments. The directives
+/// are returned in the order in which they are used, and the ones which are
+/// invalid are left out and reported in `errors`.
+pub(super) fn normalize_directives<'q, 's, S: schema::SchemaContext>(
+    namespace: &S::Namespace,
+    schema: &'s schema::Schema<S>,
+    variables: &input::value::Variables<'q, 's, S>,
+    location: DirectiveLocation,
+    directives: &'q [spanning::Spanning<executable::Directive>],
+    errors: &mut Vec<PositionedError>,
+) -> Vec<normalized::Directive<'s, S>>
+where
+    's: 'q,
+{
+    let mut normalized_directives: Vec<normalized::Directive<'s, S>> = Vec::new();
+    for directive in directives {
+        let directive_name = &directive.item.name.item;
+        let Some(directive_info) = schema.get_directive(directive_name) else {
+            errors.push(
+                Error::UnknownDirective {
+                    directive_name: directive_name.clone(),
+                }
+                .at(directive.start),
+            );
+            continue;
+        };
+        if !directive_info.is_allowed_on(location) {
+            errors.push(
+                Error::DirectiveNotAllowed {
+                    directive_name: directive_name.clone(),
+                    location: location.to_string(),
+                }
+                .at(directive.start),
+            );
+            continue;
+        }
+        if !directive_info.is_repeatable
+            && normalized_directives
+                .iter()
+                .any(|normalized_directive| &normalized_directive.name == directive_name)
+        {
+            errors.push(
+                Error::DuplicateDirectives {
+                    directive_name: directive_name.clone(),
+                }
+                .at(directive.start),
+            );
+            continue;
+        }
+        let errors_count = errors.len();
+        let arguments = normalize_arguments(
+            namespace,
+            schema,
+            variables,
+            directive_info,
+            directive,
+            errors,
+        );
+        if errors.len() == errors_count {
+            normalized_directives.push(normalized::Directive {
+                name: directive_name.clone(),
+                arguments,
+            });
+        }
+    }
+    normalized_directives
+}
+
+/// Evaluates `@skip` and `@include` on a field or a fragment, returning whether
+/// it is included in the response
+pub(super) fn is_included<S: schema::SchemaContext>(
+    directives: &[normalized::Directive<'_, S>],
+) -> bool {
+    directives.iter().all(|directive| {
+        // the `if` argument of both is a `Boolean!`, which normalization
+        // guarantees a value for
+        let condition = || {
+            directive
+                .arguments
+                .get(&mk_name!("if"))
+                .map_or(false, |argument| {
+                    matches!(
+                        argument.value,
+                        normalized::Value::SimpleValue(normalized::SimpleValue::Boolean(true))
+                    )
+                })
+        };
+        match directive.name.as_str() {
+            "skip" => !condition(),
+            "include" => condition(),
+            _ => true,
+        }
+    })
+}
+
+fn normalize_arguments<'q, 's, S: schema::SchemaContext>(
+    namespace: &S::Namespace,
+    schema: &'s schema::Schema<S>,
+    variables: &input::value::Variables<'q, 's, S>,
+    directive_info: &'s schema::Directive<S>,
+    directive: &'q spanning::Spanning<executable::Directive>,
+    errors: &mut Vec<PositionedError>,
+) -> IndexMap<ast::Name, normalized::InputField<'s, S>>
+where
+    's: 'q,
+{
+    let directive_name = &directive_info.name;
+    let mut arguments_map = IndexMap::new();
+    for argument in directive
+        .item
+        .arguments
+        .iter()
+        .flat_map(|arguments| &arguments.item)
+    {
+        let name = &argument.item.key.item;
+        if arguments_map.contains_key(name) {
+            errors.push(
+                Error::DuplicateDirectiveArguments {
+                    directive_name: directive_name.clone(),
+                    argument_name: name.clone(),
+                }
+                .at(argument.start),
+            );
+        } else {
+            arguments_map.insert(name, argument);
+        }
+    }
+    let mut normalized_arguments = IndexMap::new();
+    for (name, info) in &directive_info.arguments {
+        // arguments which aren't allowed for the namespace are reported as
+        // not found, along with the unknown ones
+        let Some((argument_info, namespaced)) = info.get(namespace) else {
+            continue;
+        };
+        let argument = arguments_map.shift_remove(name);
+        match normalize_argument(
+            namespace,
+            schema,
+            variables,
+            argument_info,
+            argument.map(|argument| &argument.item.value.item),
+        ) {
+            Ok(Some(normalized_value)) => {
+                normalized_arguments.insert(
+                    name.clone(),
+                    normalized::InputField {
+                        name: name.clone(),
+                        info: schema::NodeInfo {
+                            generic: &argument_info.info,
+                            namespaced,
+                        },
+                        value: normalized_value,
+                    },
+                );
+            }
+            Ok(None) if argument_info.field_type.nullable => {}
+            Ok(None) => errors.push(
+                Error::RequiredDirectiveArgumentNotFound {
+                    directive_name: directive_name.clone(),
+                    argument_name: name.clone(),
+                }
+                .at(directive.start),
+            ),
+            Err(error) => {
+                errors.push(error.at(argument.map_or(directive.start, |argument| argument.start)))
+            }
+        }
+    }
+    if !arguments_map.is_empty() {
+        errors.push(
+            Error::DirectiveArgumentsNotFound {
+                directive_name: directive_name.clone(),
+                argument_names: arguments_map.keys().copied().cloned().collect(),
+            }
+            .at_locations(
+                arguments_map
+                    .values()
+                    .map(|argument| argument.start)
+                    .collect(),
+            ),
+        );
+    }
+    normalized_arguments
+}
+
+/// Normalizes the value of an argument, falling back to its default value if
+/// it isn't given. `None` is returned if neither is there.
+fn normalize_argument<'q, 's, S: schema::SchemaContext>(
+    namespace: &S::Namespace,
+    schema: &'s schema::Schema<S>,
+    variables: &input::value::Variables<'q, 's, S>,
+    argument_info: &'s schema::InputField<S>,
+    argument_value: Option<&'q gql::Value>,
+) -> Result<Option<normalized::Value<'s, S>>>
+where
+    's: 'q,
+{
+    let argument_type = &argument_info.field_type;
+    let argument_type_info = schema
+        .get_type(argument_type.underlying_type())
+        .ok_or_else(|| Error::InternalTypeNotFound {
+            type_name: argument_type.underlying_type().clone(),
+        })?;
+    let argument_type_info =
+        argument_type_info
+            .as_input_type()
+            .ok_or_else(|| Error::InternalNotInputType {
+                type_name: argument_type.underlying_type().clone(),
+                actual_type: argument_type_info.kind(),
+            })?;
+    let location_type = input::source::LocationType::Argument {
+        type_: argument_type,
+        default_value: argument_info.default_value.as_ref(),
+    };
+    match (argument_value, &argument_info.default_value) {
+        (Some(argument_value), _) => input::normalize::normalize(
+            schema,
+            namespace,
+            variables,
+            argument_value,
+            &location_type,
+            &argument_type_info,
+        )
+        .map(Some),
+        (None, Some(default_value)) => input::normalize::normalize(
+            schema,
+            namespace,
+            &(),
+            default_value,
+            &location_type,
+            &argument_type_info,
+        )
+        .map(Some),
+        (None, None) => Ok(None),
+    }
+}
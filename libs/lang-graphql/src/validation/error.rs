@@ -1,14 +1,72 @@
+use std::collections::HashSet;
+
 use thiserror::Error;
 
 use crate::ast::{common as ast, spanning};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// A validation error, along with the locations in the document that it
+/// applies to, and the response path of the field on which it is found, if
+/// any
+#[derive(Error, Debug, Clone)]
+#[error("{error}")]
+pub struct PositionedError {
+    pub error: Error,
+    pub locations: Vec<spanning::SourcePosition>,
+    pub path: Vec<ast::Alias>,
+}
+
+impl PositionedError {
+    /// Prepends the alias of the field which the error is nested in to the
+    /// path of the error
+    pub(super) fn in_field(mut self, alias: &ast::Alias) -> PositionedError {
+        self.path.insert(0, alias.clone());
+        self
+    }
+}
+
+/// All the errors found while validating a document, in the order in which
+/// they are found. There is at least one.
+#[derive(Error, Debug, Clone)]
+#[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+pub struct Errors(pub Vec<PositionedError>);
+
+impl Errors {
+    /// Orders the errors by their locations, and drops the ones which are
+    /// found more than once, e.g. in a fragment which is spread twice on the
+    /// same field
+    pub(super) fn new(mut errors: Vec<PositionedError>) -> Errors {
+        let mut found_errors = HashSet::new();
+        errors.retain(|error| {
+            found_errors.insert((
+                error.to_string(),
+                error.locations.clone(),
+                error.path.clone(),
+            ))
+        });
+        errors.sort_by_key(|error| error.locations.first().copied());
+        Errors(errors)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, PositionedError> {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for Errors {
+    type Item = PositionedError;
+    type IntoIter = std::vec::IntoIter<PositionedError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
 #[derive(Error, Debug, Clone)]
 pub enum Error {
-    #[error("fragment cycle detected through: {0:?}")]
+    #[error("fragment cycle detected through: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(" -> "))]
     CycleDetected(Vec<ast::Name>),
-    // TODO, this error isn't thrown yet
     #[error("unused fragment: {0}")]
     FragmentNotUsed(ast::Name),
     #[error("fragment not defined in the document: {0}")]
@@ -23,7 +81,6 @@ pub enum Error {
         selection_type: ast::TypeName,
         fragment_type: ast::TypeName,
     },
-    // TODO, this error isn't thrown yet
     #[error(
         "a selection set is specified on field '{field_name}' of non-composite type: {type_name}"
     )]
@@ -31,6 +88,11 @@ pub enum Error {
         field_name: ast::Name,
         type_name: ast::TypeName,
     },
+    #[error("a selection set is required on field '{field_name}' of composite type: {type_name}")]
+    NoSelectionOnCompositeType {
+        field_name: ast::Name,
+        type_name: ast::TypeName,
+    },
     #[error("no such field on type {type_name}: {field_name}")]
     NoFieldOnType {
         type_name: ast::TypeName,
@@ -125,6 +187,8 @@ pub enum Error {
     },
     #[error("the variable {variable_name} is not defined in the document")]
     VariableNotDefined { variable_name: ast::Name },
+    #[error("the variable {variable_name} is defined but not used by the operation")]
+    VariableNotUsed { variable_name: ast::Name },
     #[error("required variable {variable_name} not provided")]
     RequiredVariableNotProvided { variable_name: ast::Name },
     #[error("the variable {variable_name} of type {variable_type} cannot be used at a location of type {location_type}")]
@@ -145,13 +209,15 @@ pub enum Error {
     NoMutationsAreDefined,
     #[error("no subscriptions are defined in the schema")]
     NoSubscriptionsAreDefined,
+    #[error("a subscription must select exactly one root field, found {root_field_count}")]
+    SubscriptionMustHaveSingleRootField { root_field_count: usize },
     #[error("internal error: selection root is not of object type")]
     InternalSelectionRootIsNotObject,
     #[error("operation not found: {operation_name}")]
     OperationNotFound { operation_name: ast::Name },
     #[error("no anonymous operation found in the document")]
     AnonymousOperationNotFound,
-    #[error("expected arguments {} on field {field_name} of type {type_name} are not found", argument_names.iter().fold(String::new(), |acc, name| acc + &name.to_string()))]
+    #[error("expected arguments {} on field {field_name} of type {type_name} are not found", argument_names.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
     ArgumentsNotFound {
         type_name: ast::TypeName,
         field_name: ast::Name,
@@ -178,4 +244,43 @@ pub enum Error {
         field_name: ast::Name,
         argument_name: ast::Name,
     },
+    #[error("unknown directive: {directive_name}")]
+    UnknownDirective { directive_name: ast::Name },
+    #[error("directive {directive_name} is not allowed on {location}")]
+    DirectiveNotAllowed {
+        directive_name: ast::Name,
+        location: String,
+    },
+    #[error("directive {directive_name} is used more than once at the same location")]
+    DuplicateDirectives { directive_name: ast::Name },
+    #[error("expected arguments {} on directive {directive_name} are not found", argument_names.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    DirectiveArgumentsNotFound {
+        directive_name: ast::Name,
+        argument_names: Vec<ast::Name>,
+    },
+    #[error("argument {argument_name} on directive {directive_name} is defined more than once")]
+    DuplicateDirectiveArguments {
+        directive_name: ast::Name,
+        argument_name: ast::Name,
+    },
+    #[error("required argument {argument_name} not found on directive {directive_name}")]
+    RequiredDirectiveArgumentNotFound {
+        directive_name: ast::Name,
+        argument_name: ast::Name,
+    },
+}
+
+impl Error {
+    /// Positions the error at the given location of the document
+    pub(super) fn at(self, location: spanning::SourcePosition) -> PositionedError {
+        self.at_locations(vec![location])
+    }
+
+    pub(super) fn at_locations(self, locations: Vec<spanning::SourcePosition>) -> PositionedError {
+        PositionedError {
+            error: self,
+            locations,
+            path: Vec::new(),
+        }
+    }
 }
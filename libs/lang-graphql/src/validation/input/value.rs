@@ -32,8 +32,9 @@ fn are_base_types_compatible(variable_type: &ast::BaseType, location_type: &ast:
 }
 
 fn are_types_compatible(variable_type: &ast::Type, location_type: &ast::Type) -> bool {
+    // a nullable variable can't be used at a non-nullable location
     fn check_nullability(variable_nullability: bool, location_nullability: bool) -> bool {
-        !matches!((variable_nullability, location_nullability), (false, true))
+        !matches!((variable_nullability, location_nullability), (true, false))
     }
     are_base_types_compatible(&variable_type.base, &location_type.base)
         && check_nullability(variable_type.nullable, location_type.nullable)
@@ -8,10 +8,13 @@ use super::input;
 use crate::ast::common as ast;
 use crate::ast::executable;
 use crate::ast::spanning;
-use crate::ast::spanning::Spanning;
+use crate::ast::value as gql;
 use crate::normalized_ast as normalized;
 use crate::schema;
 
+/// Normalizes the selection set of an operation. The errors found along the
+/// way are added to `errors`, with the response path of the field that they
+/// are found on.
 pub fn normalize_selection_set<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
@@ -20,7 +23,8 @@ pub fn normalize_selection_set<'q, 's, S: schema::SchemaContext>(
 
     selection_type: &collect::SelectableType<'s, S>,
     selection_set: &'q executable::SelectionSet,
-) -> Result<normalized::SelectionSet<'s, S>>
+    errors: &mut Vec<PositionedError>,
+) -> normalized::SelectionSet<'s, S>
 where
     's: 'q,
 {
@@ -31,7 +35,8 @@ where
         fragments,
         variables,
         selection_type,
-        Vec::from([(&reachability, Vec::from([&selection_set.items]))]),
+        Vec::from([(&reachability, Vec::from([(&selection_set.items, true)]))]),
+        errors,
     )
 }
 
@@ -47,24 +52,29 @@ fn normalize_selection_sets<'q, 's, S: schema::SchemaContext>(
     selection_set_groups: Vec<(
         // field path
         &Vec<&'s ast::TypeName>,
-        Vec<&'q Vec<spanning::Spanning<executable::Selection>>>,
+        // the selection sets, and whether they are included by @skip and @include
+        Vec<(&'q Vec<spanning::Spanning<executable::Selection>>, bool)>,
     )>,
-) -> Result<normalized::SelectionSet<'s, S>>
+    errors: &mut Vec<PositionedError>,
+) -> normalized::SelectionSet<'s, S>
 where
     's: 'q,
 {
     let mut fields = Vec::new();
     for (path, selection_sets) in selection_set_groups {
-        for selection_set in selection_sets {
+        for (selection_set, included) in selection_sets {
             collect::collect_fields(
                 namespace,
                 schema,
                 fragments,
+                variables,
                 path,
                 selection_type,
                 selection_set,
+                included,
                 &mut fields,
-            )?;
+                errors,
+            );
         }
     }
     let field_map = fields.iter().fold(IndexMap::new(), |mut acc, field| {
@@ -92,6 +102,9 @@ where
     let mut normalized_fields = IndexMap::new();
     for (alias, (alias_type, typed_fields)) in field_map.into_iter() {
         let alias = ast::Alias(alias.clone());
+        // the errors of the field and of its selection set are found under
+        // its alias
+        let mut field_errors = Vec::new();
         let (field_calls, selection_set) = merge_fields(
             namespace,
             schema,
@@ -101,7 +114,9 @@ where
             &alias,
             alias_type,
             typed_fields,
-        )?;
+            &mut field_errors,
+        );
+        errors.extend(field_errors.into_iter().map(|error| error.in_field(&alias)));
         // if let normalized::FieldCalls::Conditional(conditional) = &field_calls {
         if !field_calls.is_empty() {
             let normalized_field = normalized::Field {
@@ -124,12 +139,15 @@ where
     } else {
         None
     };
-    Ok(normalized::SelectionSet {
+    normalized::SelectionSet {
         fields: normalized_fields,
         type_name: type_name_response.cloned(),
-    })
+    }
 }
 
+/// Merges the fields collected under the same alias. Fields which conflict
+/// with the first one of their field path are reported in `errors` and left
+/// out.
 #[allow(clippy::too_many_arguments)]
 fn merge_fields<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
@@ -140,20 +158,14 @@ fn merge_fields<'q, 's, S: schema::SchemaContext>(
     alias: &ast::Alias,
     alias_type: &ast::Type,
     typed_fields: HashMap<&Vec<&'s ast::TypeName>, NonEmpty<&collect::CollectedField<'q, 's, S>>>,
-) -> Result<(
+    errors: &mut Vec<PositionedError>,
+) -> (
     normalized::FieldCalls<'s, S>,
     normalized::SelectionSet<'s, S>,
-)>
+)
 where
     's: 'q,
 {
-    let alias_type_info = schema
-        .get_type(alias_type.underlying_type())
-        .ok_or_else(|| Error::InternalTypeNotFound {
-            type_name: alias_type.underlying_type().clone(),
-        })?;
-    let alias_selectable_type = alias_type_info.to_selectable_type();
-
     let mut alias_selection_sets = Vec::new();
     let mut field_calls = HashMap::new();
     for (reachability, fields) in typed_fields.into_iter() {
@@ -163,72 +175,87 @@ where
             schema,
             variables,
             type_name,
-            &cannonical_field.info.generic.name,
-            &cannonical_field.info.generic.arguments,
-            &cannonical_field.field.arguments,
-        )?;
-        let directives =
-            normalize_directives(namespace, schema, &cannonical_field.field.directives)?;
-
+            cannonical_field,
+            errors,
+        );
         let cannonical_field_type = &cannonical_field.info.generic.field_type;
         if cannonical_field_type != alias_type {
-            return Err(Error::FieldsConflictDifferingTypes {
-                alias: alias.clone(),
-                type1: alias_type.clone(),
-                type2: cannonical_field_type.clone(),
-            });
+            errors.push(
+                Error::FieldsConflictDifferingTypes {
+                    alias: alias.clone(),
+                    type1: alias_type.clone(),
+                    type2: cannonical_field_type.clone(),
+                }
+                .at(cannonical_field.location),
+            );
+            continue;
         }
         let mut selection_sets = Vec::with_capacity(fields.len());
         if let Some(selection_set) = &cannonical_field.field.selection_set {
-            selection_sets.push(&selection_set.item.items);
+            selection_sets.push((&selection_set.item.items, cannonical_field.included));
         }
+        // the first of the merged fields which isn't skipped, if any
+        let mut included_field = cannonical_field.included.then_some(cannonical_field);
         for field in fields.tail() {
             if field.field.name.item != cannonical_field.field.name.item {
-                return Err(Error::FieldsConflictDifferentFields {
-                    alias: alias.clone(),
-                    field1: cannonical_field.field.name.item.clone(),
-                    field2: field.field.name.item.clone(),
-                });
+                errors.push(
+                    Error::FieldsConflictDifferentFields {
+                        alias: alias.clone(),
+                        field1: cannonical_field.field.name.item.clone(),
+                        field2: field.field.name.item.clone(),
+                    }
+                    .at_locations(vec![cannonical_field.location, field.location]),
+                );
+                continue;
             }
-            // TODO deal with directives
-            // let this_directives = normalize_directives(namespace, schema, &field.field.directives)?;
-            let this_arguments = normalize_arguments(
-                namespace,
-                schema,
-                variables,
-                type_name,
-                &field.info.generic.name,
-                &field.info.generic.arguments,
-                &field.field.arguments,
-            )?;
-            if arguments != this_arguments {
-                return Err(Error::FieldsConflictDifferingArguments {
-                    alias: alias.clone(),
-                    location1: cannonical_field.field.arguments.as_ref().map(|a| a.start),
-                    location2: field.field.arguments.as_ref().map(|a| a.start),
-                });
+            let this_arguments =
+                normalize_arguments(namespace, schema, variables, type_name, field, errors);
+            // arguments which are invalid are already reported
+            if let (Some(arguments), Some(this_arguments)) = (&arguments, &this_arguments) {
+                if arguments != this_arguments {
+                    errors.push(
+                        Error::FieldsConflictDifferingArguments {
+                            alias: alias.clone(),
+                            location1: cannonical_field.field.arguments.as_ref().map(|a| a.start),
+                            location2: field.field.arguments.as_ref().map(|a| a.start),
+                        }
+                        .at_locations(vec![cannonical_field.location, field.location]),
+                    );
+                    continue;
+                }
             }
             // the field can be merged so we collect the selection set
             if let Some(selection_set) = &field.field.selection_set {
-                selection_sets.push(&selection_set.item.items);
+                selection_sets.push((&selection_set.item.items, field.included));
+            }
+            if included_field.is_none() && field.included {
+                included_field = Some(field);
             }
         }
-        let field_call = normalized::FieldCall {
-            name: cannonical_field.field.name.item.clone(),
-            info: schema::NodeInfo {
-                generic: &cannonical_field.info.generic.info,
-                namespaced: cannonical_field.info.namespaced,
-            },
-            arguments,
-            directives,
-        };
-        if cannonical_field.reachable {
-            field_calls.insert(reachability.iter().cloned().cloned().collect(), field_call);
+        // the fields excluded by @skip and @include are only validated
+        if let Some(included_field) = included_field {
+            let field_call = normalized::FieldCall {
+                name: cannonical_field.field.name.item.clone(),
+                info: schema::NodeInfo {
+                    generic: &cannonical_field.info.generic.info,
+                    namespaced: cannonical_field.info.namespaced,
+                },
+                arguments: arguments.unwrap_or_default(),
+                directives: included_field.directives.clone(),
+                location: included_field.location,
+            };
+            if cannonical_field.reachable {
+                field_calls.insert(reachability.iter().cloned().cloned().collect(), field_call);
+            }
         }
         alias_selection_sets.push((reachability, selection_sets));
     }
     // TODO: this alias_selectable_type is empty when the underlying type is scalar,
     // maybe processing of alias_selection_sets can be cleaned up
+    // the types of the collected fields are known to be in the schema
+    let alias_selectable_type = schema
+        .get_type(alias_type.underlying_type())
+        .and_then(|alias_type_info| alias_type_info.to_selectable_type());
     let normalized_selection_set = match alias_selectable_type {
         Some(selection_type) => normalize_selection_sets(
             namespace,
@@ -237,159 +264,180 @@ where
             variables,
             &selection_type,
             alias_selection_sets,
-        )?,
+            errors,
+        ),
         None => normalized::SelectionSet {
             fields: IndexMap::new(),
             type_name: None,
         },
     };
-    Ok((
+    (
         // normalized::FieldCalls::Conditional(field_calls),
         field_calls,
         normalized_selection_set,
-    ))
+    )
 }
 
+/// Normalizes the arguments of a field. `None` is returned if any of them is
+/// invalid, in which case the errors are added to `errors`.
 fn normalize_arguments<'q, 's, S: schema::SchemaContext>(
     namespace: &S::Namespace,
     schema: &'s schema::Schema<S>,
     variables: &input::value::Variables<'q, 's, S>,
     type_name: &ast::TypeName,
-    field_name: &ast::Name,
-    arguments_schema: &'s HashMap<ast::Name, schema::Namespaced<S, schema::InputField<S>>>,
-    arguments: &'q Option<Spanning<Vec<executable::Argument>>>,
-) -> Result<IndexMap<ast::Name, normalized::InputField<'s, S>>> {
-    let mut arguments_map = HashMap::new();
-    if let Some(arguments) = arguments {
+    field: &collect::CollectedField<'q, 's, S>,
+    errors: &mut Vec<PositionedError>,
+) -> Option<IndexMap<ast::Name, normalized::InputField<'s, S>>> {
+    let field_name = &field.info.generic.name;
+    let errors_count = errors.len();
+    let mut arguments_map = IndexMap::new();
+    if let Some(arguments) = &field.field.arguments {
         for argument in &arguments.item {
             let name = &argument.item.key.item;
             // if the argument already exists, we throw an error
-            if arguments_map
-                .insert(name, &argument.item.value.item)
-                .is_some()
-            {
-                return Err(Error::DuplicateArguments {
-                    type_name: type_name.clone(),
-                    field_name: field_name.clone(),
-                    argument_name: name.clone(),
-                });
+            if arguments_map.contains_key(name) {
+                errors.push(
+                    Error::DuplicateArguments {
+                        type_name: type_name.clone(),
+                        field_name: field_name.clone(),
+                        argument_name: name.clone(),
+                    }
+                    .at(argument.start),
+                );
+            } else {
+                arguments_map.insert(name, argument);
             }
         }
     }
     let mut normalized_arguments = IndexMap::new();
-    for (name, info) in arguments_schema {
-        let argument_value = arguments_map.remove(name);
+    for (name, info) in &field.info.generic.arguments {
+        let argument = arguments_map.shift_remove(name);
 
         // if the argument is allowed for the given namespace
         if let Some((argument_info, namespaced)) = info.get(namespace) {
-            let argument_type = &argument_info.field_type;
-            // get the information of the type in the base_type
-            let argument_type_info = {
-                let argument_type_info = schema
-                    .types
-                    .get(argument_type.underlying_type())
-                    .ok_or_else(|| Error::InternalTypeNotFound {
-                        type_name: argument_type.underlying_type().clone(),
-                    })?;
-                argument_type_info
-                    .as_input_type()
-                    .ok_or_else(|| Error::InternalNotInputType {
-                        type_name: argument_type.underlying_type().clone(),
-                        actual_type: argument_type_info.kind(),
-                    })?
-            };
-
-            let normalized_field_value = match (argument_value, &argument_info.default_value) {
-                // Neither argument value nor default value
-                (None, None) => {
-                    if !argument_type.nullable {
-                        return Err(Error::RequiredArgumentNotFound {
-                            type_name: type_name.clone(),
-                            field_name: field_name.clone(),
-                            argument_name: name.clone(),
-                        });
-                    } else {
-                        None
-                    }
-                }
-
-                // if argument value is present, normalize it
-                (Some(argument_value), _) => {
-                    Some(input::normalize::normalize(
-                        schema,
-                        namespace,
-                        variables,
-                        argument_value,
-                        // TODO, this has to change to field info
-                        &input::source::LocationType::Field {
-                            type_: &argument_info.field_type,
-                            default_value: argument_info.default_value.as_ref(),
-                        },
-                        &argument_type_info,
-                    )?)
-                }
-
-                // we fall back to the default value, ideally we should've a normalized
-                // version of the default value but storing it as such in 'schema' adds
-                // a lot of complexity
-                (None, Some(default_value)) => {
-                    Some(input::normalize::normalize(
-                        schema,
-                        namespace,
-                        &(),
-                        default_value,
-                        // TODO, this has to change to field info
-                        &input::source::LocationType::Field {
-                            type_: &argument_info.field_type,
-                            default_value: argument_info.default_value.as_ref(),
+            match normalize_argument(
+                namespace,
+                schema,
+                variables,
+                argument_info,
+                argument.map(|argument| &argument.item.value.item),
+            ) {
+                Ok(Some(normalized_field_value)) => {
+                    normalized_arguments.insert(
+                        name.clone(),
+                        normalized::InputField {
+                            name: name.clone(),
+                            info: schema::NodeInfo {
+                                generic: &argument_info.info,
+                                namespaced,
+                            },
+                            value: normalized_field_value,
                         },
-                        &argument_type_info,
-                    )?)
+                    );
                 }
-            };
-
-            if let Some(normalized_field_value) = normalized_field_value {
-                normalized_arguments.insert(
-                    name.clone(),
-                    normalized::InputField {
-                        name: name.clone(),
-                        info: schema::NodeInfo {
-                            generic: &argument_info.info,
-                            namespaced,
-                        },
-                        value: normalized_field_value,
-                    },
-                );
+                // Neither argument value nor default value
+                Ok(None) if argument_info.field_type.nullable => {}
+                Ok(None) => errors.push(
+                    Error::RequiredArgumentNotFound {
+                        type_name: type_name.clone(),
+                        field_name: field_name.clone(),
+                        argument_name: name.clone(),
+                    }
+                    .at(field.location),
+                ),
+                Err(error) => errors
+                    .push(error.at(argument.map_or(field.location, |argument| argument.start))),
             }
 
         // if the argument isn't allowed for the given namespace, we throw a more
         // useful error message
-        } else if argument_value.is_some() {
-            return Err(Error::ArgumentNotAllowed {
-                namespace: namespace.to_string(),
-                type_name: type_name.clone(),
-                field_name: field_name.clone(),
-                argument_name: name.clone(),
-            });
+        } else if let Some(argument) = argument {
+            errors.push(
+                Error::ArgumentNotAllowed {
+                    namespace: namespace.to_string(),
+                    type_name: type_name.clone(),
+                    field_name: field_name.clone(),
+                    argument_name: name.clone(),
+                }
+                .at(argument.start),
+            );
         }
     }
 
     // Finally we check if there are any arguments that aren't defined on the field
     if !arguments_map.is_empty() {
-        Err(Error::ArgumentsNotFound {
-            type_name: type_name.clone(),
-            field_name: field_name.clone(),
-            argument_names: arguments_map.keys().copied().cloned().collect(),
-        })
-    } else {
-        Ok(normalized_arguments)
+        errors.push(
+            Error::ArgumentsNotFound {
+                type_name: type_name.clone(),
+                field_name: field_name.clone(),
+                argument_names: arguments_map.keys().copied().cloned().collect(),
+            }
+            .at_locations(
+                arguments_map
+                    .values()
+                    .map(|argument| argument.start)
+                    .collect(),
+            ),
+        );
     }
+    (errors.len() == errors_count).then_some(normalized_arguments)
 }
 
-fn normalize_directives<'s, S: schema::SchemaContext>(
-    _namespace: &S::Namespace,
-    _schema: &'s schema::Schema<S>,
-    _directives: &[spanning::Spanning<executable::Directive>],
-) -> Result<IndexMap<ast::Name, normalized::Directive<'s, S>>> {
-    Ok(IndexMap::new())
+/// Normalizes the value of an argument, falling back to its default value if
+/// it isn't given. `None` is returned if neither is there.
+fn normalize_argument<'q, 's, S: schema::SchemaContext>(
+    namespace: &S::Namespace,
+    schema: &'s schema::Schema<S>,
+    variables: &input::value::Variables<'q, 's, S>,
+    argument_info: &'s schema::InputField<S>,
+    argument_value: Option<&'q gql::Value>,
+) -> Result<Option<normalized::Value<'s, S>>> {
+    let argument_type = &argument_info.field_type;
+    // get the information of the type in the base_type
+    let argument_type_info = {
+        let argument_type_info = schema
+            .types
+            .get(argument_type.underlying_type())
+            .ok_or_else(|| Error::InternalTypeNotFound {
+                type_name: argument_type.underlying_type().clone(),
+            })?;
+        argument_type_info
+            .as_input_type()
+            .ok_or_else(|| Error::InternalNotInputType {
+                type_name: argument_type.underlying_type().clone(),
+                actual_type: argument_type_info.kind(),
+            })?
+    };
+    // TODO, this has to change to field info
+    let location_type = input::source::LocationType::Field {
+        type_: argument_type,
+        default_value: argument_info.default_value.as_ref(),
+    };
+    match (argument_value, &argument_info.default_value) {
+        // if argument value is present, normalize it
+        (Some(argument_value), _) => input::normalize::normalize(
+            schema,
+            namespace,
+            variables,
+            argument_value,
+            &location_type,
+            &argument_type_info,
+        )
+        .map(Some),
+
+        // we fall back to the default value, ideally we should've a normalized
+        // version of the default value but storing it as such in 'schema' adds
+        // a lot of complexity
+        (None, Some(default_value)) => input::normalize::normalize(
+            schema,
+            namespace,
+            &(),
+            default_value,
+            &location_type,
+            &argument_type_info,
+        )
+        .map(Some),
+
+        (None, None) => Ok(None),
+    }
 }
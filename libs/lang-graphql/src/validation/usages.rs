@@ -0,0 +1,153 @@
+//! Checks on the fragments and the variables of a document which only need the
+//! document itself: every fragment has to be used by an operation, and every
+//! variable used by an operation, including through the fragments it spreads,
+//! has to be defined by it, and the other way round.
+
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use super::error::*;
+use crate::ast::common as ast;
+use crate::ast::executable;
+use crate::ast::spanning;
+use crate::ast::value as gql;
+
+type Fragments<'q> = HashMap<&'q ast::Name, &'q executable::FragmentDefinition>;
+
+/// The fragments and variables that an operation uses, either directly or
+/// through the fragments that it spreads, along with the location of the first
+/// usage of each variable
+#[derive(Default)]
+struct Usages<'q> {
+    fragments: HashSet<&'q ast::Name>,
+    variables: IndexMap<&'q ast::Name, spanning::SourcePosition>,
+}
+
+impl<'q> Usages<'q> {
+    fn of_operation(
+        fragments: &Fragments<'q>,
+        operation: &'q executable::OperationDefinition,
+    ) -> Usages<'q> {
+        let mut usages = Usages::default();
+        usages.directives(&operation.directives);
+        usages.selection_set(fragments, &operation.selection_set.item);
+        usages
+    }
+
+    fn selection_set(
+        &mut self,
+        fragments: &Fragments<'q>,
+        selection_set: &'q executable::SelectionSet,
+    ) {
+        for selection in &selection_set.items {
+            match &selection.item {
+                executable::Selection::Field(field) => {
+                    self.arguments(&field.arguments);
+                    self.directives(&field.directives);
+                    if let Some(selection_set) = &field.selection_set {
+                        self.selection_set(fragments, &selection_set.item);
+                    }
+                }
+                executable::Selection::FragmentSpread(spread) => {
+                    self.directives(&spread.directives);
+                    let fragment_name = &spread.fragment_name.item;
+                    // fragments are only visited once, which also guards
+                    // against cycles
+                    if self.fragments.insert(fragment_name) {
+                        if let Some(fragment_definition) = fragments.get(fragment_name) {
+                            self.directives(&fragment_definition.directives);
+                            self.selection_set(fragments, &fragment_definition.selection_set.item);
+                        }
+                    }
+                }
+                executable::Selection::InlineFragment(inline_fragment) => {
+                    self.directives(&inline_fragment.directives);
+                    self.selection_set(fragments, &inline_fragment.selection_set.item);
+                }
+            }
+        }
+    }
+
+    fn directives(&mut self, directives: &'q [spanning::Spanning<executable::Directive>]) {
+        for directive in directives {
+            self.arguments(&directive.item.arguments);
+        }
+    }
+
+    fn arguments(&mut self, arguments: &'q Option<spanning::Spanning<Vec<executable::Argument>>>) {
+        for argument in arguments.iter().flat_map(|arguments| &arguments.item) {
+            self.value(&argument.item.value);
+        }
+    }
+
+    fn value(&mut self, value: &'q spanning::Spanning<gql::Value>) {
+        match &value.item {
+            gql::Value::Variable(variable_name) => {
+                self.variables.entry(variable_name).or_insert(value.start);
+            }
+            gql::Value::SimpleValue(_) => {}
+            gql::Value::List(values) => {
+                for value in values {
+                    self.value(value);
+                }
+            }
+            gql::Value::Object(fields) => {
+                for field in fields {
+                    self.value(&field.item.value);
+                }
+            }
+        }
+    }
+}
+
+/// Checks that every fragment of the document is used by one of its
+/// operations, and that the operations define exactly the variables that they
+/// use. Every violation is added to `errors`.
+pub(super) fn check_usages<'q>(
+    document: &'q executable::ExecutableDocument,
+    fragments: &Fragments<'q>,
+    errors: &mut Vec<PositionedError>,
+) {
+    let mut used_fragments = HashSet::new();
+    for definition in &document.items {
+        if let executable::ExecutableDefinition::Operation(operation) = &definition.item {
+            let usages = Usages::of_operation(fragments, operation);
+            let defined_variables = operation
+                .variable_definitions
+                .iter()
+                .flat_map(|definitions| &definitions.item)
+                .map(|definition| (&definition.item.name.item, definition.start))
+                .collect::<IndexMap<_, _>>();
+            for (variable_name, location) in &usages.variables {
+                if !defined_variables.contains_key(variable_name) {
+                    errors.push(
+                        Error::VariableNotDefined {
+                            variable_name: (*variable_name).clone(),
+                        }
+                        .at(*location),
+                    );
+                }
+            }
+            for (variable_name, location) in &defined_variables {
+                if !usages.variables.contains_key(variable_name) {
+                    errors.push(
+                        Error::VariableNotUsed {
+                            variable_name: (*variable_name).clone(),
+                        }
+                        .at(*location),
+                    );
+                }
+            }
+            used_fragments.extend(usages.fragments);
+        }
+    }
+    for definition in &document.items {
+        if let executable::ExecutableDefinition::Fragment(fragment) = &definition.item {
+            if !used_fragments.contains(&fragment.name.item) {
+                errors
+                    .push(Error::FragmentNotUsed(fragment.name.item.clone()).at(definition.start));
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use lang_graphql::ast::executable;
 use lang_graphql::parser;
 use std::env;
 use std::fs;
@@ -60,3 +61,149 @@ fn test_parser() -> Result<(), io::Error> {
     }
     Ok(())
 }
+
+/// Renders the selections of a selection set, without their arguments and
+/// directives
+#[cfg(test)]
+fn render_selection_set(selection_set: &executable::SelectionSet) -> String {
+    let selections = selection_set
+        .items
+        .iter()
+        .map(|selection| match &selection.item {
+            executable::Selection::Field(field) => match &field.selection_set {
+                None => field.name.item.to_string(),
+                Some(selection_set) => format!(
+                    "{} {}",
+                    field.name.item,
+                    render_selection_set(&selection_set.item)
+                ),
+            },
+            executable::Selection::FragmentSpread(spread) => {
+                format!("...{}", spread.fragment_name.item)
+            }
+            executable::Selection::InlineFragment(inline_fragment) => format!(
+                "... {}",
+                render_selection_set(&inline_fragment.selection_set.item)
+            ),
+        })
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", selections.join(" "))
+}
+
+/// Parses a document in recovering mode, and renders its definitions along
+/// with the positions of the errors
+#[cfg(test)]
+fn parse_with_recovery(document: &str) -> (Vec<String>, Vec<String>) {
+    let (document, errors) =
+        parser::Parser::new(document).parse_executable_document_with_recovery();
+    let definitions = document
+        .items
+        .iter()
+        .map(|definition| match &definition.item {
+            executable::ExecutableDefinition::Operation(operation) => format!(
+                "{} {}",
+                operation
+                    .name
+                    .as_ref()
+                    .map_or("anonymous".to_string(), |name| name.item.to_string()),
+                render_selection_set(&operation.selection_set.item)
+            ),
+            executable::ExecutableDefinition::Fragment(fragment) => format!(
+                "fragment {} {}",
+                fragment.name.item,
+                render_selection_set(&fragment.selection_set.item)
+            ),
+        })
+        .collect();
+    let errors = errors
+        .iter()
+        .map(|error| error.position.to_string())
+        .collect();
+    (definitions, errors)
+}
+
+#[test]
+fn test_parser_recovery() {
+    // a valid document is parsed as usual
+    let document = "query Hero { hero { name ...Friends } } fragment Friends on Character { friends { name } }";
+    assert_eq!(
+        parse_with_recovery(document),
+        (
+            vec![
+                "Hero { hero { name ...Friends } }".to_string(),
+                "fragment Friends { friends { name } }".to_string()
+            ],
+            vec![]
+        )
+    );
+
+    // invalid selections are skipped up to the next selection in the same
+    // selection set, and the selection sets which enclose them are parsed as
+    // usual
+    let document = r#"
+query Hero {
+  hero {
+    friends(first: ) { name }
+    name
+  }
+  droid {
+    id
+    name(
+  }
+  human { id }
+}
+"#;
+    assert_eq!(
+        parse_with_recovery(document),
+        (
+            vec!["Hero { hero { name } droid { id } human { id } }".to_string()],
+            vec!["4:20".to_string(), "10:3".to_string()]
+        )
+    );
+
+    // definitions which can't be parsed are skipped up to the next one
+    let document = r#"
+query Hero($episode: ) {
+  hero { name }
+}
+
+fragment Friends on {
+  friends { name }
+}
+
+query Droid { droid { id } }
+
+query Human { human { id ... on Human { name @ } } }
+"#;
+    assert_eq!(
+        parse_with_recovery(document),
+        (
+            vec![
+                "Droid { droid { id } }".to_string(),
+                "Human { human { id ... {  } } }".to_string()
+            ],
+            vec!["2:22".to_string(), "6:21".to_string(), "12:48".to_string()]
+        )
+    );
+
+    // the selection sets which aren't closed end with the file, and lexer
+    // errors are skipped
+    let document = "query Hero { hero { name ~ id } droid { id \"name";
+    assert_eq!(
+        parse_with_recovery(document),
+        (
+            vec!["Hero { hero { name id } droid { id } }".to_string()],
+            vec!["1:26".to_string(), "1:49".to_string()]
+        )
+    );
+
+    // each of the invalid selections of a selection set is reported
+    let document = "query Hero { hero { name ! id friends(first: ) { name } ...Friends } }";
+    assert_eq!(
+        parse_with_recovery(document),
+        (
+            vec!["Hero { hero { name id ...Friends } }".to_string()],
+            vec!["1:26".to_string(), "1:46".to_string()]
+        )
+    );
+}
@@ -0,0 +1,195 @@
+use lang_graphql::ast::common::{Name, TypeName};
+use lang_graphql::ast::schema::DirectiveLocation;
+use lang_graphql::http;
+use lang_graphql::parser::Parser;
+use lang_graphql::schema::{self, sdl};
+use lang_graphql::validation;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+static SCHEMA: &str = r#"
+"Caches the response for `ttl` seconds"
+directive @cached(ttl: Int = 60, refresh: Boolean) repeatable on QUERY | FIELD
+
+directive @tag(name: String!) on
+  | FIELD_DEFINITION
+  | ENUM_VALUE
+
+scalar DateTime @specifiedBy(url: "https://scalars.graphql.org/andimarek/date-time")
+
+enum Episode {
+  NEWHOPE
+  EMPIRE @deprecated
+  JEDI @tag(name: "jedi")
+}
+
+type Query {
+  hello: String @deprecated(reason: "Use `greeting`.")
+  greeting(episode: Episode): String @tag(name: "greeting")
+  now: DateTime
+}
+"#;
+
+fn build_schema() -> schema::Schema<sdl::SDL> {
+    sdl::SDL::new(SCHEMA)
+        .and_then(|sdl| sdl.build_schema())
+        .unwrap()
+}
+
+fn normalize(
+    schema: &schema::Schema<sdl::SDL>,
+    query: &str,
+) -> Result<Vec<(String, usize)>, validation::Error> {
+    let request = http::Request {
+        operation_name: None,
+        query: Parser::new(query).parse_executable_document().unwrap(),
+        variables: HashMap::new(),
+    };
+    let operation = validation::normalize_request(&sdl::Namespace, schema, &request)
+        .map_err(|errors| errors.0[0].error.clone())?;
+    let mut directives = operation
+        .directives
+        .iter()
+        .map(|directive| (directive.name.to_string(), directive.arguments.len()))
+        .collect::<Vec<_>>();
+    for field in operation.selection_set.fields.values() {
+        for field_call in field.field_calls.values() {
+            directives.extend(
+                field_call
+                    .directives
+                    .iter()
+                    .map(|directive| (directive.name.to_string(), directive.arguments.len())),
+            );
+        }
+    }
+    Ok(directives)
+}
+
+#[test]
+fn test_directive_definitions() {
+    let schema = build_schema();
+
+    let cached = schema
+        .get_directive(&Name::from_str("cached").unwrap())
+        .unwrap();
+    assert_eq!(
+        cached.description.as_deref(),
+        Some("Caches the response for `ttl` seconds")
+    );
+    assert_eq!(
+        cached.locations,
+        vec![DirectiveLocation::Query, DirectiveLocation::Field]
+    );
+    assert!(cached.is_repeatable);
+    assert_eq!(cached.arguments.len(), 2);
+
+    let tag = schema
+        .get_directive(&Name::from_str("tag").unwrap())
+        .unwrap();
+    assert_eq!(
+        tag.locations,
+        vec![
+            DirectiveLocation::FieldDefinition,
+            DirectiveLocation::EnumValue
+        ]
+    );
+    assert!(!tag.is_repeatable);
+
+    // the directives of the spec are always defined
+    for name in ["skip", "include", "deprecated", "specifiedBy"] {
+        assert!(schema
+            .get_directive(&Name::from_str(name).unwrap())
+            .is_some());
+    }
+}
+
+#[test]
+fn test_deprecated_and_specified_by() {
+    let schema = build_schema();
+    let Some(schema::TypeInfo::Object(query)) =
+        schema.get_type(&TypeName(Name::from_str("Query").unwrap()))
+    else {
+        panic!("Query is not an object");
+    };
+    let (hello, _) = query.fields[&Name::from_str("hello").unwrap()]
+        .get(&sdl::Namespace)
+        .unwrap();
+    assert_eq!(hello.deprecation_status.reason(), Some("Use `greeting`."));
+
+    let Some(schema::TypeInfo::Enum(episode)) =
+        schema.get_type(&TypeName(Name::from_str("Episode").unwrap()))
+    else {
+        panic!("Episode is not an enum");
+    };
+    let (empire, _) = episode.values[&Name::from_str("EMPIRE").unwrap()]
+        .get(&sdl::Namespace)
+        .unwrap();
+    assert_eq!(
+        empire.deprecation_status.reason(),
+        Some("No longer supported")
+    );
+
+    let Some(schema::TypeInfo::Scalar(date_time)) =
+        schema.get_type(&TypeName(Name::from_str("DateTime").unwrap()))
+    else {
+        panic!("DateTime is not a scalar");
+    };
+    assert_eq!(
+        date_time.specified_by_url.as_deref(),
+        Some("https://scalars.graphql.org/andimarek/date-time")
+    );
+}
+
+#[test]
+fn test_directive_usages() {
+    let schema = build_schema();
+    assert_eq!(
+        normalize(
+            &schema,
+            "query @cached(ttl: 10) { greeting @cached @cached(refresh: true) }"
+        )
+        .unwrap(),
+        vec![
+            ("cached".to_string(), 1),
+            ("cached".to_string(), 1),
+            ("cached".to_string(), 2)
+        ]
+    );
+    assert!(matches!(
+        normalize(&schema, "query { greeting @tag(name: \"greeting\") }"),
+        Err(validation::Error::DirectiveNotAllowed { .. })
+    ));
+    assert!(matches!(
+        normalize(&schema, "query { greeting @cached(ttl: \"10\") }"),
+        Err(validation::Error::IncorrectFormat { .. })
+    ));
+    assert!(matches!(
+        normalize(&schema, "query { greeting @cached(seconds: 10) }"),
+        Err(validation::Error::DirectiveArgumentsNotFound { .. })
+    ));
+    assert!(matches!(
+        normalize(&schema, "query { hello @skip(if: true) greeting @include(if: false) }"),
+        Ok(directives) if directives.is_empty()
+    ));
+}
+
+#[test]
+fn test_invalid_directive_definitions() {
+    assert!(matches!(
+        sdl::SDL::new(
+            "directive @cached on FIELD directive @cached on QUERY type Query { hello: String }"
+        ),
+        Err(sdl::SDLError::DuplicateDirectiveDefinitions(_))
+    ));
+    assert!(matches!(
+        sdl::SDL::new("directive @skip(if: Boolean!) on FIELD type Query { hello: String }")
+            .and_then(|sdl| sdl.build_schema()),
+        Err(sdl::SDLError::Internal(
+            schema::build::Error::ConflictingDirective(_)
+        ))
+    ));
+    assert!(matches!(
+        sdl::SDL::new("directive @cached on COLUMN type Query { hello: String }"),
+        Err(sdl::SDLError::ParseFailure(_))
+    ));
+}
@@ -0,0 +1,100 @@
+use lang_graphql::http;
+use lang_graphql::parser::Parser;
+use lang_graphql::schema::sdl;
+use lang_graphql::validation;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Validates a query against the schema of the test data. The query is
+/// expected to fail with the errors in `<query>.error.txt` if there is one, one
+/// per line along with their locations and paths, and to pass otherwise.
+/// Variables are read from `<query>.variables.json`.
+#[cfg(test)]
+fn test_validation_for_query(
+    schema: &lang_graphql::schema::Schema<sdl::SDL>,
+    query_path: &Path,
+) -> Result<(), io::Error> {
+    println!("Testing validation for query: {}", query_path.display());
+    let query = fs::read_to_string(query_path)?;
+    let variables = match fs::read_to_string(query_path.with_extension("variables.json")) {
+        Ok(variables) => serde_json::from_str(&variables)?,
+        Err(io_error) if matches!(io_error.kind(), io::ErrorKind::NotFound) => HashMap::new(),
+        Err(io_error) => return Err(io_error),
+    };
+    let expected_error = match fs::read_to_string(query_path.with_extension("error.txt")) {
+        Ok(expected_error) => Some(expected_error.trim_end().to_string()),
+        Err(io_error) if matches!(io_error.kind(), io::ErrorKind::NotFound) => None,
+        Err(io_error) => return Err(io_error),
+    };
+    let request = http::Request {
+        operation_name: None,
+        query: Parser::new(&query)
+            .parse_executable_document()
+            .unwrap_or_else(|err| panic!("Parsing error:\n{:#?}", err)),
+        variables,
+    };
+    let result = validation::normalize_request(&sdl::Namespace, schema, &request);
+    match (result, expected_error) {
+        (Ok(_), None) => {}
+        (Ok(_), Some(expected_error)) => {
+            panic!("expected validation to fail with: {expected_error}")
+        }
+        (Err(err), None) => panic!("unexpected validation error: {err}"),
+        (Err(err), Some(expected_error)) => {
+            assert_eq!(format_errors(&err), expected_error)
+        }
+    }
+    Ok(())
+}
+
+/// Formats each error on its own line as `<locations> at <path>: <message>`
+#[cfg(test)]
+fn format_errors(errors: &validation::Errors) -> String {
+    errors
+        .iter()
+        .map(|error| {
+            let locations = error
+                .locations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let path = if error.path.is_empty() {
+                String::new()
+            } else {
+                let path = error
+                    .path
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                format!(" at {path}")
+            };
+            format!("{locations}{path}: {error}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_validation() -> Result<(), io::Error> {
+    let mut testdata = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    testdata.push("tests");
+    testdata.push("testdata");
+    testdata.push("validation");
+    let schema = sdl::SDL::new(&fs::read_to_string(testdata.join("schema.graphql"))?)
+        .and_then(|sdl| sdl.build_schema())
+        .unwrap();
+    for dir_entry in fs::read_dir(testdata.join("queries"))? {
+        let dir_entry = dir_entry?;
+        if matches!(
+            dir_entry.path().extension().map(|e| e.to_str()),
+            Some(Some("graphql"))
+        ) {
+            test_validation_for_query(&schema, &dir_entry.path())?;
+        }
+    }
+    Ok(())
+}
@@ -88,6 +88,6 @@ pub fn urlencode<T: AsRef<str>>(s: T) -> String {
     ::url::form_urlencoded::byte_serialize(s.as_ref().as_bytes()).collect()
 }
 
-// pub mod default_api;
+pub mod default_api;
 
-// pub mod configuration;
\ No newline at end of file
+pub mod configuration;
\ No newline at end of file
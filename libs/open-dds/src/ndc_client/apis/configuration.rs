@@ -0,0 +1,22 @@
+use reqwest::header::{HeaderMap, HeaderValue};
+
+/// How to reach a data connector: the base URL which the endpoints of the
+/// NDC API are relative to, and the headers to send with each request.
+#[derive(Debug, Clone)]
+pub struct Configuration {
+    pub base_path: reqwest::Url,
+    pub user_agent: Option<String>,
+    pub client: reqwest::Client,
+    pub headers: HeaderMap<HeaderValue>,
+}
+
+impl Configuration {
+    pub fn new(base_path: reqwest::Url) -> Self {
+        Configuration {
+            base_path,
+            user_agent: None,
+            client: reqwest::Client::new(),
+            headers: HeaderMap::new(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! The endpoints of the NDC API. Requests are sent with `reqwest`, which uses
+//! `fetch` when compiled to wasm32, so these work both natively and in the
+//! browser.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{configuration, ConnectorError, Error, ErrorResponse};
+use crate::ndc_client::models;
+
+/// GET /capabilities
+pub async fn capabilities_get(
+    configuration: &configuration::Configuration,
+) -> Result<models::CapabilitiesResponse, Error> {
+    let request_builder = request(configuration, reqwest::Method::GET, "capabilities")?;
+    execute_request(configuration, request_builder).await
+}
+
+/// GET /schema
+pub async fn schema_get(
+    configuration: &configuration::Configuration,
+) -> Result<models::SchemaResponse, Error> {
+    let request_builder = request(configuration, reqwest::Method::GET, "schema")?;
+    execute_request(configuration, request_builder).await
+}
+
+/// POST /query
+pub async fn query_post(
+    configuration: &configuration::Configuration,
+    query_request: &models::QueryRequest,
+) -> Result<models::QueryResponse, Error> {
+    let request_builder = json_request(configuration, "query", query_request)?;
+    execute_request(configuration, request_builder).await
+}
+
+/// POST /mutation
+pub async fn mutation_post(
+    configuration: &configuration::Configuration,
+    mutation_request: &models::MutationRequest,
+) -> Result<models::MutationResponse, Error> {
+    let request_builder = json_request(configuration, "mutation", mutation_request)?;
+    execute_request(configuration, request_builder).await
+}
+
+/// POST /explain
+pub async fn explain_post(
+    configuration: &configuration::Configuration,
+    query_request: &models::QueryRequest,
+) -> Result<models::ExplainResponse, Error> {
+    let request_builder = json_request(configuration, "explain", query_request)?;
+    execute_request(configuration, request_builder).await
+}
+
+fn request(
+    configuration: &configuration::Configuration,
+    method: reqwest::Method,
+    endpoint: &str,
+) -> Result<reqwest::RequestBuilder, Error> {
+    let uri = append_path(&configuration.base_path, endpoint)?;
+    let mut request_builder = configuration.client.request(method, uri);
+    if let Some(user_agent) = &configuration.user_agent {
+        request_builder = request_builder.header(reqwest::header::USER_AGENT, user_agent.clone());
+    }
+    Ok(request_builder.headers(configuration.headers.clone()))
+}
+
+fn json_request<T: Serialize>(
+    configuration: &configuration::Configuration,
+    endpoint: &str,
+    body: &T,
+) -> Result<reqwest::RequestBuilder, Error> {
+    Ok(request(configuration, reqwest::Method::POST, endpoint)?.json(body))
+}
+
+/// Send a request, and parse the response. Responses with a non-2xx status are
+/// returned as a `ConnectorError`. If their body isn't an NDC `ErrorResponse`,
+/// the body is used as the error message.
+async fn execute_request<T: DeserializeOwned>(
+    configuration: &configuration::Configuration,
+    request_builder: reqwest::RequestBuilder,
+) -> Result<T, Error> {
+    let request = request_builder.build()?;
+    let response = configuration.client.execute(request).await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if status.is_success() {
+        Ok(serde_json::from_str(&body)?)
+    } else {
+        let error_response = serde_json::from_str(&body).unwrap_or(ErrorResponse {
+            message: body,
+            details: serde_json::Value::Null,
+        });
+        Err(Error::ConnectorError(ConnectorError {
+            status,
+            error_response,
+        }))
+    }
+}
+
+/// Append an endpoint to the base URL, keeping any path of the base URL
+fn append_path(url: &reqwest::Url, endpoint: &str) -> Result<reqwest::Url, Error> {
+    let mut url = url.clone();
+    url.path_segments_mut()
+        .map_err(|()| Error::InvalidBaseURL)?
+        .pop_if_empty()
+        .push(endpoint);
+    Ok(url)
+}
@@ -0,0 +1,169 @@
+//! Data connector implementations that the engine executes NDC requests against.
+//!
+//! Instead of talking to connectors over HTTP, the engine looks up a
+//! `Connector` by the name of the resolved `DataConnector` and hands it the
+//! NDC request directly. Connectors which aren't registered can optionally be
+//! called over HTTP, at the URL of the data connector in the metadata.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+
+use open_dds::data_connector::DataConnectorName;
+use open_dds::ndc_client as ndc;
+
+use crate::metadata::resolved::{self, subgraph::Qualified};
+use crate::schema::operations;
+
+pub mod error;
+pub mod http;
+pub mod js;
+pub mod memory;
+pub mod sqlite;
+
+/// An NDC data connector which can be called from within the engine.
+///
+/// The methods are asynchronous so that implementations can await calls to JS
+/// or to the network. The returned futures aren't required to be `Send` since
+/// the engine runs on a single thread in the browser.
+#[async_trait(?Send)]
+pub trait Connector {
+    /// The capabilities of this connector
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error>;
+
+    /// The NDC schema of this connector
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error>;
+
+    /// Execute a query request
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error>;
+
+    /// Execute a mutation request
+    async fn mutation(
+        &self,
+        request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error>;
+
+    /// Explain how a query request would be executed. Only called if the
+    /// connector advertises the `explain` capability.
+    async fn explain(
+        &self,
+        _request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::ExplainResponse, ndc::apis::Error> {
+        Err(ndc::apis::Error::ConnectorError(ndc::apis::ConnectorError {
+            status: reqwest::StatusCode::NOT_IMPLEMENTED,
+            error_response: ndc::apis::ErrorResponse {
+                message: "explain is not supported by this connector".to_string(),
+                details: serde_json::Value::Null,
+            },
+        }))
+    }
+}
+
+/// Registry of the connector implementations available to the engine, keyed
+/// by the (qualified) name of the data connector in the metadata. Cloning the
+/// registry shares the registered connectors.
+#[derive(Clone, Default)]
+pub struct Connectors {
+    connectors: HashMap<Qualified<DataConnectorName>, Rc<dyn Connector>>,
+    /// Connectors which serve the data connectors of a name in any subgraph
+    unqualified_connectors: HashMap<DataConnectorName, Rc<dyn Connector>>,
+    http_fallback: bool,
+    /// The HTTP connectors of the data connectors called over HTTP, so that
+    /// their client is reused across requests
+    http_connectors: RefCell<HashMap<Qualified<DataConnectorName>, Rc<http::HttpConnector>>>,
+}
+
+/// A connector looked up in the registry
+pub enum ConnectorRef<'a> {
+    Registered(&'a dyn Connector),
+    Http(Rc<http::HttpConnector>),
+}
+
+impl<'a> Deref for ConnectorRef<'a> {
+    type Target = dyn Connector + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ConnectorRef::Registered(connector) => *connector,
+            ConnectorRef::Http(connector) => connector.as_ref(),
+        }
+    }
+}
+
+impl Connectors {
+    pub fn new() -> Self {
+        Connectors::default()
+    }
+
+    /// Call data connectors which don't have a registered connector over HTTP,
+    /// instead of failing with `ConnectorNotFound`
+    pub fn with_http_fallback(mut self) -> Self {
+        self.http_fallback = true;
+        self
+    }
+
+    /// Register a connector for the given data connector. Returns the
+    /// previously registered connector, if any.
+    pub fn register(
+        &mut self,
+        name: Qualified<DataConnectorName>,
+        connector: impl Connector + 'static,
+    ) -> Option<Rc<dyn Connector>> {
+        self.connectors.insert(name, Rc::new(connector))
+    }
+
+    /// Register a connector for the data connectors of the given name in any
+    /// subgraph. Connectors registered for a qualified name take precedence.
+    /// Returns the previously registered connector, if any.
+    pub fn register_unqualified(
+        &mut self,
+        name: DataConnectorName,
+        connector: impl Connector + 'static,
+    ) -> Option<Rc<dyn Connector>> {
+        self.unqualified_connectors.insert(name, Rc::new(connector))
+    }
+
+    /// Look up the connector which serves the given data connector
+    pub fn get(
+        &self,
+        data_connector: &resolved::data_connector::DataConnector,
+    ) -> Result<ConnectorRef<'_>, operations::Error> {
+        let connector = self
+            .connectors
+            .get(&data_connector.name)
+            .or_else(|| self.unqualified_connectors.get(data_connector.name.name()));
+        match connector {
+            Some(connector) => Ok(ConnectorRef::Registered(connector.as_ref())),
+            None if self.http_fallback => {
+                Ok(ConnectorRef::Http(self.http_connector(data_connector)))
+            }
+            None => Err(operations::InternalDeveloperError::ConnectorNotFound {
+                data_connector_name: data_connector.name.clone(),
+            }
+            .into()),
+        }
+    }
+
+    /// The HTTP connector of the given data connector. It is built once, and
+    /// again only if the URL or the headers of the data connector change.
+    fn http_connector(
+        &self,
+        data_connector: &resolved::data_connector::DataConnector,
+    ) -> Rc<http::HttpConnector> {
+        let mut http_connectors = self.http_connectors.borrow_mut();
+        match http_connectors.get(&data_connector.name) {
+            Some(http_connector) if http_connector.is_for(data_connector) => http_connector.clone(),
+            _ => {
+                let http_connector = Rc::new(http::HttpConnector::new(data_connector));
+                http_connectors.insert(data_connector.name.clone(), http_connector.clone());
+                http_connector
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Errors of the connectors which execute NDC requests themselves, such as
+//! the SQLite and in-memory connectors, rather than sending them elsewhere.
+
+use std::collections::BTreeMap;
+
+use open_dds::ndc_client as ndc;
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("collection {collection} not found")]
+    CollectionNotFound { collection: String },
+
+    #[error("object type {object_type} not found")]
+    ObjectTypeNotFound { object_type: String },
+
+    #[error("column {column} not found in collection {collection}")]
+    ColumnNotFound { collection: String, column: String },
+
+    #[error("relationship {relationship} not found in the request")]
+    RelationshipNotFound { relationship: String },
+
+    #[error("variable {variable} not found")]
+    VariableNotFound { variable: String },
+
+    #[error("the relationship path of an aggregate must not be empty")]
+    EmptyAggregatePath,
+
+    #[error("unsupported comparison operator {operator}")]
+    UnsupportedComparisonOperator { operator: String },
+
+    #[error("unsupported aggregate function {function}")]
+    UnsupportedAggregateFunction { function: String },
+
+    #[error("collection {collection} doesn't accept arguments")]
+    CollectionArgumentsNotSupported { collection: String },
+
+    #[error("procedure {procedure} not found")]
+    ProcedureNotFound { procedure: String },
+
+    #[error("argument {argument} of procedure {procedure} not found")]
+    ArgumentNotFound { procedure: String, argument: String },
+
+    #[error("invalid argument {argument} of procedure {procedure}: {message}")]
+    InvalidArgument {
+        procedure: String,
+        argument: String,
+        message: String,
+    },
+
+    #[error("uniqueness constraint {constraint} of collection {collection} violated")]
+    UniquenessConstraintViolation {
+        collection: String,
+        constraint: String,
+    },
+
+    #[error("invalid response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+}
+
+impl From<Error> for ndc::apis::Error {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::UnsupportedComparisonOperator { .. }
+            | Error::UnsupportedAggregateFunction { .. }
+            | Error::CollectionArgumentsNotSupported { .. } => StatusCode::NOT_IMPLEMENTED,
+            Error::UniquenessConstraintViolation { .. } => StatusCode::CONFLICT,
+            Error::ObjectTypeNotFound { .. } | Error::InvalidResponse(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        ndc::apis::Error::ConnectorError(ndc::apis::ConnectorError {
+            status,
+            error_response: ndc::apis::ErrorResponse {
+                message: error.to_string(),
+                details: serde_json::Value::Null,
+            },
+        })
+    }
+}
+
+/// Neither connector supports collection arguments
+pub(super) fn check_no_arguments<T>(
+    collection: &str,
+    arguments: &BTreeMap<String, T>,
+) -> Result<(), Error> {
+    if arguments.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::CollectionArgumentsNotSupported {
+            collection: collection.to_string(),
+        })
+    }
+}
@@ -0,0 +1,82 @@
+//! A connector which calls a data connector over HTTP, at the URL of the data
+//! connector in the metadata.
+
+use async_trait::async_trait;
+use lang_graphql::ast::common::OperationType;
+use open_dds::ndc_client as ndc;
+
+use super::Connector;
+use crate::metadata::resolved::data_connector::{
+    DataConnector, ResolvedDataConnectorUrl, SerializableHeaderMap,
+};
+
+/// Calls the NDC API of a data connector. Queries (and the capabilities and
+/// schema) are sent to the read URL of the data connector, and mutations to
+/// its write URL.
+pub struct HttpConnector {
+    url: ResolvedDataConnectorUrl,
+    headers: SerializableHeaderMap,
+    client: reqwest::Client,
+}
+
+impl HttpConnector {
+    pub fn new(data_connector: &DataConnector) -> Self {
+        HttpConnector {
+            url: data_connector.url.clone(),
+            headers: data_connector.headers.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Whether this connector calls the given data connector, at its current
+    /// URL and with its current headers
+    pub fn is_for(&self, data_connector: &DataConnector) -> bool {
+        self.url == data_connector.url && self.headers == data_connector.headers
+    }
+
+    fn configuration(&self, operation: OperationType) -> ndc::apis::configuration::Configuration {
+        ndc::apis::configuration::Configuration {
+            base_path: self.url.get_url(operation),
+            user_agent: None,
+            client: self.client.clone(),
+            headers: self.headers.0.clone(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Connector for HttpConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        let configuration = self.configuration(OperationType::Query);
+        ndc::apis::default_api::capabilities_get(&configuration).await
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        let configuration = self.configuration(OperationType::Query);
+        ndc::apis::default_api::schema_get(&configuration).await
+    }
+
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        let configuration = self.configuration(OperationType::Query);
+        ndc::apis::default_api::query_post(&configuration, &request).await
+    }
+
+    async fn mutation(
+        &self,
+        request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        let configuration = self.configuration(OperationType::Mutation);
+        ndc::apis::default_api::mutation_post(&configuration, &request).await
+    }
+
+    async fn explain(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::ExplainResponse, ndc::apis::Error> {
+        let configuration = self.configuration(OperationType::Query);
+        ndc::apis::default_api::explain_post(&configuration, &request).await
+    }
+}
@@ -0,0 +1,190 @@
+//! A connector implemented in JS, such as the Turso connector in
+//! `www/connector/query.ts`. The engine plans the NDC requests and hands them
+//! to the connector, which only has to execute them.
+
+use async_trait::async_trait;
+use js_sys::{Function, Promise, Reflect, JSON};
+use open_dds::ndc_client as ndc;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::Connector;
+
+/// A connector object passed from JS, e.g.
+///
+/// ```js
+/// {
+///   query: async (request) => response,
+///   mutation: async (request) => response,
+///   capabilities: async () => capabilities,  // optional
+///   schema: async () => schema,              // optional
+///   explain: async (request) => explanation, // optional
+/// }
+/// ```
+///
+/// Requests and responses are plain JSON objects, as they would be sent over
+/// HTTP. The functions may return either a response or a `Promise` of one.
+/// A thrown error or a rejected `Promise` becomes a connector error; the
+/// `statusCode` and `details` of the errors of `@hasura/ndc-sdk-typescript`
+/// are kept.
+pub struct JsConnector {
+    object: JsValue,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("the connector must be an object")]
+    NotAnObject,
+    #[error("the connector has no '{function}' function")]
+    FunctionNotFound { function: String },
+}
+
+impl JsConnector {
+    /// Wraps a JS connector object, checking that it has `query` and
+    /// `mutation` functions
+    pub fn new(object: JsValue) -> Result<Self, Error> {
+        if !object.is_object() {
+            return Err(Error::NotAnObject);
+        }
+        let connector = JsConnector { object };
+        for function in ["query", "mutation"] {
+            if connector.function(function).is_none() {
+                return Err(Error::FunctionNotFound {
+                    function: function.to_string(),
+                });
+            }
+        }
+        Ok(connector)
+    }
+
+    fn function(&self, name: &str) -> Option<Function> {
+        Reflect::get(&self.object, &JsValue::from_str(name))
+            .ok()?
+            .dyn_into::<Function>()
+            .ok()
+    }
+
+    /// Calls a function of the connector with the given request, if any, and
+    /// awaits the response
+    async fn call<Request: Serialize, Response: DeserializeOwned>(
+        &self,
+        name: &str,
+        request: Option<&Request>,
+    ) -> Result<Response, ndc::apis::Error> {
+        let function = self.function(name).ok_or_else(|| {
+            connector_error(
+                StatusCode::NOT_IMPLEMENTED,
+                format!("the connector has no '{name}' function"),
+                serde_json::Value::Null,
+            )
+        })?;
+        let result = match request {
+            Some(request) => {
+                let request = to_js(request)?;
+                function.call1(&self.object, &request)
+            }
+            None => function.call0(&self.object),
+        };
+        let response = match result {
+            Ok(value) => JsFuture::from(Promise::resolve(&value)).await,
+            Err(error) => Err(error),
+        }
+        .map_err(js_error)?;
+        from_js(&response)
+    }
+}
+
+#[async_trait(?Send)]
+impl Connector for JsConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        self.call::<(), _>("capabilities", None).await
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        self.call::<(), _>("schema", None).await
+    }
+
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        self.call("query", Some(&request)).await
+    }
+
+    async fn mutation(
+        &self,
+        request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        self.call("mutation", Some(&request)).await
+    }
+
+    async fn explain(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::ExplainResponse, ndc::apis::Error> {
+        self.call("explain", Some(&request)).await
+    }
+}
+
+/// Converts a value to a JS object by way of JSON, so that the connector sees
+/// the same request as it would over HTTP
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, ndc::apis::Error> {
+    let json = serde_json::to_string(value)?;
+    JSON::parse(&json).map_err(|error| invalid_value("request", error))
+}
+
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, ndc::apis::Error> {
+    let json = JSON::stringify(value)
+        .map_err(|error| invalid_value("response", error))?
+        .as_string()
+        .unwrap_or_default();
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn invalid_value(kind: &str, error: JsValue) -> ndc::apis::Error {
+    connector_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("invalid {kind}: {}", error_message(&error)),
+        serde_json::Value::Null,
+    )
+}
+
+/// Converts an error thrown by the connector to a connector error
+fn js_error(error: JsValue) -> ndc::apis::Error {
+    let property = |name: &str| {
+        Reflect::get(&error, &JsValue::from_str(name))
+            .ok()
+            .filter(|value| !value.is_undefined())
+    };
+    let status = property("statusCode")
+        .and_then(|status| status.as_f64())
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let details = property("details")
+        .and_then(|details| from_js(&details).ok())
+        .unwrap_or(serde_json::Value::Null);
+    connector_error(status, error_message(&error), details)
+}
+
+fn error_message(error: &JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => error
+            .as_string()
+            .unwrap_or_else(|| "the connector failed".to_string()),
+    }
+}
+
+fn connector_error(
+    status: StatusCode,
+    message: String,
+    details: serde_json::Value,
+) -> ndc::apis::Error {
+    ndc::apis::Error::ConnectorError(ndc::apis::ConnectorError {
+        status,
+        error_response: ndc::apis::ErrorResponse { message, details },
+    })
+}
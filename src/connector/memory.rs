@@ -0,0 +1,136 @@
+//! An NDC connector which holds its collections in memory, for tests and for
+//! managing local state in the browser.
+//!
+//! The connector is built from an NDC schema, such as the one of a
+//! `DataConnector` in the metadata, and starts with an empty collection for
+//! each of its collections. Procedures which insert, update and delete rows
+//! are added to the schema.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use open_dds::ndc_client as ndc;
+
+use super::Connector;
+
+pub use super::error::Error;
+
+mod mutation;
+mod query;
+mod schema;
+
+/// A row of a collection
+pub type Row = IndexMap<String, serde_json::Value>;
+
+/// The rows of each collection
+type Collections = BTreeMap<String, Vec<Row>>;
+
+/// An in-memory connector. Clones share the same collections, so a clone can
+/// be kept to read and write the rows of a connector registered with the
+/// engine.
+#[derive(Clone)]
+pub struct MemoryConnector {
+    schema: Rc<ndc::models::SchemaResponse>,
+    collections: Rc<RefCell<Collections>>,
+}
+
+impl MemoryConnector {
+    /// A connector with empty collections for the collections of the schema
+    pub fn new(schema: ndc::models::SchemaResponse) -> Self {
+        let schema = schema::with_procedures(schema);
+        let collections = schema
+            .collections
+            .iter()
+            .map(|collection| (collection.name.clone(), Vec::new()))
+            .collect();
+        MemoryConnector {
+            schema: Rc::new(schema),
+            collections: Rc::new(RefCell::new(collections)),
+        }
+    }
+
+    /// Append rows to a collection, without checking its uniqueness
+    /// constraints
+    pub fn insert_rows(
+        &self,
+        collection: &str,
+        rows: impl IntoIterator<Item = Row>,
+    ) -> Result<(), Error> {
+        let mut collections = self.collections.borrow_mut();
+        let collection_rows =
+            collections
+                .get_mut(collection)
+                .ok_or_else(|| Error::CollectionNotFound {
+                    collection: collection.to_string(),
+                })?;
+        collection_rows.extend(rows);
+        Ok(())
+    }
+
+    /// The rows of a collection
+    pub fn rows(&self, collection: &str) -> Result<Vec<Row>, Error> {
+        self.collections
+            .borrow()
+            .get(collection)
+            .cloned()
+            .ok_or_else(|| Error::CollectionNotFound {
+                collection: collection.to_string(),
+            })
+    }
+
+    /// The schema of the connector, including the generated procedures
+    pub fn schema_response(&self) -> &ndc::models::SchemaResponse {
+        &self.schema
+    }
+
+    pub fn capabilities_response() -> ndc::models::CapabilitiesResponse {
+        ndc::models::CapabilitiesResponse {
+            versions: "^0.1.0".to_string(),
+            capabilities: ndc::models::Capabilities {
+                explain: None,
+                query: ndc::models::QueryCapabilities {
+                    aggregates: Some(ndc::models::LeafCapability {}),
+                    variables: Some(ndc::models::LeafCapability {}),
+                },
+                relationships: Some(ndc::models::RelationshipCapabilities {
+                    relation_comparisons: Some(ndc::models::LeafCapability {}),
+                    order_by_aggregate: Some(ndc::models::LeafCapability {}),
+                }),
+            },
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Connector for MemoryConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        Ok(MemoryConnector::capabilities_response())
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        Ok(self.schema.as_ref().clone())
+    }
+
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        let collections = self.collections.borrow();
+        Ok(query::execute_query(&self.schema, &collections, &request)?)
+    }
+
+    async fn mutation(
+        &self,
+        request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        // Procedures are applied to a copy of the collections, so that a
+        // failed request leaves the collections unchanged
+        let mut collections = self.collections.borrow().clone();
+        let response = mutation::execute_mutation(&self.schema, &mut collections, &request)?;
+        *self.collections.borrow_mut() = collections;
+        Ok(response)
+    }
+}
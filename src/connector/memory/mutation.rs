@@ -0,0 +1,261 @@
+//! Applies NDC mutation requests to the rows held in memory.
+
+use std::collections::BTreeMap;
+
+use indexmap::IndexMap;
+use open_dds::ndc_client as ndc;
+
+use super::query::{Evaluator, Scope};
+use super::schema::{Procedure, OBJECTS_ARGUMENT, SET_ARGUMENT};
+use super::{Collections, Error, Row};
+
+/// Apply the operations of a mutation request in order. The fields of the
+/// affected rows are returned in the `__value` field of a single row, as
+/// procedures return a single value.
+pub(super) fn execute_mutation(
+    schema: &ndc::models::SchemaResponse,
+    collections: &mut Collections,
+    request: &ndc::models::MutationRequest,
+) -> Result<ndc::models::MutationResponse, Error> {
+    let operation_results = request
+        .operations
+        .iter()
+        .map(|operation| match operation {
+            ndc::models::MutationOperation::Procedure {
+                name,
+                arguments,
+                fields,
+            } => {
+                let procedure = ProcedureCall {
+                    schema,
+                    name,
+                    arguments,
+                };
+                let (collection, affected_rows, single_row) = match Procedure::find(schema, name)
+                    .ok_or_else(|| Error::ProcedureNotFound {
+                        procedure: name.clone(),
+                    })? {
+                    Procedure::Insert { collection } => (
+                        collection,
+                        procedure.insert(collections, collection)?,
+                        false,
+                    ),
+                    Procedure::UpdateByPk { collection, key } => (
+                        collection,
+                        procedure.update_by_pk(collections, collection, key)?,
+                        true,
+                    ),
+                    Procedure::DeleteByPk { collection, key } => (
+                        collection,
+                        procedure.delete_by_pk(collections, collection, key)?,
+                        true,
+                    ),
+                };
+
+                let returning = fields
+                    .as_ref()
+                    .map(|fields| {
+                        let evaluator = Evaluator {
+                            schema,
+                            collections,
+                            collection_relationships: &request.collection_relationships,
+                            variables: None,
+                        };
+                        let rows = affected_rows
+                            .iter()
+                            .map(|row| {
+                                let scope = Scope {
+                                    collection: &collection.name,
+                                    row,
+                                };
+                                Ok(serde_json::to_value(evaluator.fields(fields, scope)?)?)
+                            })
+                            .collect::<Result<Vec<_>, Error>>()?;
+                        let value = if single_row {
+                            rows.into_iter().next().unwrap_or(serde_json::Value::Null)
+                        } else {
+                            serde_json::Value::Array(rows)
+                        };
+                        Ok::<_, Error>(vec![IndexMap::from([(
+                            "__value".to_string(),
+                            ndc::models::RowFieldValue(value),
+                        )])])
+                    })
+                    .transpose()?;
+                Ok(ndc::models::MutationOperationResults {
+                    affected_rows: affected_rows.len() as u32,
+                    returning,
+                })
+            }
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok(ndc::models::MutationResponse { operation_results })
+}
+
+struct ProcedureCall<'a> {
+    schema: &'a ndc::models::SchemaResponse,
+    name: &'a str,
+    arguments: &'a BTreeMap<String, serde_json::Value>,
+}
+
+impl<'a> ProcedureCall<'a> {
+    /// Insert the `objects` argument, returning the inserted rows
+    fn insert(
+        &self,
+        collections: &mut Collections,
+        collection: &ndc::models::CollectionInfo,
+    ) -> Result<Vec<Row>, Error> {
+        let objects = self.argument(OBJECTS_ARGUMENT)?.as_array().ok_or_else(|| {
+            self.invalid_argument(OBJECTS_ARGUMENT, "expected an array of objects")
+        })?;
+        let mut inserted_rows = Vec::new();
+        for object in objects {
+            let row = self.row(collections, collection, OBJECTS_ARGUMENT, object)?;
+            let rows = collection_rows_mut(collections, collection)?;
+            check_uniqueness(collection, rows, &row)?;
+            rows.push(row.clone());
+            inserted_rows.push(row);
+        }
+        Ok(inserted_rows)
+    }
+
+    /// Update the columns of the row with the given key to the values of the
+    /// `_set` argument, returning the updated row
+    fn update_by_pk(
+        &self,
+        collections: &mut Collections,
+        collection: &ndc::models::CollectionInfo,
+        key: &[String],
+    ) -> Result<Vec<Row>, Error> {
+        let new_values = match self.arguments.get(SET_ARGUMENT) {
+            None | Some(serde_json::Value::Null) => Row::new(),
+            Some(new_values) => self.row(collections, collection, SET_ARGUMENT, new_values)?,
+        };
+        let key_values = self.key_values(key)?;
+        let rows = collection_rows_mut(collections, collection)?;
+        let Some(index) = find_row(rows, &key_values) else {
+            return Ok(Vec::new());
+        };
+        let mut row = rows.remove(index);
+        row.extend(new_values);
+        let uniqueness = check_uniqueness(collection, rows, &row);
+        rows.insert(index, row.clone());
+        uniqueness?;
+        Ok(vec![row])
+    }
+
+    /// Delete the row with the given key, returning the deleted row
+    fn delete_by_pk(
+        &self,
+        collections: &mut Collections,
+        collection: &ndc::models::CollectionInfo,
+        key: &[String],
+    ) -> Result<Vec<Row>, Error> {
+        let key_values = self.key_values(key)?;
+        let rows = collection_rows_mut(collections, collection)?;
+        Ok(find_row(rows, &key_values)
+            .map(|index| rows.remove(index))
+            .into_iter()
+            .collect())
+    }
+
+    /// Convert an argument to a row, checking that its fields are columns of
+    /// the collection
+    fn row(
+        &self,
+        collections: &Collections,
+        collection: &ndc::models::CollectionInfo,
+        argument: &str,
+        value: &serde_json::Value,
+    ) -> Result<Row, Error> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| self.invalid_argument(argument, "expected an object"))?;
+        let evaluator = Evaluator {
+            schema: self.schema,
+            collections,
+            collection_relationships: &BTreeMap::new(),
+            variables: None,
+        };
+        object
+            .iter()
+            .map(|(column, value)| {
+                evaluator.check_column(&collection.name, column)?;
+                Ok((column.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn key_values(
+        &self,
+        key: &'a [String],
+    ) -> Result<Vec<(&'a str, &'a serde_json::Value)>, Error> {
+        key.iter()
+            .map(|column| Ok((column.as_str(), self.argument(column)?)))
+            .collect()
+    }
+
+    fn argument(&self, argument: &str) -> Result<&'a serde_json::Value, Error> {
+        self.arguments
+            .get(argument)
+            .ok_or_else(|| Error::ArgumentNotFound {
+                procedure: self.name.to_string(),
+                argument: argument.to_string(),
+            })
+    }
+
+    fn invalid_argument(&self, argument: &str, message: &str) -> Error {
+        Error::InvalidArgument {
+            procedure: self.name.to_string(),
+            argument: argument.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn collection_rows_mut<'c>(
+    collections: &'c mut Collections,
+    collection: &ndc::models::CollectionInfo,
+) -> Result<&'c mut Vec<Row>, Error> {
+    collections
+        .get_mut(&collection.name)
+        .ok_or_else(|| Error::CollectionNotFound {
+            collection: collection.name.clone(),
+        })
+}
+
+fn find_row(rows: &[Row], key_values: &[(&str, &serde_json::Value)]) -> Option<usize> {
+    rows.iter().position(|row| {
+        key_values
+            .iter()
+            .all(|(column, value)| row.get(*column) == Some(*value))
+    })
+}
+
+/// Check that a row doesn't have the same unique columns as any of the rows
+/// of the collection. Rows with null unique columns are never duplicates.
+fn check_uniqueness(
+    collection: &ndc::models::CollectionInfo,
+    rows: &[Row],
+    row: &Row,
+) -> Result<(), Error> {
+    for (constraint_name, constraint) in &collection.uniqueness_constraints {
+        let key_values = constraint
+            .unique_columns
+            .iter()
+            .map(|column| {
+                let value = row.get(column).filter(|value| !value.is_null())?;
+                Some((column.as_str(), value))
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(key_values) = key_values {
+            if find_row(rows, &key_values).is_some() {
+                return Err(Error::UniquenessConstraintViolation {
+                    collection: collection.name.clone(),
+                    constraint: constraint_name.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
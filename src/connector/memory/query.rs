@@ -0,0 +1,720 @@
+//! Evaluates NDC query requests against the rows held in memory.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+use indexmap::IndexMap;
+use open_dds::ndc_client as ndc;
+
+use super::{Collections, Error, Row};
+use crate::connector::error::check_no_arguments;
+
+/// Evaluate a query request, returning a row set for each set of variables in
+/// the request, or a single row set if there are none
+pub(super) fn execute_query(
+    schema: &ndc::models::SchemaResponse,
+    collections: &Collections,
+    request: &ndc::models::QueryRequest,
+) -> Result<ndc::models::QueryResponse, Error> {
+    check_no_arguments(&request.collection, &request.arguments)?;
+    let row_set = |variables| {
+        let evaluator = Evaluator {
+            schema,
+            collections,
+            collection_relationships: &request.collection_relationships,
+            variables,
+        };
+        let rows = evaluator.collection_rows(&request.collection)?;
+        evaluator.row_set(&request.collection, rows, &request.query)
+    };
+    let row_sets = match &request.variables {
+        None => vec![row_set(None)?],
+        Some(variable_sets) => variable_sets
+            .iter()
+            .map(|variables| row_set(Some(variables)))
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(ndc::models::QueryResponse(row_sets))
+}
+
+/// A row, along with the collection it belongs to
+#[derive(Clone, Copy)]
+pub(super) struct Scope<'a> {
+    pub collection: &'a str,
+    pub row: &'a Row,
+}
+
+pub(super) struct Evaluator<'a> {
+    pub schema: &'a ndc::models::SchemaResponse,
+    pub collections: &'a Collections,
+    pub collection_relationships: &'a BTreeMap<String, ndc::models::Relationship>,
+    pub variables: Option<&'a BTreeMap<String, serde_json::Value>>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn collection_rows(&self, collection: &'a str) -> Result<Vec<Scope<'a>>, Error> {
+        let rows = self
+            .collections
+            .get(collection)
+            .ok_or_else(|| Error::CollectionNotFound {
+                collection: collection.to_string(),
+            })?;
+        Ok(rows.iter().map(|row| Scope { collection, row }).collect())
+    }
+
+    /// The row set of a query over the given rows: the rows are filtered,
+    /// sorted and paginated, before the fields and aggregates are evaluated
+    pub fn row_set(
+        &self,
+        collection: &'a str,
+        rows: Vec<Scope<'a>>,
+        query: &'a ndc::models::Query,
+    ) -> Result<ndc::models::RowSet, Error> {
+        let mut rows = match &query.predicate {
+            None => rows,
+            Some(predicate) => {
+                let mut filtered = Vec::new();
+                for scope in rows {
+                    if self.expression(predicate, scope, scope)? {
+                        filtered.push(scope);
+                    }
+                }
+                filtered
+            }
+        };
+        if let Some(order_by) = &query.order_by {
+            rows = self.sort(rows, order_by)?;
+        }
+        let offset = query.offset.map_or(0, |offset| offset as usize);
+        let limit = query.limit.map_or(usize::MAX, |limit| limit as usize);
+        let rows = rows
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect::<Vec<_>>();
+
+        let aggregates = query
+            .aggregates
+            .as_ref()
+            .map(|aggregates| {
+                aggregates
+                    .iter()
+                    .map(|(name, aggregate)| {
+                        Ok((name.clone(), self.aggregate(collection, &rows, aggregate)?))
+                    })
+                    .collect::<Result<IndexMap<_, _>, Error>>()
+            })
+            .transpose()?;
+        let rows = query
+            .fields
+            .as_ref()
+            .map(|fields| {
+                rows.iter()
+                    .map(|scope| self.fields(fields, *scope))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        Ok(ndc::models::RowSet { aggregates, rows })
+    }
+
+    /// The requested fields of a row
+    pub fn fields(
+        &self,
+        fields: &'a IndexMap<String, ndc::models::Field>,
+        scope: Scope<'a>,
+    ) -> Result<IndexMap<String, ndc::models::RowFieldValue>, Error> {
+        fields
+            .iter()
+            .map(|(field_name, field)| {
+                let value = match field {
+                    ndc::models::Field::Column { column } => self.column(scope, column)?,
+                    ndc::models::Field::Relationship {
+                        query,
+                        relationship,
+                        arguments,
+                    } => {
+                        let relationship = self.relationship(relationship, arguments)?;
+                        let rows = self.related_rows(scope, relationship)?;
+                        let row_set = self.row_set(&relationship.target_collection, rows, query)?;
+                        serde_json::to_value(row_set)?
+                    }
+                };
+                Ok((field_name.clone(), ndc::models::RowFieldValue(value)))
+            })
+            .collect()
+    }
+
+    /// The value of a column of a row. Columns of the object type which are
+    /// missing from the row are null.
+    pub fn column(&self, scope: Scope, column: &str) -> Result<serde_json::Value, Error> {
+        self.check_column(scope.collection, column)?;
+        Ok(scope
+            .row
+            .get(column)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null))
+    }
+
+    pub fn check_column(&self, collection: &str, column: &str) -> Result<(), Error> {
+        if self.object_type(collection)?.fields.contains_key(column) {
+            Ok(())
+        } else {
+            Err(Error::ColumnNotFound {
+                collection: collection.to_string(),
+                column: column.to_string(),
+            })
+        }
+    }
+
+    fn object_type(&self, collection: &str) -> Result<&'a ndc::models::ObjectType, Error> {
+        let collection_info = self
+            .schema
+            .collections
+            .iter()
+            .find(|collection_info| collection_info.name == collection)
+            .ok_or_else(|| Error::CollectionNotFound {
+                collection: collection.to_string(),
+            })?;
+        self.schema
+            .object_types
+            .get(&collection_info.collection_type)
+            .ok_or_else(|| Error::ObjectTypeNotFound {
+                object_type: collection_info.collection_type.clone(),
+            })
+    }
+
+    fn variable(&self, name: &str) -> Result<serde_json::Value, Error> {
+        self.variables
+            .and_then(|variables| variables.get(name))
+            .cloned()
+            .ok_or_else(|| Error::VariableNotFound {
+                variable: name.to_string(),
+            })
+    }
+
+    fn relationship(
+        &self,
+        name: &str,
+        arguments: &BTreeMap<String, ndc::models::RelationshipArgument>,
+    ) -> Result<&'a ndc::models::Relationship, Error> {
+        let relationship =
+            self.collection_relationships
+                .get(name)
+                .ok_or_else(|| Error::RelationshipNotFound {
+                    relationship: name.to_string(),
+                })?;
+        check_no_arguments(&relationship.target_collection, arguments)?;
+        check_no_arguments(&relationship.target_collection, &relationship.arguments)?;
+        Ok(relationship)
+    }
+
+    /// The rows of the target collection whose columns match the columns of
+    /// the source row
+    fn related_rows(
+        &self,
+        source: Scope<'a>,
+        relationship: &'a ndc::models::Relationship,
+    ) -> Result<Vec<Scope<'a>>, Error> {
+        let mut source_values = Vec::new();
+        for (source_column, target_column) in &relationship.column_mapping {
+            self.check_column(&relationship.target_collection, target_column)?;
+            source_values.push((self.column(source, source_column)?, target_column));
+        }
+        let rows = self.collection_rows(&relationship.target_collection)?;
+        Ok(rows
+            .into_iter()
+            .filter(|target| {
+                source_values.iter().all(|(source_value, target_column)| {
+                    let target_value = target.row.get(target_column.as_str());
+                    target_value.is_some_and(|target_value| equal(source_value, target_value))
+                })
+            })
+            .collect())
+    }
+
+    /// The rows at the end of a relationship path, which match the predicates
+    /// along the path
+    fn path(
+        &self,
+        scope: Scope<'a>,
+        root: Scope<'a>,
+        path: &'a [ndc::models::PathElement],
+    ) -> Result<Vec<Scope<'a>>, Error> {
+        let mut rows = vec![scope];
+        for path_element in path {
+            let relationship =
+                self.relationship(&path_element.relationship, &path_element.arguments)?;
+            let mut next_rows = Vec::new();
+            for source in rows {
+                for target in self.related_rows(source, relationship)? {
+                    if self.expression(&path_element.predicate, root, target)? {
+                        next_rows.push(target);
+                    }
+                }
+            }
+            rows = next_rows;
+        }
+        Ok(rows)
+    }
+
+    fn sort(
+        &self,
+        rows: Vec<Scope<'a>>,
+        order_by: &'a ndc::models::OrderBy,
+    ) -> Result<Vec<Scope<'a>>, Error> {
+        let mut keyed_rows = rows
+            .into_iter()
+            .map(|scope| {
+                let keys = order_by
+                    .elements
+                    .iter()
+                    .map(|element| self.sort_key(scope, &element.target))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((keys, scope))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // The sort is stable, so rows which compare equal keep their order
+        keyed_rows.sort_by(|(left_keys, _), (right_keys, _)| {
+            order_by
+                .elements
+                .iter()
+                .zip(left_keys.iter().zip(right_keys))
+                .map(|(element, (left, right))| match element.order_direction {
+                    ndc::models::OrderDirection::Asc => sort_order(left, right),
+                    ndc::models::OrderDirection::Desc => sort_order(right, left),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(keyed_rows.into_iter().map(|(_, scope)| scope).collect())
+    }
+
+    fn sort_key(
+        &self,
+        scope: Scope<'a>,
+        target: &'a ndc::models::OrderByTarget,
+    ) -> Result<serde_json::Value, Error> {
+        match target {
+            ndc::models::OrderByTarget::Column { name, path } => {
+                match self.path(scope, scope, path)?.first() {
+                    Some(target) => self.column(*target, name),
+                    None => Ok(serde_json::Value::Null),
+                }
+            }
+            ndc::models::OrderByTarget::SingleColumnAggregate {
+                column,
+                function,
+                path,
+            } => {
+                let rows = self.path(scope, scope, path)?;
+                let values = rows
+                    .iter()
+                    .map(|target| self.column(*target, column))
+                    .collect::<Result<Vec<_>, _>>()?;
+                aggregate_function(function, values)
+            }
+            ndc::models::OrderByTarget::StarCountAggregate { path } => {
+                Ok(self.path(scope, scope, path)?.len().into())
+            }
+        }
+    }
+
+    fn aggregate(
+        &self,
+        collection: &str,
+        rows: &[Scope<'a>],
+        aggregate: &ndc::models::Aggregate,
+    ) -> Result<serde_json::Value, Error> {
+        match aggregate {
+            ndc::models::Aggregate::StarCount {} => Ok(rows.len().into()),
+            ndc::models::Aggregate::ColumnCount { column, distinct } => {
+                self.check_column(collection, column)?;
+                let values = self.column_values(rows, column)?;
+                let values = values.iter().filter(|value| !value.is_null());
+                if *distinct {
+                    let distinct_values = values
+                        .map(serde_json::Value::to_string)
+                        .collect::<BTreeSet<_>>();
+                    Ok(distinct_values.len().into())
+                } else {
+                    Ok(values.count().into())
+                }
+            }
+            ndc::models::Aggregate::SingleColumn { column, function } => {
+                self.check_column(collection, column)?;
+                aggregate_function(function, self.column_values(rows, column)?)
+            }
+        }
+    }
+
+    fn column_values(
+        &self,
+        rows: &[Scope<'a>],
+        column: &str,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        rows.iter()
+            .map(|scope| self.column(*scope, column))
+            .collect()
+    }
+
+    /// Evaluate a predicate against a row. Columns of the root collection
+    /// refer to the `root` row, which is the row of the query being filtered.
+    pub fn expression(
+        &self,
+        expression: &'a ndc::models::Expression,
+        root: Scope<'a>,
+        scope: Scope<'a>,
+    ) -> Result<bool, Error> {
+        match expression {
+            ndc::models::Expression::And { expressions } => {
+                for expression in expressions {
+                    if !self.expression(expression, root, scope)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            ndc::models::Expression::Or { expressions } => {
+                for expression in expressions {
+                    if self.expression(expression, root, scope)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            ndc::models::Expression::Not { expression } => {
+                Ok(!self.expression(expression, root, scope)?)
+            }
+            ndc::models::Expression::UnaryComparisonOperator { column, operator } => match operator
+            {
+                ndc::models::UnaryComparisonOperator::IsNull => {
+                    let values = self.comparison_target(column, root, scope)?;
+                    Ok(values.iter().any(serde_json::Value::is_null))
+                }
+            },
+            ndc::models::Expression::BinaryComparisonOperator {
+                column,
+                operator,
+                value,
+            } => {
+                let left_values = self.comparison_target(column, root, scope)?;
+                let right_values = self.comparison_value(value, root, scope)?;
+                for left in &left_values {
+                    for right in &right_values {
+                        if compare(operator, left, right)? {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(false)
+            }
+            ndc::models::Expression::BinaryArrayComparisonOperator {
+                column,
+                operator,
+                values,
+            } => match operator {
+                ndc::models::BinaryArrayComparisonOperator::In => {
+                    let left_values = self.comparison_target(column, root, scope)?;
+                    let mut right_values = Vec::new();
+                    for value in values {
+                        right_values.extend(self.comparison_value(value, root, scope)?);
+                    }
+                    Ok(left_values
+                        .iter()
+                        .any(|left| right_values.iter().any(|right| equal(left, right))))
+                }
+            },
+            ndc::models::Expression::Exists {
+                in_collection,
+                predicate,
+            } => {
+                let rows = match in_collection {
+                    ndc::models::ExistsInCollection::Related {
+                        relationship,
+                        arguments,
+                    } => {
+                        let relationship = self.relationship(relationship, arguments)?;
+                        self.related_rows(scope, relationship)?
+                    }
+                    ndc::models::ExistsInCollection::Unrelated {
+                        collection,
+                        arguments,
+                    } => {
+                        check_no_arguments(collection, arguments)?;
+                        self.collection_rows(collection)?
+                    }
+                };
+                for row in rows {
+                    if self.expression(predicate, root, row)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn comparison_target(
+        &self,
+        target: &'a ndc::models::ComparisonTarget,
+        root: Scope<'a>,
+        scope: Scope<'a>,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        match target {
+            ndc::models::ComparisonTarget::Column { name, path } => self
+                .path(scope, root, path)?
+                .into_iter()
+                .map(|target| self.column(target, name))
+                .collect(),
+            ndc::models::ComparisonTarget::RootCollectionColumn { name } => {
+                Ok(vec![self.column(root, name)?])
+            }
+        }
+    }
+
+    fn comparison_value(
+        &self,
+        value: &'a ndc::models::ComparisonValue,
+        root: Scope<'a>,
+        scope: Scope<'a>,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        match value {
+            ndc::models::ComparisonValue::Column { column } => {
+                self.comparison_target(column, root, scope)
+            }
+            ndc::models::ComparisonValue::Scalar { value } => Ok(vec![value.clone()]),
+            ndc::models::ComparisonValue::Variable { name } => Ok(vec![self.variable(name)?]),
+        }
+    }
+}
+
+/// Whether two values are equal. As in SQL, null isn't equal to anything.
+fn equal(left: &serde_json::Value, right: &serde_json::Value) -> bool {
+    match (left, right) {
+        (serde_json::Value::Null, _) | (_, serde_json::Value::Null) => false,
+        (serde_json::Value::Number(left), serde_json::Value::Number(right)) => {
+            left.as_f64() == right.as_f64()
+        }
+        _ => left == right,
+    }
+}
+
+/// Compare two values of the same type. Values of different types, and
+/// values which aren't numbers, strings or booleans, aren't comparable.
+fn partial_order(left: &serde_json::Value, right: &serde_json::Value) -> Option<Ordering> {
+    match (left, right) {
+        (serde_json::Value::Number(left), serde_json::Value::Number(right)) => {
+            left.as_f64()?.partial_cmp(&right.as_f64()?)
+        }
+        (serde_json::Value::String(left), serde_json::Value::String(right)) => {
+            Some(left.cmp(right))
+        }
+        (serde_json::Value::Bool(left), serde_json::Value::Bool(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// The order of values when sorting. Nulls sort first, as in SQLite, followed
+/// by booleans, numbers and strings.
+fn sort_order(left: &serde_json::Value, right: &serde_json::Value) -> Ordering {
+    fn rank(value: &serde_json::Value) -> u8 {
+        match value {
+            serde_json::Value::Null => 0,
+            serde_json::Value::Bool(_) => 1,
+            serde_json::Value::Number(_) => 2,
+            serde_json::Value::String(_) => 3,
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => 4,
+        }
+    }
+    rank(left)
+        .cmp(&rank(right))
+        .then_with(|| partial_order(left, right).unwrap_or(Ordering::Equal))
+}
+
+fn compare(
+    operator: &ndc::models::BinaryComparisonOperator,
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+) -> Result<bool, Error> {
+    let operator = match operator {
+        ndc::models::BinaryComparisonOperator::Equal => return Ok(equal(left, right)),
+        ndc::models::BinaryComparisonOperator::Other { name } => name.as_str(),
+    };
+    let ordering = partial_order(left, right);
+    Ok(match operator {
+        "_eq" => equal(left, right),
+        "_neq" => !left.is_null() && !right.is_null() && !equal(left, right),
+        "_gt" => ordering == Some(Ordering::Greater),
+        "_lt" => ordering == Some(Ordering::Less),
+        "_gte" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        "_lte" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        "_like" | "_glob" => match (left, right) {
+            (serde_json::Value::String(text), serde_json::Value::String(pattern)) => {
+                let text = text.chars().collect::<Vec<_>>();
+                let pattern = pattern.chars().collect::<Vec<_>>();
+                if operator == "_like" {
+                    wildcard_match(&pattern, &text, '%', '_', true)
+                } else {
+                    wildcard_match(&pattern, &text, '*', '?', false)
+                }
+            }
+            _ => false,
+        },
+        _ => {
+            return Err(Error::UnsupportedComparisonOperator {
+                operator: operator.to_string(),
+            })
+        }
+    })
+}
+
+/// Match text against a pattern where `any` matches any sequence of
+/// characters, and `one` matches a single character. Like SQLite, `LIKE` is
+/// case insensitive for ASCII characters. Character classes of `GLOB` aren't
+/// supported.
+///
+/// The pattern is matched greedily, only backtracking to the last `any` when
+/// the rest of the pattern doesn't match, which keeps matching linear in the
+/// length of the text for each `any` in the pattern.
+fn wildcard_match(
+    pattern: &[char],
+    text: &[char],
+    any: char,
+    one: char,
+    ignore_case: bool,
+) -> bool {
+    let matches = |expected: char, character: char| {
+        expected == one
+            || expected == character
+            || (ignore_case && expected.eq_ignore_ascii_case(&character))
+    };
+    let (mut pattern_index, mut text_index) = (0, 0);
+    // The position in the pattern after the last `any`, and the position in
+    // the text where what follows it is being matched
+    let mut backtrack = None;
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some(&expected) if expected == any => {
+                pattern_index += 1;
+                backtrack = Some((pattern_index, text_index));
+            }
+            Some(&expected) if matches(expected, text[text_index]) => {
+                pattern_index += 1;
+                text_index += 1;
+            }
+            _ => match backtrack {
+                // Let the last `any` match one more character
+                Some((any_pattern_index, any_text_index)) => {
+                    pattern_index = any_pattern_index;
+                    text_index = any_text_index + 1;
+                    backtrack = Some((any_pattern_index, text_index));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_index..].iter().all(|&expected| expected == any)
+}
+
+/// Apply an aggregate function to the values of a column. Null values are
+/// ignored, and functions other than `count` and `total` return null if there
+/// are no other values.
+fn aggregate_function(
+    function: &str,
+    values: Vec<serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    let values = values
+        .into_iter()
+        .filter(|value| !value.is_null())
+        .collect::<Vec<_>>();
+    let float_total = || float_sum(values.iter().filter_map(serde_json::Value::as_f64));
+    let sum = || -> serde_json::Value {
+        let integers = values
+            .iter()
+            .map(serde_json::Value::as_i64)
+            .collect::<Option<Vec<_>>>();
+        match integers.and_then(|integers| {
+            integers
+                .into_iter()
+                .try_fold(0i64, |sum, integer| sum.checked_add(integer))
+        }) {
+            Some(sum) => sum.into(),
+            None => number(float_total()),
+        }
+    };
+    Ok(match function {
+        "count" => values.len().into(),
+        "min" => values
+            .iter()
+            .min_by(|left, right| sort_order(left, right))
+            .cloned()
+            .unwrap_or(serde_json::Value::Null),
+        "max" => values
+            .iter()
+            .max_by(|left, right| sort_order(left, right))
+            .cloned()
+            .unwrap_or(serde_json::Value::Null),
+        "sum" if values.is_empty() => serde_json::Value::Null,
+        "sum" => sum(),
+        "total" => number(float_total()),
+        "avg" if values.is_empty() => serde_json::Value::Null,
+        "avg" => number(float_total() / values.len() as f64),
+        _ => {
+            return Err(Error::UnsupportedAggregateFunction {
+                function: function.to_string(),
+            })
+        }
+    })
+}
+
+/// Sum floating point numbers with Kahan-Babuska-Neumaier summation, as
+/// SQLite does, to limit rounding errors
+fn float_sum(numbers: impl Iterator<Item = f64>) -> f64 {
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+    for number in numbers {
+        let total = sum + number;
+        compensation += if f64::abs(sum) >= f64::abs(number) {
+            (sum - total) + number
+        } else {
+            (number - total) + sum
+        };
+        sum = total;
+    }
+    sum + compensation
+}
+
+fn number(value: f64) -> serde_json::Value {
+    serde_json::Number::from_f64(value).map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wildcard_match;
+
+    fn like(pattern: &str, text: &str) -> bool {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+        wildcard_match(&pattern, &text, '%', '_', true)
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like("", ""));
+        assert!(!like("", "a"));
+        assert!(like("%", ""));
+        assert!(like("a_c", "ABC"));
+        assert!(like("%b%", "abc"));
+        assert!(like("a%c%e", "abcdce"));
+        assert!(!like("a%c%e", "abcdcf"));
+        assert!(like("%%a", "ba"));
+        assert!(!like("_", ""));
+    }
+
+    #[test]
+    fn test_like_with_many_wildcards() {
+        let text = "a".repeat(10_000);
+        assert!(!like(&format!("{}b", "%a".repeat(50)), &text));
+        assert!(like(&format!("{}%", "%a".repeat(50)), &text));
+    }
+}
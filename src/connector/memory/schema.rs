@@ -0,0 +1,187 @@
+//! The procedures which the connector adds to its schema.
+
+use std::collections::BTreeMap;
+
+use open_dds::ndc_client as ndc;
+
+/// The argument of `update_<collection>_by_pk` which holds the new values of
+/// the columns
+pub(super) const SET_ARGUMENT: &str = "_set";
+
+/// The argument of `insert_<collection>` which holds the rows to insert
+pub(super) const OBJECTS_ARGUMENT: &str = "objects";
+
+/// The procedures of a collection
+pub(super) enum Procedure<'a> {
+    Insert {
+        collection: &'a ndc::models::CollectionInfo,
+    },
+    UpdateByPk {
+        collection: &'a ndc::models::CollectionInfo,
+        key: &'a [String],
+    },
+    DeleteByPk {
+        collection: &'a ndc::models::CollectionInfo,
+        key: &'a [String],
+    },
+}
+
+impl<'a> Procedure<'a> {
+    pub fn find(schema: &'a ndc::models::SchemaResponse, name: &str) -> Option<Self> {
+        schema.collections.iter().find_map(|collection| {
+            let key = primary_key(collection);
+            if name == insert_procedure_name(&collection.name) {
+                Some(Procedure::Insert { collection })
+            } else if name == update_by_pk_procedure_name(&collection.name) {
+                key.map(|key| Procedure::UpdateByPk { collection, key })
+            } else if name == delete_by_pk_procedure_name(&collection.name) {
+                key.map(|key| Procedure::DeleteByPk { collection, key })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Add the `insert_<collection>`, `update_<collection>_by_pk` and
+/// `delete_<collection>_by_pk` procedures to a schema. Procedures and object
+/// types which the schema already defines are left as they are.
+pub(super) fn with_procedures(
+    mut schema: ndc::models::SchemaResponse,
+) -> ndc::models::SchemaResponse {
+    let mut procedures = Vec::new();
+    let mut object_types = BTreeMap::new();
+    for collection in &schema.collections {
+        let collection_type = named(&collection.collection_type);
+        procedures.push(ndc::models::ProcedureInfo {
+            name: insert_procedure_name(&collection.name),
+            description: Some(format!("Insert rows into {}", collection.name)),
+            arguments: BTreeMap::from([(
+                OBJECTS_ARGUMENT.to_string(),
+                argument(array(collection_type.clone())),
+            )]),
+            result_type: array(collection_type.clone()),
+        });
+
+        let (Some(key), Some(object_type)) = (
+            primary_key(collection),
+            schema.object_types.get(&collection.collection_type),
+        ) else {
+            continue;
+        };
+        let key_arguments = key
+            .iter()
+            .filter_map(|column| {
+                let field = object_type.fields.get(column)?;
+                Some((column.clone(), argument(field.r#type.clone())))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let set_object_type_name = format!("{}_set_object", collection.name);
+        object_types.insert(
+            set_object_type_name.clone(),
+            ndc::models::ObjectType {
+                description: Some(format!("The columns of {} to update", collection.name)),
+                fields: object_type
+                    .fields
+                    .iter()
+                    .filter(|(column, _)| !key.contains(column))
+                    .map(|(column, field)| {
+                        let field = ndc::models::ObjectField {
+                            description: field.description.clone(),
+                            r#type: nullable(field.r#type.clone()),
+                        };
+                        (column.clone(), field)
+                    })
+                    .collect(),
+            },
+        );
+        let mut update_arguments = key_arguments.clone();
+        update_arguments.insert(
+            SET_ARGUMENT.to_string(),
+            argument(named(&set_object_type_name)),
+        );
+        procedures.push(ndc::models::ProcedureInfo {
+            name: update_by_pk_procedure_name(&collection.name),
+            description: Some(format!(
+                "Update a row of {} by its primary key",
+                collection.name
+            )),
+            arguments: update_arguments,
+            result_type: nullable(collection_type.clone()),
+        });
+        procedures.push(ndc::models::ProcedureInfo {
+            name: delete_by_pk_procedure_name(&collection.name),
+            description: Some(format!(
+                "Delete a row of {} by its primary key",
+                collection.name
+            )),
+            arguments: key_arguments,
+            result_type: nullable(collection_type),
+        });
+    }
+
+    for (name, object_type) in object_types {
+        schema.object_types.entry(name).or_insert(object_type);
+    }
+    for procedure in procedures {
+        if !schema
+            .procedures
+            .iter()
+            .any(|existing| existing.name == procedure.name)
+        {
+            schema.procedures.push(procedure);
+        }
+    }
+    schema
+}
+
+/// The columns of the first uniqueness constraint of a collection, which
+/// identify a row in the `_by_pk` procedures
+fn primary_key(collection: &ndc::models::CollectionInfo) -> Option<&[String]> {
+    collection
+        .uniqueness_constraints
+        .values()
+        .next()
+        .map(|constraint| constraint.unique_columns.as_slice())
+}
+
+fn insert_procedure_name(collection: &str) -> String {
+    format!("insert_{collection}")
+}
+
+fn update_by_pk_procedure_name(collection: &str) -> String {
+    format!("update_{collection}_by_pk")
+}
+
+fn delete_by_pk_procedure_name(collection: &str) -> String {
+    format!("delete_{collection}_by_pk")
+}
+
+fn named(name: &str) -> ndc::models::Type {
+    ndc::models::Type::Named {
+        name: name.to_string(),
+    }
+}
+
+fn nullable(underlying_type: ndc::models::Type) -> ndc::models::Type {
+    match underlying_type {
+        ndc::models::Type::Nullable { .. } => underlying_type,
+        _ => ndc::models::Type::Nullable {
+            underlying_type: Box::new(underlying_type),
+        },
+    }
+}
+
+fn array(element_type: ndc::models::Type) -> ndc::models::Type {
+    ndc::models::Type::Array {
+        element_type: Box::new(element_type),
+    }
+}
+
+fn argument(argument_type: ndc::models::Type) -> ndc::models::ArgumentInfo {
+    ndc::models::ArgumentInfo {
+        description: None,
+        argument_type,
+    }
+}
@@ -0,0 +1,37 @@
+//! An NDC connector for SQLite databases, such as the Turso database used by
+//! the browser build.
+//!
+//! NDC requests are compiled to parameterised SQL which builds the NDC
+//! response as JSON inside the database, so that each request is a single
+//! round-trip to the database.
+
+use serde::Serialize;
+
+pub mod configuration;
+pub mod mutation;
+pub mod query;
+pub mod schema;
+
+pub use super::error::Error;
+pub use configuration::Configuration;
+
+/// A parameterised SQL statement. Parameters are referred to by their
+/// (1-based) index in `params`, as `?1`, `?2`, ...
+///
+/// Parameters are JSON values: booleans should be bound as integers, and
+/// arrays and objects as JSON text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SqlStatement {
+    pub sql: String,
+    pub params: Vec<serde_json::Value>,
+}
+
+/// Quote an identifier, such as the name of a table or a column
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Quote a string literal
+fn quote_string(string: &str) -> String {
+    format!("'{}'", string.replace('\'', "''"))
+}
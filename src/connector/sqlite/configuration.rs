@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use open_dds::ndc_client as ndc;
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+
+/// The tables of the database which are exposed as collections, as found
+/// under `config` in `www/configuration.json`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Configuration {
+    pub collection_names: Vec<String>,
+    pub object_types: BTreeMap<String, ndc::models::ObjectType>,
+    pub object_fields: BTreeMap<String, ObjectFieldDetails>,
+}
+
+/// The columns of a table
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ObjectFieldDetails {
+    pub field_names: Vec<String>,
+    /// The name of the scalar type of each column
+    pub field_types: BTreeMap<String, String>,
+    pub primary_keys: Vec<String>,
+    pub unique_keys: Vec<String>,
+    pub nullable_keys: Vec<String>,
+    pub foreign_keys: BTreeMap<String, ForeignKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ForeignKey {
+    pub table: String,
+    pub column: String,
+}
+
+/// The connector configuration file, of which only the `config` is of
+/// interest to the engine. The credentials are used by the database client.
+#[derive(Deserialize)]
+struct ConfigurationFile {
+    config: Configuration,
+}
+
+impl Configuration {
+    /// Parse the contents of a connector configuration file, like
+    /// `www/configuration.json`
+    pub fn from_json(json: &str) -> Result<Configuration, serde_json::Error> {
+        serde_json::from_str::<ConfigurationFile>(json).map(|file| file.config)
+    }
+
+    /// The columns of the given collection
+    pub fn object_fields(&self, collection: &str) -> Result<&ObjectFieldDetails, Error> {
+        self.collection_names
+            .iter()
+            .any(|name| name == collection)
+            .then(|| self.object_fields.get(collection))
+            .flatten()
+            .ok_or_else(|| Error::CollectionNotFound {
+                collection: collection.to_string(),
+            })
+    }
+
+    /// Check that the given column exists in the collection
+    pub fn check_column(&self, collection: &str, column: &str) -> Result<(), Error> {
+        if self
+            .object_fields(collection)?
+            .field_names
+            .iter()
+            .any(|name| name == column)
+        {
+            Ok(())
+        } else {
+            Err(Error::ColumnNotFound {
+                collection: collection.to_string(),
+                column: column.to_string(),
+            })
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Compiles NDC mutation requests to SQL.
+//!
+//! Each procedure is a sequence of `INSERT`, `UPDATE` or `DELETE` statements
+//! whose `RETURNING` clause builds the requested fields of each affected row
+//! as JSON, in a `data` column. The statements of a request should be executed
+//! in a single transaction.
+
+use std::collections::BTreeMap;
+
+use indexmap::IndexMap;
+use open_dds::ndc_client as ndc;
+
+use super::query::{QueryBuilder, Scope};
+use super::schema::{
+    delete_by_pk_procedure_name, insert_procedure_name, update_by_pk_procedure_name, SET_ARGUMENT,
+};
+use super::{quote_identifier, Configuration, Error, SqlStatement};
+
+/// The statements of a procedure, and how to build its results from the rows
+/// they return
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcedurePlan {
+    /// The statements to execute in order. Each returns a row, with a single
+    /// `data` column, for every row it affects.
+    pub statements: Vec<SqlStatement>,
+    /// Whether the procedure returns a single (nullable) row rather than an
+    /// array of rows
+    single_row: bool,
+    /// Whether fields were requested, in which case the rows are returned
+    returning: bool,
+}
+
+impl ProcedurePlan {
+    /// Build the results of the procedure from the `data` column of the rows
+    /// returned by its statements. As procedures return a single value, the
+    /// rows are returned in the `__value` field of a single row.
+    pub fn operation_results(
+        &self,
+        rows: &[String],
+    ) -> Result<ndc::models::MutationOperationResults, Error> {
+        let returning = if self.returning {
+            let rows = rows
+                .iter()
+                .map(|row| serde_json::from_str(row))
+                .collect::<Result<Vec<serde_json::Value>, _>>()?;
+            let value = if self.single_row {
+                rows.into_iter().next().unwrap_or(serde_json::Value::Null)
+            } else {
+                serde_json::Value::Array(rows)
+            };
+            Some(vec![IndexMap::from([(
+                "__value".to_string(),
+                ndc::models::RowFieldValue(value),
+            )])])
+        } else {
+            None
+        };
+        Ok(ndc::models::MutationOperationResults {
+            affected_rows: rows.len() as u32,
+            returning,
+        })
+    }
+}
+
+/// Compile a mutation request to SQL, returning a plan for each operation
+pub fn plan_mutation(
+    configuration: &Configuration,
+    request: &ndc::models::MutationRequest,
+) -> Result<Vec<ProcedurePlan>, Error> {
+    request
+        .operations
+        .iter()
+        .map(|operation| match operation {
+            ndc::models::MutationOperation::Procedure {
+                name,
+                arguments,
+                fields,
+            } => {
+                let planner = ProcedurePlanner {
+                    configuration,
+                    collection_relationships: &request.collection_relationships,
+                    procedure: name,
+                    arguments,
+                    fields: fields.as_ref(),
+                };
+                planner.plan()
+            }
+        })
+        .collect()
+}
+
+/// The procedures advertised by the schema of the connector
+enum Procedure<'a> {
+    Insert { collection: &'a str },
+    UpdateByPk { collection: &'a str },
+    DeleteByPk { collection: &'a str },
+}
+
+impl<'a> Procedure<'a> {
+    fn find(configuration: &'a Configuration, name: &str) -> Option<Self> {
+        configuration
+            .collection_names
+            .iter()
+            .find_map(|collection| {
+                // like in the schema, the `_by_pk` procedures only exist for
+                // the collections which have a primary key
+                let has_primary_key = configuration
+                    .object_fields(collection)
+                    .is_ok_and(|object_fields| !object_fields.primary_keys.is_empty());
+                if name == insert_procedure_name(collection) {
+                    Some(Procedure::Insert { collection })
+                } else if has_primary_key && name == update_by_pk_procedure_name(collection) {
+                    Some(Procedure::UpdateByPk { collection })
+                } else if has_primary_key && name == delete_by_pk_procedure_name(collection) {
+                    Some(Procedure::DeleteByPk { collection })
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+struct ProcedurePlanner<'a> {
+    configuration: &'a Configuration,
+    collection_relationships: &'a BTreeMap<String, ndc::models::Relationship>,
+    procedure: &'a str,
+    arguments: &'a BTreeMap<String, serde_json::Value>,
+    fields: Option<&'a IndexMap<String, ndc::models::Field>>,
+}
+
+impl<'a> ProcedurePlanner<'a> {
+    fn plan(&self) -> Result<ProcedurePlan, Error> {
+        let procedure = Procedure::find(self.configuration, self.procedure).ok_or_else(|| {
+            Error::ProcedureNotFound {
+                procedure: self.procedure.to_string(),
+            }
+        })?;
+        let (statements, single_row) = match procedure {
+            Procedure::Insert { collection } => (self.insert(collection)?, false),
+            Procedure::UpdateByPk { collection } => (vec![self.update_by_pk(collection)?], true),
+            Procedure::DeleteByPk { collection } => (vec![self.delete_by_pk(collection)?], true),
+        };
+        Ok(ProcedurePlan {
+            statements,
+            single_row,
+            returning: self.fields.is_some(),
+        })
+    }
+
+    fn insert(&self, collection: &'a str) -> Result<Vec<SqlStatement>, Error> {
+        let objects = self
+            .argument("objects")?
+            .as_array()
+            .ok_or_else(|| self.invalid_argument("objects", "expected an array of objects"))?;
+        objects
+            .iter()
+            .map(|object| {
+                let object = object.as_object().ok_or_else(|| {
+                    self.invalid_argument("objects", "expected an array of objects")
+                })?;
+                let mut builder = self.builder();
+                let mut columns = Vec::new();
+                let mut values = Vec::new();
+                for (column, value) in object {
+                    self.configuration.check_column(collection, column)?;
+                    columns.push(quote_identifier(column));
+                    values.push(builder.param(value.clone()));
+                }
+                let values = if columns.is_empty() {
+                    "DEFAULT VALUES".to_string()
+                } else {
+                    format!("({}) VALUES ({})", columns.join(", "), values.join(", "))
+                };
+                let returning = self.returning(&mut builder, collection)?;
+                Ok(SqlStatement {
+                    sql: format!(
+                        "INSERT INTO {} {values}{returning}",
+                        quote_identifier(collection)
+                    ),
+                    params: builder.params,
+                })
+            })
+            .collect()
+    }
+
+    fn update_by_pk(&self, collection: &'a str) -> Result<SqlStatement, Error> {
+        let new_values = match self.arguments.get(SET_ARGUMENT) {
+            None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+            Some(serde_json::Value::Object(new_values)) => new_values.clone(),
+            Some(_) => return Err(self.invalid_argument(SET_ARGUMENT, "expected an object")),
+        };
+        let mut builder = self.builder();
+        let mut assignments = Vec::new();
+        for (column, value) in &new_values {
+            self.configuration.check_column(collection, column)?;
+            let param = builder.param(value.clone());
+            assignments.push(format!("{} = {param}", quote_identifier(column)));
+        }
+        let primary_key = self.primary_key_condition(&mut builder, collection)?;
+        if assignments.is_empty() {
+            // Nothing to update, but the row is still returned if it exists
+            let primary_keys = &self.configuration.object_fields(collection)?.primary_keys;
+            assignments.extend(primary_keys.iter().map(|column| {
+                let column = quote_identifier(column);
+                format!("{column} = {column}")
+            }));
+        }
+        let returning = self.returning(&mut builder, collection)?;
+        Ok(SqlStatement {
+            sql: format!(
+                "UPDATE {} SET {} WHERE {primary_key}{returning}",
+                quote_identifier(collection),
+                assignments.join(", ")
+            ),
+            params: builder.params,
+        })
+    }
+
+    fn delete_by_pk(&self, collection: &'a str) -> Result<SqlStatement, Error> {
+        let mut builder = self.builder();
+        let primary_key = self.primary_key_condition(&mut builder, collection)?;
+        let returning = self.returning(&mut builder, collection)?;
+        Ok(SqlStatement {
+            sql: format!(
+                "DELETE FROM {} WHERE {primary_key}{returning}",
+                quote_identifier(collection)
+            ),
+            params: builder.params,
+        })
+    }
+
+    fn builder(&self) -> QueryBuilder<'a> {
+        QueryBuilder::new(self.configuration, self.collection_relationships, None)
+    }
+
+    /// The condition which matches the row identified by the primary key
+    /// arguments
+    fn primary_key_condition(
+        &self,
+        builder: &mut QueryBuilder<'a>,
+        collection: &'a str,
+    ) -> Result<String, Error> {
+        let scope = self.scope(collection);
+        let primary_keys = &self.configuration.object_fields(collection)?.primary_keys;
+        let mut conditions = Vec::new();
+        for column in primary_keys {
+            let value = self.argument(column)?.clone();
+            let column = builder.column(&scope, column)?;
+            conditions.push(format!("{column} = {}", builder.param(value)));
+        }
+        Ok(conditions.join(" AND "))
+    }
+
+    /// The `RETURNING` clause which builds the requested fields of the affected
+    /// rows
+    fn returning(
+        &self,
+        builder: &mut QueryBuilder<'a>,
+        collection: &'a str,
+    ) -> Result<String, Error> {
+        // Rows are returned even if no fields are requested, to count them
+        let row = match self.fields {
+            Some(fields) => builder.fields(fields, &self.scope(collection))?,
+            None => "json_object()".to_string(),
+        };
+        Ok(format!(" RETURNING {row} AS data"))
+    }
+
+    /// `RETURNING` clauses can't refer to an alias of the table, so the
+    /// mutated table is referred to by its name
+    fn scope(&self, collection: &'a str) -> Scope<'a> {
+        Scope {
+            collection,
+            table: quote_identifier(collection),
+        }
+    }
+
+    fn argument(&self, argument: &str) -> Result<&'a serde_json::Value, Error> {
+        self.arguments
+            .get(argument)
+            .ok_or_else(|| Error::ArgumentNotFound {
+                procedure: self.procedure.to_string(),
+                argument: argument.to_string(),
+            })
+    }
+
+    fn invalid_argument(&self, argument: &str, message: &str) -> Error {
+        Error::InvalidArgument {
+            procedure: self.procedure.to_string(),
+            argument: argument.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,574 @@
+//! Compiles NDC query requests to SQL.
+//!
+//! Each row set is built with `json_object`, so that a query returns a single
+//! row with a single `data` column containing the JSON `RowSet`. Relationships
+//! are fetched with correlated subqueries, and comparisons across
+//! relationships become `EXISTS` subqueries.
+
+use std::collections::BTreeMap;
+
+use indexmap::IndexMap;
+use open_dds::ndc_client as ndc;
+
+use super::{quote_identifier, quote_string, Configuration, Error, SqlStatement};
+use crate::connector::error::check_no_arguments;
+
+/// Compile a query request to SQL. A statement is returned for each set of
+/// variables in the request, or a single statement if there are none. Each
+/// statement returns the corresponding `RowSet`, which can be parsed with
+/// `parse_row_set`.
+pub fn plan_query(
+    configuration: &Configuration,
+    request: &ndc::models::QueryRequest,
+) -> Result<Vec<SqlStatement>, Error> {
+    match &request.variables {
+        None => Ok(vec![plan_query_with_variables(
+            configuration,
+            request,
+            None,
+        )?]),
+        Some(variable_sets) => variable_sets
+            .iter()
+            .map(|variables| plan_query_with_variables(configuration, request, Some(variables)))
+            .collect(),
+    }
+}
+
+/// Parse the `data` column returned by a statement built by `plan_query`
+pub fn parse_row_set(data: &str) -> Result<ndc::models::RowSet, Error> {
+    Ok(serde_json::from_str(data)?)
+}
+
+fn plan_query_with_variables(
+    configuration: &Configuration,
+    request: &ndc::models::QueryRequest,
+    variables: Option<&BTreeMap<String, serde_json::Value>>,
+) -> Result<SqlStatement, Error> {
+    check_no_arguments(&request.collection, &request.arguments)?;
+    let mut builder =
+        QueryBuilder::new(configuration, &request.collection_relationships, variables);
+    let row_set = builder.row_set(&request.collection, &request.query, None)?;
+    Ok(SqlStatement {
+        sql: format!("SELECT {row_set} AS data"),
+        params: builder.params,
+    })
+}
+
+/// A table in the `FROM` clause of the SQL, along with the collection it
+/// queries
+#[derive(Clone)]
+pub(super) struct Scope<'a> {
+    pub collection: &'a str,
+    /// The quoted alias of the table
+    pub table: String,
+}
+
+/// The tables and conditions which follow a relationship path, rendered as
+/// `FROM ... WHERE ...`
+struct PathJoin<'a> {
+    /// The tables along the path, to be used in a `FROM` clause
+    tables: Vec<String>,
+    conditions: Vec<String>,
+    /// The last table in the path
+    target: Scope<'a>,
+}
+
+impl PathJoin<'_> {
+    fn to_sql(&self) -> String {
+        format!(
+            "FROM {}{}",
+            self.tables.join(", "),
+            where_clause(&self.conditions)
+        )
+    }
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    }
+}
+
+pub(super) struct QueryBuilder<'a> {
+    configuration: &'a Configuration,
+    collection_relationships: &'a BTreeMap<String, ndc::models::Relationship>,
+    variables: Option<&'a BTreeMap<String, serde_json::Value>>,
+    pub params: Vec<serde_json::Value>,
+    next_table: usize,
+}
+
+impl<'a> QueryBuilder<'a> {
+    pub fn new(
+        configuration: &'a Configuration,
+        collection_relationships: &'a BTreeMap<String, ndc::models::Relationship>,
+        variables: Option<&'a BTreeMap<String, serde_json::Value>>,
+    ) -> Self {
+        QueryBuilder {
+            configuration,
+            collection_relationships,
+            variables,
+            params: Vec::new(),
+            next_table: 0,
+        }
+    }
+
+    /// A new table alias, unique within the statement
+    pub fn table(&mut self, collection: &'a str) -> Result<Scope<'a>, Error> {
+        self.configuration.object_fields(collection)?;
+        let table = quote_identifier(&format!("t{}", self.next_table));
+        self.next_table += 1;
+        Ok(Scope { collection, table })
+    }
+
+    /// Add a parameter, and return its placeholder
+    pub fn param(&mut self, value: serde_json::Value) -> String {
+        self.params.push(value);
+        format!("?{}", self.params.len())
+    }
+
+    /// A column of the given table
+    pub fn column(&self, scope: &Scope, column: &str) -> Result<String, Error> {
+        self.configuration.check_column(scope.collection, column)?;
+        Ok(format!("{}.{}", scope.table, quote_identifier(column)))
+    }
+
+    fn variable(&self, name: &str) -> Result<serde_json::Value, Error> {
+        self.variables
+            .and_then(|variables| variables.get(name))
+            .cloned()
+            .ok_or_else(|| Error::VariableNotFound {
+                variable: name.to_string(),
+            })
+    }
+
+    fn relationship(
+        &self,
+        relationship: &str,
+        arguments: &BTreeMap<String, ndc::models::RelationshipArgument>,
+    ) -> Result<&'a ndc::models::Relationship, Error> {
+        let relationship_info =
+            self.collection_relationships
+                .get(relationship)
+                .ok_or_else(|| Error::RelationshipNotFound {
+                    relationship: relationship.to_string(),
+                })?;
+        check_no_arguments(&relationship_info.target_collection, arguments)?;
+        check_no_arguments(
+            &relationship_info.target_collection,
+            &relationship_info.arguments,
+        )?;
+        Ok(relationship_info)
+    }
+
+    /// The conditions which join the target table of a relationship to its
+    /// source table
+    fn join_conditions(
+        &self,
+        source: &Scope,
+        target: &Scope,
+        relationship: &ndc::models::Relationship,
+    ) -> Result<Vec<String>, Error> {
+        relationship
+            .column_mapping
+            .iter()
+            .map(|(source_column, target_column)| {
+                Ok(format!(
+                    "{} = {}",
+                    self.column(target, target_column)?,
+                    self.column(source, source_column)?
+                ))
+            })
+            .collect()
+    }
+
+    /// The JSON `RowSet` of a query against the given collection. If the
+    /// collection is the target of a relationship, only the rows related to
+    /// the source row are selected.
+    pub fn row_set(
+        &mut self,
+        collection: &'a str,
+        query: &'a ndc::models::Query,
+        relationship: Option<(&Scope<'a>, &'a ndc::models::Relationship)>,
+    ) -> Result<String, Error> {
+        let scope = self.table(collection)?;
+
+        let mut conditions = Vec::new();
+        if let Some((source, relationship)) = relationship {
+            conditions.extend(self.join_conditions(source, &scope, relationship)?);
+        }
+        if let Some(predicate) = &query.predicate {
+            conditions.push(self.expression(predicate, &scope, &scope)?);
+        }
+        let order_by = match &query.order_by {
+            Some(order_by) if !order_by.elements.is_empty() => {
+                format!(" ORDER BY {}", self.order_by(order_by, &scope)?)
+            }
+            _ => String::new(),
+        };
+        let limit = match (query.limit, query.offset) {
+            (None, None) => String::new(),
+            (Some(limit), None) => format!(" LIMIT {limit}"),
+            (limit, Some(offset)) => {
+                format!(" LIMIT {} OFFSET {offset}", limit.map_or(-1, i64::from))
+            }
+        };
+        let from = format!(
+            "FROM {} AS {}{}{order_by}{limit}",
+            quote_identifier(collection),
+            scope.table,
+            where_clause(&conditions),
+        );
+
+        let mut row_set = Vec::new();
+        if let Some(fields) = &query.fields {
+            let row = self.fields(fields, &scope)?;
+            row_set.push(format!(
+                "'rows', json((SELECT json_group_array(json(r)) FROM (SELECT {row} AS r {from})))"
+            ));
+        }
+        if let Some(aggregates) = &query.aggregates {
+            // The aggregates are computed over the same rows as those returned
+            let aggregate_scope = self.table(collection)?;
+            let aggregates = self.aggregates(aggregates, &aggregate_scope)?;
+            row_set.push(format!(
+                "'aggregates', json((SELECT {aggregates} FROM (SELECT {}.* {from}) AS {}))",
+                scope.table, aggregate_scope.table
+            ));
+        }
+        Ok(format!("json_object({})", row_set.join(", ")))
+    }
+
+    /// The JSON object of a row
+    pub fn fields(
+        &mut self,
+        fields: &'a IndexMap<String, ndc::models::Field>,
+        scope: &Scope<'a>,
+    ) -> Result<String, Error> {
+        let mut row = Vec::new();
+        for (field_name, field) in fields {
+            let value = match field {
+                ndc::models::Field::Column { column } => self.column(scope, column)?,
+                ndc::models::Field::Relationship {
+                    query,
+                    relationship,
+                    arguments,
+                } => {
+                    let relationship = self.relationship(relationship, arguments)?;
+                    self.row_set(
+                        &relationship.target_collection,
+                        query,
+                        Some((scope, relationship)),
+                    )?
+                }
+            };
+            row.push(format!("{}, {value}", quote_string(field_name)));
+        }
+        Ok(format!("json_object({})", row.join(", ")))
+    }
+
+    fn aggregates(
+        &self,
+        aggregates: &IndexMap<String, ndc::models::Aggregate>,
+        scope: &Scope,
+    ) -> Result<String, Error> {
+        let mut fields = Vec::new();
+        for (name, aggregate) in aggregates {
+            let value = match aggregate {
+                ndc::models::Aggregate::StarCount {} => "COUNT(*)".to_string(),
+                ndc::models::Aggregate::ColumnCount { column, distinct } => format!(
+                    "COUNT({}{})",
+                    if *distinct { "DISTINCT " } else { "" },
+                    self.column(scope, column)?
+                ),
+                ndc::models::Aggregate::SingleColumn { column, function } => {
+                    aggregate_function(function, &self.column(scope, column)?)?
+                }
+            };
+            fields.push(format!("{}, {value}", quote_string(name)));
+        }
+        Ok(format!("json_object({})", fields.join(", ")))
+    }
+
+    fn order_by(
+        &mut self,
+        order_by: &'a ndc::models::OrderBy,
+        scope: &Scope<'a>,
+    ) -> Result<String, Error> {
+        let mut elements = Vec::new();
+        for element in &order_by.elements {
+            let target = match &element.target {
+                ndc::models::OrderByTarget::Column { name, path } => {
+                    if path.is_empty() {
+                        self.column(scope, name)?
+                    } else {
+                        let join = self.path(path, scope, scope)?;
+                        let column = self.column(&join.target, name)?;
+                        format!("(SELECT {column} {} LIMIT 1)", join.to_sql())
+                    }
+                }
+                ndc::models::OrderByTarget::SingleColumnAggregate {
+                    column,
+                    function,
+                    path,
+                } => {
+                    let join = self.non_empty_path(path, scope)?;
+                    let aggregate =
+                        aggregate_function(function, &self.column(&join.target, column)?)?;
+                    format!("(SELECT {aggregate} {})", join.to_sql())
+                }
+                ndc::models::OrderByTarget::StarCountAggregate { path } => {
+                    let join = self.non_empty_path(path, scope)?;
+                    format!("(SELECT COUNT(*) {})", join.to_sql())
+                }
+            };
+            let direction = match element.order_direction {
+                ndc::models::OrderDirection::Asc => "ASC",
+                ndc::models::OrderDirection::Desc => "DESC",
+            };
+            elements.push(format!("{target} {direction}"));
+        }
+        Ok(elements.join(", "))
+    }
+
+    fn non_empty_path(
+        &mut self,
+        path: &'a [ndc::models::PathElement],
+        scope: &Scope<'a>,
+    ) -> Result<PathJoin<'a>, Error> {
+        if path.is_empty() {
+            Err(Error::EmptyAggregatePath)
+        } else {
+            self.path(path, scope, scope)
+        }
+    }
+
+    /// Follow the relationships of a path, starting from the given table
+    fn path(
+        &mut self,
+        path: &'a [ndc::models::PathElement],
+        scope: &Scope<'a>,
+        root: &Scope<'a>,
+    ) -> Result<PathJoin<'a>, Error> {
+        let mut tables = Vec::new();
+        let mut conditions = Vec::new();
+        let mut source = scope.clone();
+        for element in path {
+            let relationship = self.relationship(&element.relationship, &element.arguments)?;
+            let target = self.table(&relationship.target_collection)?;
+            tables.push(format!(
+                "{} AS {}",
+                quote_identifier(target.collection),
+                target.table
+            ));
+            conditions.extend(self.join_conditions(&source, &target, relationship)?);
+            conditions.push(self.expression(&element.predicate, &target, root)?);
+            source = target;
+        }
+        Ok(PathJoin {
+            tables,
+            conditions,
+            target: source,
+        })
+    }
+
+    /// A boolean expression. Columns of the root collection are those of the
+    /// `root` table.
+    pub fn expression(
+        &mut self,
+        expression: &'a ndc::models::Expression,
+        scope: &Scope<'a>,
+        root: &Scope<'a>,
+    ) -> Result<String, Error> {
+        match expression {
+            ndc::models::Expression::And { expressions } => {
+                self.expressions(expressions, " AND ", "1", scope, root)
+            }
+            ndc::models::Expression::Or { expressions } => {
+                self.expressions(expressions, " OR ", "0", scope, root)
+            }
+            ndc::models::Expression::Not { expression } => Ok(format!(
+                "NOT ({})",
+                self.expression(expression, scope, root)?
+            )),
+            ndc::models::Expression::UnaryComparisonOperator { column, operator } => match operator
+            {
+                ndc::models::UnaryComparisonOperator::IsNull => {
+                    self.comparison(column, scope, root, |column| format!("{column} IS NULL"))
+                }
+            },
+            ndc::models::Expression::BinaryComparisonOperator {
+                column,
+                operator,
+                value,
+            } => {
+                let operator = comparison_operator(operator)?;
+                let value = self.comparison_value(value, scope, root)?;
+                self.comparison(column, scope, root, |column| {
+                    format!("{column} {operator} {value}")
+                })
+            }
+            ndc::models::Expression::BinaryArrayComparisonOperator {
+                column,
+                operator,
+                values,
+            } => match operator {
+                ndc::models::BinaryArrayComparisonOperator::In => {
+                    if values.is_empty() {
+                        return Ok("0".to_string());
+                    }
+                    let values = values
+                        .iter()
+                        .map(|value| self.comparison_value(value, scope, root))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join(", ");
+                    self.comparison(column, scope, root, |column| {
+                        format!("{column} IN ({values})")
+                    })
+                }
+            },
+            ndc::models::Expression::Exists {
+                in_collection,
+                predicate,
+            } => {
+                let (target, mut conditions) = match in_collection {
+                    ndc::models::ExistsInCollection::Related {
+                        relationship,
+                        arguments,
+                    } => {
+                        let relationship = self.relationship(relationship, arguments)?;
+                        let target = self.table(&relationship.target_collection)?;
+                        let conditions = self.join_conditions(scope, &target, relationship)?;
+                        (target, conditions)
+                    }
+                    ndc::models::ExistsInCollection::Unrelated {
+                        collection,
+                        arguments,
+                    } => {
+                        check_no_arguments(collection, arguments)?;
+                        (self.table(collection)?, Vec::new())
+                    }
+                };
+                conditions.push(self.expression(predicate, &target, root)?);
+                Ok(format!(
+                    "EXISTS (SELECT 1 FROM {} AS {}{})",
+                    quote_identifier(target.collection),
+                    target.table,
+                    where_clause(&conditions)
+                ))
+            }
+        }
+    }
+
+    fn expressions(
+        &mut self,
+        expressions: &'a [ndc::models::Expression],
+        separator: &str,
+        empty: &str,
+        scope: &Scope<'a>,
+        root: &Scope<'a>,
+    ) -> Result<String, Error> {
+        if expressions.is_empty() {
+            return Ok(empty.to_string());
+        }
+        let expressions = expressions
+            .iter()
+            .map(|expression| self.expression(expression, scope, root))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("({})", expressions.join(separator)))
+    }
+
+    /// A comparison against the target column. If the column is reached
+    /// through relationships, the comparison holds if it holds for any of the
+    /// related rows.
+    fn comparison(
+        &mut self,
+        target: &'a ndc::models::ComparisonTarget,
+        scope: &Scope<'a>,
+        root: &Scope<'a>,
+        compare: impl FnOnce(&str) -> String,
+    ) -> Result<String, Error> {
+        match target {
+            ndc::models::ComparisonTarget::Column { name, path } => {
+                if path.is_empty() {
+                    Ok(compare(&self.column(scope, name)?))
+                } else {
+                    let mut join = self.path(path, scope, root)?;
+                    join.conditions
+                        .push(compare(&self.column(&join.target, name)?));
+                    Ok(format!("EXISTS (SELECT 1 {})", join.to_sql()))
+                }
+            }
+            ndc::models::ComparisonTarget::RootCollectionColumn { name } => {
+                Ok(compare(&self.column(root, name)?))
+            }
+        }
+    }
+
+    fn comparison_value(
+        &mut self,
+        value: &'a ndc::models::ComparisonValue,
+        scope: &Scope<'a>,
+        root: &Scope<'a>,
+    ) -> Result<String, Error> {
+        match value {
+            ndc::models::ComparisonValue::Scalar { value } => Ok(self.param(value.clone())),
+            ndc::models::ComparisonValue::Variable { name } => {
+                let value = self.variable(name)?;
+                Ok(self.param(value))
+            }
+            ndc::models::ComparisonValue::Column { column } => match column {
+                ndc::models::ComparisonTarget::Column { name, path } => {
+                    if path.is_empty() {
+                        self.column(scope, name)
+                    } else {
+                        let join = self.path(path, scope, root)?;
+                        let column = self.column(&join.target, name)?;
+                        Ok(format!("(SELECT {column} {} LIMIT 1)", join.to_sql()))
+                    }
+                }
+                ndc::models::ComparisonTarget::RootCollectionColumn { name } => {
+                    self.column(root, name)
+                }
+            },
+        }
+    }
+}
+
+fn comparison_operator(
+    operator: &ndc::models::BinaryComparisonOperator,
+) -> Result<&'static str, Error> {
+    match operator {
+        ndc::models::BinaryComparisonOperator::Equal => Ok("="),
+        ndc::models::BinaryComparisonOperator::Other { name } => match name.as_str() {
+            "_eq" => Ok("="),
+            "_neq" => Ok("!="),
+            "_gt" => Ok(">"),
+            "_lt" => Ok("<"),
+            "_gte" => Ok(">="),
+            "_lte" => Ok("<="),
+            "_like" => Ok("LIKE"),
+            "_glob" => Ok("GLOB"),
+            _ => Err(Error::UnsupportedComparisonOperator {
+                operator: name.clone(),
+            }),
+        },
+    }
+}
+
+fn aggregate_function(function: &str, column: &str) -> Result<String, Error> {
+    let function_sql = match function {
+        "avg" => "AVG",
+        "sum" => "SUM",
+        "min" => "MIN",
+        "max" => "MAX",
+        "total" => "TOTAL",
+        _ => {
+            return Err(Error::UnsupportedAggregateFunction {
+                function: function.to_string(),
+            })
+        }
+    };
+    Ok(format!("{function_sql}({column})"))
+}
@@ -0,0 +1,323 @@
+//! The NDC schema of the connector, derived from the configuration.
+
+use std::collections::BTreeMap;
+
+use open_dds::ndc_client as ndc;
+
+use super::configuration::ObjectFieldDetails;
+use super::Configuration;
+
+/// The NDC schema of the connector. Along with the collections, it advertises
+/// the `insert_<collection>`, `update_<collection>_by_pk` and
+/// `delete_<collection>_by_pk` procedures, so that commands can be defined in
+/// the metadata against them.
+pub fn schema_response(configuration: &Configuration) -> ndc::models::SchemaResponse {
+    let mut object_types = configuration.object_types.clone();
+    object_types.extend(mutation_object_types(configuration));
+    ndc::models::SchemaResponse {
+        scalar_types: scalar_types(configuration),
+        object_types,
+        collections: collections(configuration),
+        functions: Vec::new(),
+        procedures: procedures(configuration),
+    }
+}
+
+/// The procedures which insert, update and delete the rows of each collection
+pub fn procedures(configuration: &Configuration) -> Vec<ndc::models::ProcedureInfo> {
+    let mut procedures = Vec::new();
+    for (collection, object_fields) in collections_with_fields(configuration) {
+        procedures.push(ndc::models::ProcedureInfo {
+            name: insert_procedure_name(collection),
+            description: Some(format!("Insert rows into {collection}")),
+            arguments: BTreeMap::from([(
+                "objects".to_string(),
+                argument(array(named(&insert_object_type_name(collection)))),
+            )]),
+            result_type: array(named(collection)),
+        });
+        if object_fields.primary_keys.is_empty() {
+            continue;
+        }
+        let primary_key_arguments = primary_key_arguments(configuration, collection, object_fields);
+
+        let mut update_arguments = primary_key_arguments.clone();
+        update_arguments.insert(
+            SET_ARGUMENT.to_string(),
+            argument(named(&set_object_type_name(collection))),
+        );
+        procedures.push(ndc::models::ProcedureInfo {
+            name: update_by_pk_procedure_name(collection),
+            description: Some(format!("Update a row of {collection} by its primary key")),
+            arguments: update_arguments,
+            result_type: nullable(named(collection)),
+        });
+        procedures.push(ndc::models::ProcedureInfo {
+            name: delete_by_pk_procedure_name(collection),
+            description: Some(format!("Delete a row of {collection} by its primary key")),
+            arguments: primary_key_arguments,
+            result_type: nullable(named(collection)),
+        });
+    }
+    procedures
+}
+
+/// The argument of `update_<collection>_by_pk` which holds the new values of
+/// the columns
+pub const SET_ARGUMENT: &str = "_set";
+
+pub fn insert_procedure_name(collection: &str) -> String {
+    format!("insert_{collection}")
+}
+
+pub fn update_by_pk_procedure_name(collection: &str) -> String {
+    format!("update_{collection}_by_pk")
+}
+
+pub fn delete_by_pk_procedure_name(collection: &str) -> String {
+    format!("delete_{collection}_by_pk")
+}
+
+fn insert_object_type_name(collection: &str) -> String {
+    format!("{collection}_insert_object")
+}
+
+fn set_object_type_name(collection: &str) -> String {
+    format!("{collection}_set_object")
+}
+
+fn collections_with_fields(
+    configuration: &Configuration,
+) -> impl Iterator<Item = (&str, &ObjectFieldDetails)> {
+    configuration
+        .collection_names
+        .iter()
+        .filter_map(|collection| {
+            let object_fields = configuration.object_fields.get(collection)?;
+            Some((collection.as_str(), object_fields))
+        })
+}
+
+/// The type of a column, as declared by the object type of the collection
+fn column_type(configuration: &Configuration, collection: &str, column: &str) -> ndc::models::Type {
+    configuration
+        .object_types
+        .get(collection)
+        .and_then(|object_type| object_type.fields.get(column))
+        .map(|field| field.r#type.clone())
+        .unwrap_or_else(|| {
+            let scalar_type = configuration
+                .object_fields
+                .get(collection)
+                .and_then(|object_fields| object_fields.field_types.get(column))
+                .map_or("String", String::as_str);
+            named(scalar_type)
+        })
+}
+
+fn primary_key_arguments(
+    configuration: &Configuration,
+    collection: &str,
+    object_fields: &ObjectFieldDetails,
+) -> BTreeMap<String, ndc::models::ArgumentInfo> {
+    object_fields
+        .primary_keys
+        .iter()
+        .map(|column| {
+            let argument_type = column_type(configuration, collection, column);
+            (column.clone(), argument(argument_type))
+        })
+        .collect()
+}
+
+/// The object types of the `objects` argument of the insert procedures, and of
+/// the `_set` argument of the update procedures
+fn mutation_object_types(
+    configuration: &Configuration,
+) -> BTreeMap<String, ndc::models::ObjectType> {
+    let mut object_types = BTreeMap::new();
+    for (collection, object_fields) in collections_with_fields(configuration) {
+        // A single integer primary key is an alias of the rowid, which is
+        // generated if it isn't provided
+        let generated_key = match object_fields.primary_keys.as_slice() {
+            [primary_key]
+                if object_fields
+                    .field_types
+                    .get(primary_key)
+                    .map(String::as_str)
+                    == Some("Int") =>
+            {
+                Some(primary_key)
+            }
+            _ => None,
+        };
+        let insert_fields = object_fields
+            .field_names
+            .iter()
+            .map(|column| {
+                let column_type = column_type(configuration, collection, column);
+                let column_type = if Some(column) == generated_key {
+                    nullable(column_type)
+                } else {
+                    column_type
+                };
+                (column.clone(), object_field(column_type))
+            })
+            .collect();
+        object_types.insert(
+            insert_object_type_name(collection),
+            ndc::models::ObjectType {
+                description: Some(format!("A row to insert into {collection}")),
+                fields: insert_fields,
+            },
+        );
+
+        if object_fields.primary_keys.is_empty() {
+            continue;
+        }
+        let set_fields = object_fields
+            .field_names
+            .iter()
+            .filter(|column| !object_fields.primary_keys.contains(column))
+            .map(|column| {
+                let column_type = nullable(column_type(configuration, collection, column));
+                (column.clone(), object_field(column_type))
+            })
+            .collect();
+        object_types.insert(
+            set_object_type_name(collection),
+            ndc::models::ObjectType {
+                description: Some(format!("The columns of {collection} to update")),
+                fields: set_fields,
+            },
+        );
+    }
+    object_types
+}
+
+fn scalar_types(configuration: &Configuration) -> BTreeMap<String, ndc::models::ScalarType> {
+    let scalar_type_names = configuration
+        .object_fields
+        .values()
+        .flat_map(|object_fields| object_fields.field_types.values());
+    let mut scalar_types = BTreeMap::new();
+    for scalar_type_name in scalar_type_names {
+        let mut comparison_operators = BTreeMap::new();
+        let mut operator_names = vec!["_gt", "_lt", "_gte", "_lte", "_neq"];
+        if scalar_type_name == "String" {
+            operator_names.extend(["_like", "_glob"]);
+        }
+        for operator_name in operator_names {
+            comparison_operators.insert(
+                operator_name.to_string(),
+                ndc::models::ComparisonOperatorDefinition {
+                    argument_type: named(scalar_type_name),
+                },
+            );
+        }
+
+        let mut aggregate_functions = BTreeMap::new();
+        let mut function_names = vec!["min", "max"];
+        if scalar_type_name == "Int" || scalar_type_name == "Float" {
+            function_names.extend(["sum", "total"]);
+            aggregate_functions.insert(
+                "avg".to_string(),
+                ndc::models::AggregateFunctionDefinition {
+                    result_type: nullable(named("Float")),
+                },
+            );
+        }
+        for function_name in function_names {
+            aggregate_functions.insert(
+                function_name.to_string(),
+                ndc::models::AggregateFunctionDefinition {
+                    result_type: nullable(named(scalar_type_name)),
+                },
+            );
+        }
+
+        scalar_types.insert(
+            scalar_type_name.clone(),
+            ndc::models::ScalarType {
+                aggregate_functions,
+                comparison_operators,
+            },
+        );
+    }
+    scalar_types
+}
+
+fn collections(configuration: &Configuration) -> Vec<ndc::models::CollectionInfo> {
+    collections_with_fields(configuration)
+        .map(|(collection, object_fields)| {
+            let mut uniqueness_constraints = BTreeMap::new();
+            if !object_fields.primary_keys.is_empty() {
+                uniqueness_constraints.insert(
+                    format!("{collection}ByID"),
+                    ndc::models::UniquenessConstraint {
+                        unique_columns: object_fields.primary_keys.clone(),
+                    },
+                );
+            }
+            let foreign_keys = object_fields
+                .foreign_keys
+                .iter()
+                .map(|(column, foreign_key)| {
+                    (
+                        column.clone(),
+                        ndc::models::ForeignKeyConstraint {
+                            column_mapping: BTreeMap::from([(
+                                column.clone(),
+                                foreign_key.column.clone(),
+                            )]),
+                            foreign_collection: foreign_key.table.clone(),
+                        },
+                    )
+                })
+                .collect();
+            ndc::models::CollectionInfo {
+                name: collection.to_string(),
+                description: None,
+                arguments: BTreeMap::new(),
+                collection_type: collection.to_string(),
+                uniqueness_constraints,
+                foreign_keys,
+            }
+        })
+        .collect()
+}
+
+fn named(name: &str) -> ndc::models::Type {
+    ndc::models::Type::Named {
+        name: name.to_string(),
+    }
+}
+
+fn nullable(underlying_type: ndc::models::Type) -> ndc::models::Type {
+    match underlying_type {
+        ndc::models::Type::Nullable { .. } => underlying_type,
+        _ => ndc::models::Type::Nullable {
+            underlying_type: Box::new(underlying_type),
+        },
+    }
+}
+
+fn array(element_type: ndc::models::Type) -> ndc::models::Type {
+    ndc::models::Type::Array {
+        element_type: Box::new(element_type),
+    }
+}
+
+fn argument(argument_type: ndc::models::Type) -> ndc::models::ArgumentInfo {
+    ndc::models::ArgumentInfo {
+        description: None,
+        argument_type,
+    }
+}
+
+fn object_field(r#type: ndc::models::Type) -> ndc::models::ObjectField {
+    ndc::models::ObjectField {
+        description: None,
+        r#type,
+    }
+}
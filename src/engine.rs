@@ -0,0 +1,351 @@
+//! A stateful engine which resolves the metadata and builds the GraphQL schema
+//! once, and then serves any number of requests against it.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use hasura_authn_core::{
+    resolve_session, Identity, Role, RoleAuthorization, Session, SessionVariable,
+    SessionVariableList, SessionVariableValue,
+};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use lang_graphql::schema::Schema;
+use open_dds::data_connector::DataConnectorName;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::connector::{js::JsConnector, Connector, Connectors};
+use crate::execute::plan_cache::PlanCache;
+use crate::persisted_queries::{self, PersistedQueries};
+use crate::schema::{self, GDS};
+
+/// The number of operation plans the engine keeps
+const PLAN_CACHE_CAPACITY: usize = 100;
+
+/// The engine exported to JS. The schema, the plan cache, the persisted
+/// queries and the connectors are reference counted so that the `Promise`
+/// returned by `execute` can hold on to them even if the engine is reloaded
+/// while the request is in flight.
+#[wasm_bindgen]
+pub struct Engine {
+    schema: Rc<Schema<GDS>>,
+    plan_cache: Rc<PlanCache>,
+    persisted_queries: Rc<PersistedQueries>,
+    connectors: Rc<Connectors>,
+    identity: Identity,
+}
+
+/// The identity requests are authorized against, as passed from JS. Either
+/// `{ admin: "admin" }`, or
+/// `{ specific: { defaultRole: "user", allowedRoles: { user: { sessionVariables: {}, allowedSessionVariablesFromRequest: ["x-hasura-user-id"] } } } }`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum IdentityInput {
+    Admin(Role),
+    #[serde(rename_all = "camelCase")]
+    Specific {
+        default_role: Role,
+        allowed_roles: HashMap<Role, RoleAuthorizationInput>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleAuthorizationInput {
+    #[serde(default)]
+    session_variables: HashMap<SessionVariable, SessionVariableValue>,
+    /// All the session variables are allowed if this isn't specified
+    allowed_session_variables_from_request: Option<HashSet<SessionVariable>>,
+}
+
+impl IdentityInput {
+    fn into_identity(self) -> Identity {
+        match self {
+            IdentityInput::Admin(role) => Identity::admin(role),
+            IdentityInput::Specific {
+                default_role,
+                allowed_roles,
+            } => Identity::Specific {
+                default_role,
+                allowed_roles: allowed_roles
+                    .into_iter()
+                    .map(|(role, authorization)| {
+                        let role_authorization = RoleAuthorization {
+                            role: role.clone(),
+                            session_variables: authorization.session_variables,
+                            allowed_session_variables_from_request: authorization
+                                .allowed_session_variables_from_request
+                                .map_or(SessionVariableList::All, SessionVariableList::Some),
+                        };
+                        (role, role_authorization)
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Converts a headers-like JS object, e.g.
+/// `{ "x-hasura-role": "user", "x-hasura-user-id": "1" }`, to a `HeaderMap`
+pub(crate) fn headers_from_js(headers: JsValue) -> Result<HeaderMap, String> {
+    if headers.is_undefined() || headers.is_null() {
+        return Ok(HeaderMap::new());
+    }
+    let headers = serde_wasm_bindgen::from_value::<HashMap<String, String>>(headers)
+        .map_err(|e| format!("invalid headers: {e}"))?;
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_str(name)
+                .map_err(|e| format!("invalid header name '{name}': {e}"))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| format!("invalid value for header '{name}': {e}"))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Resolves the given metadata and builds the GraphQL schema. Data
+    /// connectors which don't have a connector registered are called over
+    /// HTTP, at their URL in the metadata.
+    #[wasm_bindgen(constructor)]
+    pub fn new(metadata: &str) -> Result<Engine, JsError> {
+        Engine::with_connectors(metadata, Connectors::new().with_http_fallback())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Executes a GraphQL request. The session is resolved from the given
+    /// headers-like object, e.g. `{ "x-hasura-role": "user" }`, against the
+    /// identity of the engine. The plan of the operation is cached, and
+    /// reused by requests of the same operation, role and session variables,
+    /// whatever the values of their variables. The request may refer to a
+    /// persisted query by the hash in its
+    /// `extensions.persistedQuery.sha256Hash`. A batch of requests, sent as a
+    /// JSON array, is executed with the same session, and answered with the
+    /// array of their responses. The returned `Promise` resolves to the
+    /// serialized response.
+    pub fn execute(&self, request: String, headers: JsValue) -> Result<js_sys::Promise, JsError> {
+        let headers = headers_from_js(headers).map_err(|e| JsError::new(&e))?;
+        let session = resolve_session(&self.identity, &headers);
+        let plan_cache = self.plan_cache.clone();
+        let persisted_queries = self.persisted_queries.clone();
+        let connectors = self.connectors.clone();
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            let response = match session {
+                Ok(session) => {
+                    crate::execute_request_with_plan_cache(
+                        &plan_cache,
+                        &persisted_queries,
+                        &connectors,
+                        &session,
+                        &request,
+                    )
+                    .await
+                }
+                Err(e) => crate::serialize_response(e.into()),
+            };
+            Ok(JsValue::from(response))
+        }))
+    }
+
+    /// Explains how a GraphQL request would be executed, without executing
+    /// it. The session is resolved from the given headers-like object, as in
+    /// `execute`. The returned `Promise` resolves to a serialized GraphQL
+    /// response whose `data` has, for each root field, its IR, the NDC
+    /// requests of its query plan with their remote joins, and the
+    /// explanation of the requests by the connector, if it supports explain.
+    pub fn explain(&self, request: String, headers: JsValue) -> Result<js_sys::Promise, JsError> {
+        let headers = headers_from_js(headers).map_err(|e| JsError::new(&e))?;
+        let session = resolve_session(&self.identity, &headers);
+        let schema = self.schema.clone();
+        let persisted_queries = self.persisted_queries.clone();
+        let connectors = self.connectors.clone();
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            let response = match session {
+                Ok(session) => {
+                    crate::explain_request(
+                        &schema,
+                        &persisted_queries,
+                        &connectors,
+                        &session,
+                        &request,
+                    )
+                    .await
+                }
+                Err(e) => crate::serialize_response(e.into()),
+            };
+            Ok(JsValue::from(response))
+        }))
+    }
+
+    /// Sets the identity that the sessions of subsequent requests are
+    /// resolved against. The engine starts off with the `admin` role as the
+    /// admin identity.
+    #[wasm_bindgen(js_name = setIdentity)]
+    pub fn set_identity_js(&mut self, identity: JsValue) -> Result<(), JsError> {
+        let identity = serde_wasm_bindgen::from_value::<IdentityInput>(identity)
+            .map_err(|e| JsError::new(&format!("invalid identity: {e}")))?;
+        self.set_identity(identity.into_identity());
+        Ok(())
+    }
+
+    /// Registers a connector implemented in JS for the data connectors of the
+    /// given name, replacing any connector registered for them. The connector
+    /// is an object with an async `query(request)` function, and optionally
+    /// `mutation(request)`, `capabilities()` and `schema()` functions, which
+    /// take and return NDC requests and responses. Requests which are already
+    /// in flight keep using the previous connectors.
+    #[wasm_bindgen(js_name = registerConnector)]
+    pub fn register_connector_js(&mut self, name: &str, connector: JsValue) -> Result<(), JsError> {
+        let connector = JsConnector::new(connector)
+            .map_err(|e| JsError::new(&format!("invalid connector '{name}': {e}")))?;
+        self.register_connector(DataConnectorName(name.to_string()), connector);
+        Ok(())
+    }
+
+    /// Persists the queries of a manifest, an object of the hex encoded
+    /// SHA-256 hashes of queries to the queries, so that requests can refer
+    /// to them by hash. Fails, persisting none of them, if any hash doesn't
+    /// match its query or any query can't be parsed.
+    #[wasm_bindgen(js_name = loadPersistedQueries)]
+    pub fn load_persisted_queries_js(&self, manifest: JsValue) -> Result<(), JsError> {
+        let manifest = serde_wasm_bindgen::from_value::<HashMap<String, String>>(manifest)
+            .map_err(|e| JsError::new(&format!("invalid manifest: {e}")))?;
+        self.load_persisted_queries(manifest)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Rejects the requests whose query isn't persisted, including the
+    /// requests which would persist it automatically, so that only the
+    /// queries of loaded manifests are served
+    #[wasm_bindgen(js_name = setPersistedQueriesOnly)]
+    pub fn set_persisted_queries_only(&self, persisted_only: bool) {
+        self.persisted_queries.set_persisted_only(persisted_only);
+    }
+
+    /// Returns the result of the introspection query, as seen by the given role
+    pub fn introspect(&self, role: &str) -> Result<String, JsError> {
+        self.introspect_role(&Role::new(role))
+            .map(|introspection| introspection.to_string())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Rebuilds the schema from the given metadata. The registered connectors
+    /// and the persisted queries are kept. The current schema is left
+    /// untouched if the metadata is invalid.
+    pub fn reload(&mut self, metadata: &str) -> Result<(), JsError> {
+        self.reload_metadata(metadata)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+impl Engine {
+    /// Resolves the given metadata and builds the GraphQL schema, executing
+    /// NDC requests against the given connectors
+    pub fn with_connectors(metadata: &str, connectors: Connectors) -> Result<Engine, schema::Error> {
+        let schema = Rc::new(build_schema(metadata)?);
+        Ok(Engine {
+            plan_cache: Rc::new(PlanCache::new(schema.clone(), PLAN_CACHE_CAPACITY)),
+            schema,
+            persisted_queries: Rc::new(PersistedQueries::new()),
+            connectors: Rc::new(connectors),
+            identity: Identity::admin(Role::new("admin")),
+        })
+    }
+
+    /// Sets the identity that the sessions of subsequent requests are
+    /// resolved against
+    pub fn set_identity(&mut self, identity: Identity) {
+        self.identity = identity;
+    }
+
+    /// Registers a connector for the data connectors of the given name, in any
+    /// subgraph
+    pub fn register_connector(
+        &mut self,
+        name: DataConnectorName,
+        connector: impl Connector + 'static,
+    ) {
+        Rc::make_mut(&mut self.connectors).register_unqualified(name, connector);
+    }
+
+    pub fn schema(&self) -> &Schema<GDS> {
+        &self.schema
+    }
+
+    /// The plans of the operations executed by the engine
+    pub fn plan_cache(&self) -> &PlanCache {
+        &self.plan_cache
+    }
+
+    /// Executes a GraphQL request on behalf of the given session
+    pub async fn execute_with_session(&self, raw_request: &str, session: &Session) -> String {
+        crate::execute_request_with_plan_cache(
+            &self.plan_cache,
+            &self.persisted_queries,
+            &self.connectors,
+            session,
+            raw_request,
+        )
+        .await
+    }
+
+    /// Explains how a GraphQL request would be executed on behalf of the
+    /// given session, without executing it
+    pub async fn explain_with_session(&self, raw_request: &str, session: &Session) -> String {
+        crate::explain_request(
+            &self.schema,
+            &self.persisted_queries,
+            &self.connectors,
+            session,
+            raw_request,
+        )
+        .await
+    }
+
+    /// Persists the queries of a manifest of hashes to queries
+    pub fn load_persisted_queries(
+        &self,
+        manifest: HashMap<String, String>,
+    ) -> Result<(), persisted_queries::Error> {
+        self.persisted_queries.load_manifest(manifest)
+    }
+
+    pub fn persisted_queries(&self) -> &PersistedQueries {
+        &self.persisted_queries
+    }
+
+    /// Executes a GraphQL request, resolving the session from the `x-hasura-*`
+    /// headers against the identity of the engine
+    pub async fn execute_with_headers(&self, raw_request: &str, headers: &HeaderMap) -> String {
+        match resolve_session(&self.identity, headers) {
+            Ok(session) => self.execute_with_session(raw_request, &session).await,
+            Err(e) => crate::serialize_response(e.into()),
+        }
+    }
+
+    /// Returns the result of the introspection query, as seen by the given role
+    pub fn introspect_role(
+        &self,
+        role: &Role,
+    ) -> Result<serde_json::Value, lang_graphql::generate_graphql_schema::Error> {
+        lang_graphql::generate_graphql_schema::build_schema_for_namespace(role, &self.schema)
+    }
+
+    /// Rebuilds the schema from the given metadata, keeping the connectors
+    /// and the persisted queries. The plans of the previous schema are
+    /// dropped.
+    pub fn reload_metadata(&mut self, metadata: &str) -> Result<(), schema::Error> {
+        self.schema = Rc::new(build_schema(metadata)?);
+        self.plan_cache = Rc::new(PlanCache::new(self.schema.clone(), PLAN_CACHE_CAPACITY));
+        Ok(())
+    }
+}
+
+/// Resolves the given metadata and builds the GraphQL schema
+pub(crate) fn build_schema(metadata: &str) -> Result<Schema<GDS>, schema::Error> {
+    GDS::new(metadata)?.build_schema()
+}
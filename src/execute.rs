@@ -1,4 +1,6 @@
-// pub mod operation;
-// pub mod process_response;
+pub mod explain;
+pub mod operation;
+pub mod plan_cache;
+pub mod process_response;
 pub mod query_plan;
-// pub mod remote_joins;
\ No newline at end of file
+pub mod remote_joins;
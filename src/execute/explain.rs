@@ -0,0 +1,293 @@
+//! Explains how the root fields of an operation would be executed, without
+//! executing them: the IR of each root field, the NDC requests of its query
+//! plan, and the remote joins along with their join ids.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use async_recursion::async_recursion;
+use futures::future::{self, LocalBoxFuture, Shared};
+use futures::FutureExt;
+use indexmap::IndexMap;
+use open_dds::data_connector::DataConnectorName;
+use open_dds::types::FieldName;
+use serde::Serialize;
+use serde_json as json;
+
+use crate::connector::Connectors;
+use crate::metadata::resolved::{self, subgraph::Qualified, types::FieldMapping};
+use crate::schema::operations;
+use crate::schema::operations::remote_joins::{
+    JoinId, JoinLocations, RemoteJoin, SourceFieldAlias, SourceFieldName, TargetField,
+};
+use crate::schema::types::root_field::RootField;
+use lang_graphql::ast::common as ast;
+use open_dds::ndc_client as ndc;
+
+use super::operation::ExecuteQueryResult;
+use super::query_plan::{NDCMutationExecution, NDCQueryExecution, NodeQueryPlan, QueryPlan};
+
+/// The explanation of a root field
+#[derive(Serialize)]
+struct RootFieldExplain<'a, 'n, 's> {
+    ir: &'a RootField<'n, 's>,
+    /// Absent for root fields which aren't executed on a data connector, such
+    /// as `__typename` and introspection fields
+    plan: Option<PlanExplain>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PlanExplain {
+    Query {
+        root_node: Box<ExecutionNodeExplain<ndc::models::QueryRequest>>,
+        remote_joins: JoinLocationsExplain,
+    },
+    Mutation {
+        root_node: ExecutionNodeExplain<ndc::models::MutationRequest>,
+        remote_joins: JoinLocationsExplain,
+    },
+}
+
+/// An NDC request, and the explanation of it by the connector which serves
+/// the data connector, if the connector supports explain
+#[derive(Serialize)]
+struct ExecutionNodeExplain<R> {
+    data_connector: Qualified<DataConnectorName>,
+    request: R,
+    connector_explain: Option<ConnectorExplain>,
+}
+
+/// The details of the explanation of a request by its connector, or the error
+/// the connector returned instead, which doesn't fail the rest of the explain
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ConnectorExplain {
+    Details(BTreeMap<String, String>),
+    Error { error: String },
+}
+
+/// The remote joins of a node, keyed by the alias of the field they are found
+/// at
+type JoinLocationsExplain = BTreeMap<String, LocationExplain>;
+
+#[derive(Serialize)]
+struct LocationExplain {
+    join_node: Option<RemoteJoinExplain>,
+    rest: JoinLocationsExplain,
+}
+
+/// A remote join. The variables of the request are set to the values of the
+/// join columns in the response of the parent node when it is executed.
+#[derive(Serialize)]
+struct RemoteJoinExplain {
+    join_id: JoinId,
+    join_columns: BTreeMap<FieldName, JoinColumnExplain>,
+    #[serde(flatten)]
+    node: ExecutionNodeExplain<ndc::models::QueryRequest>,
+}
+
+#[derive(Serialize)]
+struct JoinColumnExplain {
+    source_field_alias: String,
+    target_field: FieldName,
+    target_field_mapping: FieldMapping,
+}
+
+/// The connectors that the requests are explained by, along with whether
+/// each data connector supports explain
+struct ExplainContext<'a> {
+    connectors: &'a Connectors,
+    /// The capabilities of a data connector are only fetched once per explain
+    /// request, however many of its requests are explained
+    supports_explain:
+        RefCell<HashMap<Qualified<DataConnectorName>, Shared<LocalBoxFuture<'a, bool>>>>,
+}
+
+impl<'a> ExplainContext<'a> {
+    /// Whether the connector of the data connector advertises the `explain`
+    /// capability. A connector whose capabilities can't be fetched isn't asked
+    /// to explain its requests.
+    async fn supports_explain(
+        &self,
+        data_connector: &resolved::data_connector::DataConnector,
+    ) -> bool {
+        let supports_explain = self
+            .supports_explain
+            .borrow_mut()
+            .entry(data_connector.name.clone())
+            .or_insert_with(|| {
+                let connectors = self.connectors;
+                let data_connector = data_connector.clone();
+                async move {
+                    match connectors.get(&data_connector) {
+                        Ok(connector) => connector
+                            .capabilities()
+                            .await
+                            .is_ok_and(|capabilities| capabilities.capabilities.explain.is_some()),
+                        Err(_) => false,
+                    }
+                }
+                .boxed_local()
+                .shared()
+            })
+            .clone();
+        supports_explain.await
+    }
+}
+
+/// Explains each of the root fields of the query plan. A failure to explain
+/// one root field doesn't affect the others.
+pub async fn explain_query_plan(
+    connectors: &Connectors,
+    ir: &IndexMap<ast::Alias, RootField<'_, '_>>,
+    query_plan: QueryPlan<'_, '_>,
+) -> ExecuteQueryResult {
+    let context = &ExplainContext {
+        connectors,
+        supports_explain: RefCell::new(HashMap::new()),
+    };
+    future::join_all(
+        query_plan
+            .into_iter()
+            .map(|(alias, field_plan)| async move {
+                let field_explain = match ir.get(&alias) {
+                    Some(field_ir) => explain_root_field(context, field_ir, field_plan).await,
+                    None => Err(operations::InternalEngineError::InternalGeneric {
+                        description: format!("could not find the IR of root field {alias}"),
+                    }
+                    .into()),
+                };
+                (alias, field_explain)
+            }),
+    )
+    .await
+    .into_iter()
+    .collect()
+}
+
+async fn explain_root_field(
+    context: &ExplainContext<'_>,
+    ir: &RootField<'_, '_>,
+    field_plan: NodeQueryPlan<'_, '_>,
+) -> Result<json::Value, operations::Error> {
+    let plan = match field_plan {
+        NodeQueryPlan::TypeName { .. }
+        | NodeQueryPlan::SchemaField { .. }
+        | NodeQueryPlan::TypeField { .. }
+        | NodeQueryPlan::RelayNodeSelect(None) => None,
+        NodeQueryPlan::NDCQueryExecution(ndc_query)
+        | NodeQueryPlan::RelayNodeSelect(Some(ndc_query)) => {
+            Some(explain_ndc_query(context, ndc_query).await?)
+        }
+        NodeQueryPlan::NDCMutationExecution(ndc_mutation) => {
+            Some(explain_ndc_mutation(context, ndc_mutation).await?)
+        }
+    };
+    Ok(json::to_value(RootFieldExplain { ir, plan })?)
+}
+
+async fn explain_ndc_query(
+    context: &ExplainContext<'_>,
+    ndc_query: NDCQueryExecution<'_, '_>,
+) -> Result<PlanExplain, operations::Error> {
+    let execution_tree = ndc_query.execution_tree;
+    let root_node = explain_execution_node(
+        context,
+        execution_tree.root_node.query,
+        execution_tree.root_node.data_connector,
+    )
+    .await?;
+    let remote_joins = explain_join_locations(context, execution_tree.remote_executions).await?;
+    Ok(PlanExplain::Query {
+        root_node: Box::new(root_node),
+        remote_joins,
+    })
+}
+
+/// NDC only defines explain for queries, so mutations are never explained by
+/// the connector
+async fn explain_ndc_mutation(
+    context: &ExplainContext<'_>,
+    ndc_mutation: NDCMutationExecution<'_, '_>,
+) -> Result<PlanExplain, operations::Error> {
+    let root_node = ExecutionNodeExplain {
+        data_connector: ndc_mutation.data_connector.name.clone(),
+        request: ndc_mutation.query,
+        connector_explain: None,
+    };
+    let remote_joins = explain_join_locations(context, ndc_mutation.join_locations).await?;
+    Ok(PlanExplain::Mutation {
+        root_node,
+        remote_joins,
+    })
+}
+
+#[async_recursion(?Send)]
+async fn explain_join_locations(
+    context: &ExplainContext<'_>,
+    join_locations: JoinLocations<(RemoteJoin<'async_recursion>, JoinId)>,
+) -> Result<JoinLocationsExplain, operations::Error> {
+    let mut locations = BTreeMap::new();
+    for (key, location) in join_locations.locations {
+        let join_node = match location.join_node {
+            Some((join_node, join_id)) => Some(RemoteJoinExplain {
+                join_id,
+                join_columns: join_columns_explain(join_node.join_columns),
+                node: explain_execution_node(
+                    context,
+                    join_node.target_ndc_ir,
+                    join_node.target_data_connector,
+                )
+                .await?,
+            }),
+            None => None,
+        };
+        let rest = explain_join_locations(context, location.rest).await?;
+        locations.insert(key, LocationExplain { join_node, rest });
+    }
+    Ok(locations)
+}
+
+fn join_columns_explain(
+    join_columns: HashMap<SourceFieldName, (SourceFieldAlias, TargetField)>,
+) -> BTreeMap<FieldName, JoinColumnExplain> {
+    join_columns
+        .into_iter()
+        .map(
+            |(source_field, (source_field_alias, (target_field, mapping)))| {
+                let join_column = JoinColumnExplain {
+                    source_field_alias,
+                    target_field,
+                    target_field_mapping: mapping,
+                };
+                (source_field, join_column)
+            },
+        )
+        .collect()
+}
+
+/// Asks the connector to explain the query, if it advertises the `explain`
+/// capability. An error of the connector is recorded in the explanation.
+async fn explain_execution_node(
+    context: &ExplainContext<'_>,
+    query: ndc::models::QueryRequest,
+    data_connector: &resolved::data_connector::DataConnector,
+) -> Result<ExecutionNodeExplain<ndc::models::QueryRequest>, operations::Error> {
+    let connector = context.connectors.get(data_connector)?;
+    let connector_explain = if context.supports_explain(data_connector).await {
+        Some(match connector.explain(query.clone()).await {
+            Ok(response) => ConnectorExplain::Details(response.details),
+            Err(error) => ConnectorExplain::Error {
+                error: operations::render_ndc_error(&error),
+            },
+        })
+    } else {
+        None
+    };
+    Ok(ExecutionNodeExplain {
+        data_connector: data_connector.name.clone(),
+        request: query,
+        connector_explain,
+    })
+}
@@ -1,298 +1,273 @@
+use std::collections::HashMap;
+
+use futures::future;
 use indexmap::IndexMap;
 use serde_json as json;
 
+use crate::connector::Connectors;
+use crate::logging::log;
 use crate::metadata::resolved;
 use crate::schema::operations;
-use crate::schema::operations::response_processing;
+use crate::schema::operations::remote_joins::{JoinId, JoinLocations, RemoteJoin};
 use crate::schema::GDS;
 use gql::normalized_ast;
 use lang_graphql as gql;
 use lang_graphql::ast::common as ast;
 use open_dds::ndc_client as ndc;
 
-use tracing_util::{set_attribute_on_active_span, AttributeVisibility, SpanVisibility};
-
 use super::process_response::process_response;
+use super::query_plan::merge::{merge_root_queries, MergedQuery};
 use super::query_plan::{
     NDCMutationExecution, NDCQueryExecution, NodeQueryPlan, ProcessResponseAs, QueryPlan,
 };
 use super::remote_joins::execute_join_locations;
 
-pub async fn execute_query_plan<'n, 's>(
-    http_client: &reqwest::Client,
-    query_plan: QueryPlan<'n, 's>,
-) -> Result<IndexMap<ast::Alias, json::Value>, operations::Error> {
+/// The result of executing each of the root fields of an operation
+pub type ExecuteQueryResult = IndexMap<ast::Alias, Result<json::Value, operations::Error>>;
+
+/// The rows of the root NDC query of a root field
+type RootResponses = HashMap<ast::Alias, Vec<ndc::models::RowSet>>;
+
+/// Executes the root fields of the query plan. Query root fields are
+/// independent of each other and are executed concurrently, with the requests
+/// they share merged (see `merge_root_queries`). Mutation root fields are
+/// executed serially, in the order in which they appear in the operation, as
+/// required by the GraphQL spec.
+///
+/// A failure in one root field doesn't affect the others.
+pub async fn execute_query_plan(
+    connectors: &Connectors,
+    query_plan: QueryPlan<'_, '_>,
+) -> ExecuteQueryResult {
     let mut response = IndexMap::new();
-    // println!("Query Plan: {:?}", query_plan);
-    for (alias, field_plan) in query_plan.into_iter() {
-        let field_response: json::Value = match field_plan {
-            NodeQueryPlan::TypeName { type_name } => {
-                set_attribute_on_active_span(AttributeVisibility::Default, "field", "__typename");
-                json::to_value(type_name)?
-            }
-            NodeQueryPlan::TypeField {
-                selection_set,
-                schema,
-                type_name,
-                role: namespace,
-            } => {
-                set_attribute_on_active_span(AttributeVisibility::Default, "field", "__type");
-                match schema.get_type(&type_name) {
-                    Some(type_info) => json::to_value(gql::introspection::named_type(
-                        schema,
-                        &namespace,
-                        type_info,
-                        selection_set,
-                    )?)?,
-                    None => json::Value::Null,
+    let is_mutation = query_plan
+        .values()
+        .any(|field_plan| matches!(field_plan, NodeQueryPlan::NDCMutationExecution(_)));
+    if is_mutation {
+        for (alias, field_plan) in query_plan.into_iter() {
+            let field_response = execute_node_query_plan(connectors, field_plan, None).await;
+            response.insert(alias, field_response);
+        }
+    } else {
+        let mut root_responses =
+            execute_merged_queries(connectors, merge_root_queries(&query_plan)).await;
+        let field_responses =
+            future::join_all(query_plan.into_iter().map(|(alias, field_plan)| {
+                let root_response = root_responses.remove(&alias);
+                async move {
+                    let field_response =
+                        execute_node_query_plan(connectors, field_plan, root_response).await;
+                    (alias, field_response)
+                }
+            }))
+            .await;
+        response.extend(field_responses);
+    }
+    response
+}
+
+/// Executes the merged requests of the root fields concurrently, and fans the
+/// row sets of the responses out to the root fields. The root fields of a
+/// request which fails are left out, so that they are executed on their own
+/// and fail independently of each other.
+async fn execute_merged_queries(
+    connectors: &Connectors,
+    merged_queries: Vec<MergedQuery<'_>>,
+) -> RootResponses {
+    let responses = future::join_all(merged_queries.into_iter().map(|merged_query| async move {
+        let response =
+            execute_ndc_query(connectors, merged_query.query, merged_query.data_connector).await;
+        (merged_query.row_set_fields, response)
+    }))
+    .await;
+    let mut root_responses = HashMap::new();
+    for (row_set_fields, response) in responses {
+        match response {
+            Ok(row_sets) if row_sets.len() == row_set_fields.len() => {
+                for (row_set, aliases) in row_sets.into_iter().zip(row_set_fields) {
+                    for alias in aliases {
+                        root_responses.insert(alias, vec![row_set.clone()]);
+                    }
                 }
             }
-            NodeQueryPlan::SchemaField {
-                role: namespace,
-                selection_set,
-                schema,
-            } => {
-                set_attribute_on_active_span(AttributeVisibility::Default, "field", "__schema");
-                json::to_value(gql::introspection::schema_type(
-                    schema,
-                    &namespace,
-                    selection_set,
-                )?)?
-            }
-            NodeQueryPlan::NDCQueryExecution(ndc_query) => {
-                let NDCQueryExecution {
-                    execution_tree,
-                    selection_set,
-                    execution_span_attribute,
-                    field_span_attribute,
-                    process_response_as,
-                } = ndc_query;
-                let mut response = execute_ndc_query(
-                    http_client,
-                    execution_tree.root_node.query,
-                    execution_tree.root_node.data_connector,
-                    execution_span_attribute.clone(),
-                    field_span_attribute.clone(),
-                )
-                .await?;
-                execute_join_locations(
-                    http_client,
-                    execution_span_attribute,
-                    field_span_attribute,
-                    &mut response,
-                    &process_response_as,
-                    execution_tree.remote_executions,
-                )
-                .await?;
-                let result = process_response(selection_set, response, process_response_as)?;
-                json::to_value(result).map_err(operations::Error::from)?
-            }
-            NodeQueryPlan::NDCMutationExecution(ndc_query) => {
-                let NDCMutationExecution {
-                    query,
-                    data_connector,
-                    selection_set,
-                    execution_span_attribute,
-                    field_span_attribute,
-                    process_response_as,
-                    // TODO: remote joins are not handled for mutations
-                    join_locations: _,
-                } = ndc_query;
-                let response = execute_ndc_mutation(
-                    http_client,
-                    query,
-                    data_connector,
-                    selection_set,
-                    execution_span_attribute,
-                    field_span_attribute,
-                    process_response_as,
-                )
-                .await?;
-                json::to_value(response).map_err(operations::Error::from)?
+            Ok(row_sets) => log(&format!(
+                "Merged query returned {} row sets instead of {}, executing its root fields separately",
+                row_sets.len(),
+                row_set_fields.len()
+            )),
+            Err(e) => log(&format!(
+                "Merged query failed, executing its root fields separately: {e}"
+            )),
+        }
+    }
+    root_responses
+}
+
+/// Builds the GraphQL response from the results of the root fields. A root
+/// field which failed is set to `null`, and its error is reported with the
+/// alias of the field as its path.
+pub fn to_graphql_response(result: ExecuteQueryResult) -> gql::http::Response {
+    let mut data = IndexMap::new();
+    let mut errors = Vec::new();
+    for (alias, field_result) in result {
+        let field_value = match field_result {
+            Ok(value) => value,
+            Err(e) => {
+                let mut error = gql::http::GraphQLError::from(e);
+                error.path = Some(vec![alias.to_string()]);
+                errors.push(error);
+                json::Value::Null
             }
-            NodeQueryPlan::RelayNodeSelect(optional_query) => match optional_query {
-                None => json::Value::Null,
-                Some(ndc_query) => {
-                    let NDCQueryExecution {
-                        execution_tree,
-                        selection_set,
-                        execution_span_attribute,
-                        field_span_attribute,
-                        process_response_as,
-                    } = ndc_query;
-                    let mut response = execute_ndc_query(
-                        http_client,
-                        execution_tree.root_node.query,
-                        execution_tree.root_node.data_connector,
-                        execution_span_attribute.clone(),
-                        field_span_attribute.clone(),
-                    )
-                    .await?;
-                    execute_join_locations(
-                        http_client,
-                        execution_span_attribute,
-                        field_span_attribute,
-                        &mut response,
-                        &process_response_as,
-                        execution_tree.remote_executions,
-                    )
-                    .await?;
-                    let result = process_response(selection_set, response, process_response_as)?;
-                    json::to_value(result).map_err(operations::Error::from)?
-                }
-            },
         };
-        response.insert(alias.clone(), field_response);
+        data.insert(alias, field_value);
+    }
+    if errors.is_empty() {
+        gql::http::Response::ok(data)
+    } else {
+        gql::http::Response::partial(data, errors)
     }
-    Ok(response)
 }
 
-/// Executes a NDC operation
-pub async fn execute_ndc_query<'n, 's>(
-    http_client: &reqwest::Client,
-    query: ndc::models::QueryRequest,
-    data_connector: &resolved::data_connector::DataConnector,
-    execution_span_attribute: String,
-    field_span_attribute: String,
-) -> Result<Vec<ndc::models::RowSet>, operations::Error> {
-    println!("Query Request: {:?}", query);
-    let tracer = tracing_util::global_tracer();
-    tracer
-        .in_span_async("execute_ndc_query", SpanVisibility::User, || {
-            Box::pin(async {
-                set_attribute_on_active_span(
-                    AttributeVisibility::Default,
-                    "operation",
-                    execution_span_attribute,
-                );
-                set_attribute_on_active_span(
-                    AttributeVisibility::Default,
-                    "field",
-                    field_span_attribute,
-                );
-                let connector_response =
-                    fetch_from_data_connector(http_client, query, data_connector).await?;
-                Ok(connector_response.0)
-            })
-        })
-        .await
+/// Executes the query plan of a single root field. The rows of its root NDC
+/// query are only fetched if they haven't been already.
+async fn execute_node_query_plan(
+    connectors: &Connectors,
+    field_plan: NodeQueryPlan<'_, '_>,
+    root_response: Option<Vec<ndc::models::RowSet>>,
+) -> Result<json::Value, operations::Error> {
+    let field_response: json::Value = match field_plan {
+        NodeQueryPlan::TypeName { type_name } => json::to_value(type_name)?,
+        NodeQueryPlan::TypeField {
+            selection_set,
+            schema,
+            type_name,
+            role: namespace,
+        } => match schema.get_type(&type_name) {
+            Some(type_info) => json::to_value(gql::introspection::named_type(
+                schema,
+                &namespace,
+                type_info,
+                selection_set,
+            )?)?,
+            None => json::Value::Null,
+        },
+        NodeQueryPlan::SchemaField {
+            role: namespace,
+            selection_set,
+            schema,
+        } => json::to_value(gql::introspection::schema_type(
+            schema,
+            &namespace,
+            selection_set,
+        )?)?,
+        NodeQueryPlan::NDCQueryExecution(ndc_query) => {
+            execute_ndc_query_plan(connectors, ndc_query, root_response).await?
+        }
+        NodeQueryPlan::NDCMutationExecution(ndc_mutation) => {
+            let NDCMutationExecution {
+                query,
+                join_locations,
+                data_connector,
+                selection_set,
+                process_response_as,
+                ..
+            } = ndc_mutation;
+            execute_ndc_mutation(
+                connectors,
+                query,
+                data_connector,
+                selection_set,
+                process_response_as,
+                join_locations,
+            )
+            .await?
+        }
+        NodeQueryPlan::RelayNodeSelect(optional_query) => match optional_query {
+            // The role doesn't have select permissions on the model which
+            // is the source of the global ID
+            None => json::Value::Null,
+            Some(ndc_query) => execute_ndc_query_plan(connectors, ndc_query, root_response).await?,
+        },
+    };
+    Ok(field_response)
 }
 
-pub(crate) async fn fetch_from_data_connector<'s>(
-    http_client: &reqwest::Client,
-    query_request: ndc::models::QueryRequest,
+/// Executes the NDC query of a root field along with its remote joins, and
+/// processes the response into the shape of the field's selection set
+async fn execute_ndc_query_plan(
+    connectors: &Connectors,
+    ndc_query: NDCQueryExecution<'_, '_>,
+    root_response: Option<Vec<ndc::models::RowSet>>,
+) -> Result<json::Value, operations::Error> {
+    let NDCQueryExecution {
+        execution_tree,
+        selection_set,
+        process_response_as,
+        ..
+    } = ndc_query;
+    let mut response = match root_response {
+        Some(response) => response,
+        None => {
+            execute_ndc_query(
+                connectors,
+                execution_tree.root_node.query,
+                execution_tree.root_node.data_connector,
+            )
+            .await?
+        }
+    };
+    execute_join_locations(
+        connectors,
+        &mut response,
+        &process_response_as,
+        execution_tree.remote_executions,
+    )
+    .await?;
+    process_response(selection_set, response, process_response_as)
+}
+
+/// Executes a NDC query on the connector registered for the data connector
+pub async fn execute_ndc_query(
+    connectors: &Connectors,
+    query: ndc::models::QueryRequest,
     data_connector: &resolved::data_connector::DataConnector,
-) -> Result<ndc::models::QueryResponse, operations::Error> {
-    let tracer = tracing_util::global_tracer();
-    tracer
-        .in_span_async(
-            "fetch_from_data_connector",
-            SpanVisibility::Internal,
-            || {
-                Box::pin(async {
-                    let ndc_config = ndc::apis::configuration::Configuration {
-                        base_path: data_connector.url.get_url(ast::OperationType::Query),
-                        user_agent: None,
-                        // This is isn't expensive, reqwest::Client is behind an Arc
-                        client: http_client.clone(),
-                        headers: data_connector.headers.0.clone(),
-                    };
-                    ndc::apis::default_api::query_post(&ndc_config, query_request)
-                        .await
-                        .map_err(operations::Error::from) // ndc_client::apis::Error -> InternalError -> Error
-                })
-            },
-        )
-        .await
+) -> Result<Vec<ndc::models::RowSet>, operations::Error> {
+    let connector_response = connectors.get(data_connector)?.query(query).await?;
+    Ok(connector_response.0)
 }
 
-pub(crate) async fn execute_ndc_mutation<'n, 's>(
-    http_client: &reqwest::Client,
+/// Executes a NDC mutation on the connector registered for the data connector,
+/// and processes the result of the (single) procedure as a command response
+pub async fn execute_ndc_mutation<'s>(
+    connectors: &Connectors,
     query: ndc::models::MutationRequest,
     data_connector: &resolved::data_connector::DataConnector,
-    selection_set: &'n normalized_ast::SelectionSet<'s, GDS>,
-    execution_span_attribute: String,
-    field_span_attribute: String,
+    selection_set: &normalized_ast::SelectionSet<'s, GDS>,
     process_response_as: ProcessResponseAs<'s>,
+    join_locations: JoinLocations<(RemoteJoin<'s>, JoinId)>,
 ) -> Result<json::Value, operations::Error> {
-    let tracer = tracing_util::global_tracer();
-    tracer
-        .in_span_async("execute_ndc_mutation", SpanVisibility::User, || {
-            Box::pin(async {
-                set_attribute_on_active_span(
-                    AttributeVisibility::Default,
-                    "operation",
-                    execution_span_attribute,
-                );
-                set_attribute_on_active_span(
-                    AttributeVisibility::Default,
-                    "field",
-                    field_span_attribute,
-                );
-                let connector_response =
-                    fetch_from_data_connector_mutation(http_client, query, data_connector).await?;
-                // Post process the response to add the `__typename` fields
-                tracer.in_span("process_response", SpanVisibility::Internal, || {
-                    // NOTE: NDC returns a `Vec<RowSet>` (to account for
-                    // variables). We don't use variables in NDC queries yet,
-                    // hence we always pick the first `RowSet`.
-                    let mutation_results = connector_response
-                        .operation_results
-                        .into_iter()
-                        .next()
-                        .ok_or(operations::InternalDeveloperError::BadGDCResponse {
-                            summary: "missing rowset".into(),
-                        })?;
-                    match process_response_as {
-                        ProcessResponseAs::CommandResponse {
-                            command_name,
-                            type_container,
-                        } => {
-                            let result = response_processing::process_command_rows(
-                                command_name,
-                                mutation_results.returning,
-                                selection_set,
-                                type_container,
-                            )?;
-                            Ok(json::to_value(result).map_err(operations::Error::from))
-                        }
-                        _ => Err(operations::Error::from(
-                            operations::InternalEngineError::InternalGeneric {
-                                description: "mutations without commands are not supported yet"
-                                    .into(),
-                            },
-                        )),
-                    }?
-                })
-            })
-        })
-        .await
-}
-
-pub(crate) async fn fetch_from_data_connector_mutation<'s>(
-    http_client: &reqwest::Client,
-    query_request: ndc::models::MutationRequest,
-    data_connector: &resolved::data_connector::DataConnector,
-) -> Result<ndc::models::MutationResponse, operations::Error> {
-    let tracer = tracing_util::global_tracer();
-    tracer
-        .in_span_async(
-            "fetch_from_data_connector",
-            SpanVisibility::Internal,
-            || {
-                Box::pin(async {
-                    let gdc_config = ndc::apis::configuration::Configuration {
-                        base_path: data_connector.url.get_url(ast::OperationType::Mutation),
-                        user_agent: None,
-                        // This is isn't expensive, reqwest::Client is behind an Arc
-                        client: http_client.clone(),
-                        headers: data_connector.headers.0.clone(),
-                    };
-                    ndc::apis::default_api::mutation_post(&gdc_config, query_request)
-                        .await
-                        .map_err(operations::Error::from) // ndc_client::apis::Error -> InternalError -> Error
-                })
-            },
-        )
-        .await
+    let connector_response = connectors.get(data_connector)?.mutation(query).await?;
+    // NOTE: A mutation root field is planned as a single procedure, hence we
+    // always pick the first operation result.
+    let mutation_results = connector_response
+        .operation_results
+        .into_iter()
+        .next()
+        .ok_or(operations::InternalDeveloperError::BadGDCResponse {
+            summary: "missing mutation operation result".into(),
+        })?;
+    // The rows returned by the procedure are processed like the rows of a
+    // command response, so that remote joins and post processing can be shared
+    let mut response = vec![ndc::models::RowSet {
+        aggregates: None,
+        rows: mutation_results.returning,
+    }];
+    execute_join_locations(
+        connectors,
+        &mut response,
+        &process_response_as,
+        join_locations,
+    )
+    .await?;
+    process_response(selection_set, response, process_response_as)
 }
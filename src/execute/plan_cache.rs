@@ -0,0 +1,607 @@
+//! A cache of the plans of the operations served by the engine, so that
+//! repeated requests, such as those of an SPA, don't each pay for parsing,
+//! normalization and IR generation.
+//!
+//! Plans are keyed by the text of the query document, the operation name, the
+//! role, and the values of the session variables which the permissions of the
+//! operation reference. A plan is reused across the values of the variables of
+//! the operation: when the operation is planned, it is planned again with a
+//! different value for each variable, to find the literal values of its NDC
+//! requests which are the value of the variable. The values of the variables
+//! of subsequent requests are substituted there. A variable whose value
+//! affects anything else, such as a `limit`, an enum or a relay ID, is made
+//! part of the key instead.
+//!
+//! This makes a miss expensive: an operation with N string or integer
+//! variables is normalized, and its IR and query plan generated, N + 1 times.
+//! Only the first request of each plan key pays for it, so it is traded for
+//! not having to track where the values of variables end up during IR
+//! generation.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+use hasura_authn_core::{Role, Session, SessionVariable};
+use indexmap::IndexMap;
+use open_dds::permissions::ValueExpression;
+use self_cell::self_cell;
+use serde_json as json;
+
+use crate::metadata::resolved::model::{FilterPermission, ModelPredicate};
+use crate::persisted_queries::Query;
+use crate::schema::operations::{
+    self,
+    remote_joins::{JoinId, JoinLocations, RemoteJoin},
+};
+use crate::schema::types::{
+    root_field::RootField, Annotation, NamespaceAnnotation, OutputAnnotation, RootFieldAnnotation,
+};
+use crate::schema::GDS;
+use crate::utils::lru::Lru;
+use lang_graphql as gql;
+use lang_graphql::ast::common as ast;
+use lang_graphql::schema::Schema;
+use open_dds::ndc_client as ndc;
+
+use super::query_plan::{generate_query_plan, NodeQueryPlan, QueryPlan};
+
+type NormalizedOperation<'s> = gql::normalized_ast::Operation<'s, GDS>;
+
+type OperationIr<'s> = IndexMap<ast::Alias, RootField<'s, 's>>;
+
+self_cell!(
+    /// A normalized operation, along with the schema it borrows from
+    struct SchemaOperation {
+        owner: Rc<Schema<GDS>>,
+        #[covariant]
+        dependent: NormalizedOperation,
+    }
+);
+
+self_cell!(
+    /// The IR of a normalized operation
+    struct OperationWithIr {
+        owner: SchemaOperation,
+        #[covariant]
+        dependent: OperationIr,
+    }
+);
+
+/// The plans of the operations executed against a schema. The cache holds
+/// at most `capacity` plans, evicting the least recently used one when full.
+pub struct PlanCache {
+    schema: Rc<Schema<GDS>>,
+    shapes: RefCell<Lru<OperationKey, Rc<OperationShape>>>,
+    plans: RefCell<Lru<PlanKey, Rc<CachedPlan>>>,
+}
+
+/// A plan taken from the cache, along with the values of the variables of the
+/// request to substitute in it
+pub struct PreparedPlan {
+    plan: Rc<CachedPlan>,
+    values: Vec<(usize, json::Value)>,
+}
+
+struct CachedPlan {
+    operation: OperationWithIr,
+    /// The positions of the literal values of the plan (see `plan_literals`)
+    /// which are the value of each variable
+    substitutions: BTreeMap<ast::Name, Vec<usize>>,
+}
+
+/// What is found out about an operation when it is first planned, which is
+/// needed to build the keys of its plans
+#[derive(Clone)]
+struct OperationShape {
+    /// The session variables which the permissions of the operation reference
+    session_variables: Vec<SessionVariable>,
+    /// The variables whose values can't be substituted in the plan
+    fixed_variables: BTreeSet<ast::Name>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct OperationKey {
+    /// The text of the query document, rather than a hash of it, so that an
+    /// operation is never served the plan of another one
+    document: Rc<str>,
+    operation_name: Option<ast::Name>,
+    role: Role,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct PlanKey {
+    operation: OperationKey,
+    session_variables: Vec<Option<String>>,
+    variables: BTreeMap<ast::Name, VariableKey>,
+}
+
+/// The part of the key of a plan which a variable makes up
+#[derive(PartialEq, Eq, Hash)]
+enum VariableKey {
+    /// The value is substituted in the plan, so only its kind matters
+    String,
+    Integer,
+    /// The serialized value
+    Value(String),
+}
+
+impl PlanCache {
+    pub fn new(schema: Rc<Schema<GDS>>, capacity: usize) -> Self {
+        PlanCache {
+            schema,
+            shapes: RefCell::new(Lru::new(capacity)),
+            plans: RefCell::new(Lru::new(capacity)),
+        }
+    }
+
+    pub fn schema(&self) -> &Schema<GDS> {
+        &self.schema
+    }
+
+    /// The number of plans in the cache
+    pub fn len(&self) -> usize {
+        self.plans.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the plan of the operation of the query, planning it on behalf
+    /// of the session if it isn't in the cache
+    pub fn prepare(
+        &self,
+        session: &Session,
+        query: &Query,
+        operation_name: Option<ast::Name>,
+        variables: HashMap<ast::Name, json::Value>,
+    ) -> Result<PreparedPlan, operations::Error> {
+        let operation_key = OperationKey {
+            document: Rc::from(query.text()),
+            operation_name: operation_name.clone(),
+            role: session.role.clone(),
+        };
+        let shape = self.shapes.borrow_mut().get(&operation_key);
+        if let Some(shape) = &shape {
+            let plan_key = shape.plan_key(operation_key.clone(), session, &variables);
+            if let Some(plan) = self.plans.borrow_mut().get(&plan_key) {
+                let values = plan
+                    .substitutions
+                    .iter()
+                    .filter_map(|(name, positions)| Some((variables.get(name)?, positions)))
+                    .flat_map(|(value, positions)| {
+                        positions.iter().map(|position| (*position, value.clone()))
+                    })
+                    .collect();
+                return Ok(PreparedPlan { plan, values });
+            }
+        }
+
+        let mut request = gql::http::Request {
+            operation_name,
+            query: query.to_document()?,
+            variables,
+        };
+        let operation = SchemaOperation::try_new(self.schema.clone(), |schema| {
+            gql::validation::normalize_request(&session.role, schema, &request)
+        })?;
+        let operation = OperationWithIr::try_new(operation, |operation| {
+            crate::generate_ir(
+                operation.borrow_owner(),
+                session,
+                operation.borrow_dependent(),
+            )
+        })?;
+        let mut shape = match shape {
+            Some(shape) => (*shape).clone(),
+            None => OperationShape {
+                session_variables: operation_session_variables(
+                    operation.borrow_owner().borrow_dependent(),
+                    &session.role,
+                ),
+                fixed_variables: BTreeSet::new(),
+            },
+        };
+        let substitutions = find_substitutions(
+            &self.schema,
+            session,
+            &mut request,
+            &operation,
+            &mut shape.fixed_variables,
+        )?;
+        let plan_key = shape.plan_key(operation_key.clone(), session, &request.variables);
+        let plan = Rc::new(CachedPlan {
+            operation,
+            substitutions,
+        });
+        self.shapes
+            .borrow_mut()
+            .insert(operation_key, Rc::new(shape));
+        self.plans.borrow_mut().insert(plan_key, plan.clone());
+        Ok(PreparedPlan {
+            plan,
+            values: Vec::new(),
+        })
+    }
+}
+
+impl PreparedPlan {
+    /// Generates the query plan from the cached IR, with the values of the
+    /// variables of the request substituted in it
+    pub fn query_plan(&self) -> Result<QueryPlan<'_, '_>, operations::Error> {
+        let mut query_plan = generate_query_plan(self.plan.operation.borrow_dependent())?;
+        if !self.values.is_empty() {
+            let mut parts = plan_parts(&mut query_plan);
+            let mut literals = plan_literals(&mut parts);
+            for (position, value) in &self.values {
+                if let Some(literal) = literals.get_mut(*position) {
+                    **literal = value.clone();
+                }
+            }
+        }
+        Ok(query_plan)
+    }
+}
+
+impl OperationShape {
+    fn plan_key(
+        &self,
+        operation: OperationKey,
+        session: &Session,
+        variables: &HashMap<ast::Name, json::Value>,
+    ) -> PlanKey {
+        let session_variables = self
+            .session_variables
+            .iter()
+            .map(|session_variable| {
+                session
+                    .variables
+                    .get(session_variable)
+                    .map(|value| value.0.clone())
+            })
+            .collect();
+        let variables = variables
+            .iter()
+            .map(|(name, value)| {
+                let variable_key = match value {
+                    _ if self.fixed_variables.contains(name) => {
+                        VariableKey::Value(value.to_string())
+                    }
+                    json::Value::String(_) => VariableKey::String,
+                    json::Value::Number(number) if number.is_i64() => VariableKey::Integer,
+                    _ => VariableKey::Value(value.to_string()),
+                };
+                (name.clone(), variable_key)
+            })
+            .collect();
+        PlanKey {
+            operation,
+            session_variables,
+            variables,
+        }
+    }
+}
+
+/// Finds the positions of the literal values of the plan of the operation
+/// which are the value of each of the string and integer variables of the
+/// request. A variable whose value can't be substituted in the plan is added
+/// to the fixed variables.
+fn find_substitutions(
+    schema: &Schema<GDS>,
+    session: &Session,
+    request: &mut gql::http::Request,
+    operation: &OperationWithIr,
+    fixed_variables: &mut BTreeSet<ast::Name>,
+) -> Result<BTreeMap<ast::Name, Vec<usize>>, operations::Error> {
+    let mut query_plan = generate_query_plan(operation.borrow_dependent())?;
+    let mut parts = plan_parts(&mut query_plan);
+    let literals = take_literals(&mut parts);
+    let candidates: Vec<ast::Name> = request
+        .variables
+        .iter()
+        .filter(|(name, value)| !fixed_variables.contains(*name) && probe_value(value).is_some())
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut substitutions = BTreeMap::new();
+    for name in candidates {
+        match probe_variable(schema, session, request, &name, &parts, &literals) {
+            Some(positions) => {
+                substitutions.insert(name, positions);
+            }
+            None => {
+                fixed_variables.insert(name);
+            }
+        }
+    }
+    Ok(substitutions)
+}
+
+/// A value of the same kind as the given one, but different from it
+fn probe_value(value: &json::Value) -> Option<json::Value> {
+    match value {
+        json::Value::String(string) => Some(json::Value::from(format!("{string}~"))),
+        json::Value::Number(number) => number.as_i64().map(|integer| (integer ^ 1).into()),
+        _ => None,
+    }
+}
+
+/// Plans the operation again with a different value for the variable, and
+/// returns the positions of the literal values which changed to it. Returns
+/// `None` if anything else about the plan changed, or if the operation can't
+/// be planned with the new value.
+fn probe_variable(
+    schema: &Schema<GDS>,
+    session: &Session,
+    request: &mut gql::http::Request,
+    name: &ast::Name,
+    parts: &[PlanPart<'_>],
+    literals: &[json::Value],
+) -> Option<Vec<usize>> {
+    let value = request.variables.get(name)?.clone();
+    let probe = probe_value(&value)?;
+    request.variables.insert(name.clone(), probe.clone());
+    let positions = (|| {
+        let operation = gql::validation::normalize_request(&session.role, schema, request).ok()?;
+        let ir = crate::generate_ir(schema, session, &operation).ok()?;
+        let mut probe_plan = generate_query_plan(&ir).ok()?;
+        let mut probe_parts = plan_parts(&mut probe_plan);
+        let probe_literals = take_literals(&mut probe_parts);
+        if probe_parts.as_slice() != parts || probe_literals.len() != literals.len() {
+            return None;
+        }
+        let mut positions = Vec::new();
+        for (position, (literal, probe_literal)) in literals.iter().zip(&probe_literals).enumerate()
+        {
+            if literal != probe_literal {
+                if *literal != value || *probe_literal != probe {
+                    return None;
+                }
+                positions.push(position);
+            }
+        }
+        Some(positions)
+    })();
+    request.variables.insert(name.clone(), value);
+    positions
+}
+
+/// The parts of a query plan which the values of variables may end up in
+#[derive(PartialEq)]
+enum PlanPart<'p> {
+    Query(&'p mut ndc::models::QueryRequest),
+    Mutation(&'p mut ndc::models::MutationRequest),
+    TypeName(&'p ast::TypeName),
+}
+
+/// The parts of the query plan, in an order which doesn't change between
+/// plans of the same IR
+fn plan_parts<'p>(query_plan: &'p mut QueryPlan<'_, '_>) -> Vec<PlanPart<'p>> {
+    let mut parts = Vec::new();
+    for field_plan in query_plan.values_mut() {
+        match field_plan {
+            NodeQueryPlan::TypeName { .. }
+            | NodeQueryPlan::SchemaField { .. }
+            | NodeQueryPlan::RelayNodeSelect(None) => {}
+            NodeQueryPlan::TypeField { type_name, .. } => parts.push(PlanPart::TypeName(type_name)),
+            NodeQueryPlan::NDCQueryExecution(ndc_query)
+            | NodeQueryPlan::RelayNodeSelect(Some(ndc_query)) => {
+                let execution_tree = &mut ndc_query.execution_tree;
+                parts.push(PlanPart::Query(&mut execution_tree.root_node.query));
+                join_location_parts(&mut execution_tree.remote_executions, &mut parts);
+            }
+            NodeQueryPlan::NDCMutationExecution(ndc_mutation) => {
+                parts.push(PlanPart::Mutation(&mut ndc_mutation.query));
+                join_location_parts(&mut ndc_mutation.join_locations, &mut parts);
+            }
+        }
+    }
+    parts
+}
+
+fn join_location_parts<'p>(
+    join_locations: &'p mut JoinLocations<(RemoteJoin<'_>, JoinId)>,
+    parts: &mut Vec<PlanPart<'p>>,
+) {
+    let mut locations: Vec<_> = join_locations.locations.iter_mut().collect();
+    locations.sort_by_key(|(key, _)| *key);
+    for (_, location) in locations {
+        if let Some((join_node, _)) = &mut location.join_node {
+            parts.push(PlanPart::Query(&mut join_node.target_ndc_ir));
+        }
+        join_location_parts(&mut location.rest, parts);
+    }
+}
+
+/// Takes the literal values out of the parts, leaving `null` in their place
+fn take_literals(parts: &mut [PlanPart<'_>]) -> Vec<json::Value> {
+    plan_literals(parts)
+        .into_iter()
+        .map(json::Value::take)
+        .collect()
+}
+
+/// The literal values of the parts: the scalar values which predicates
+/// compare against, and the values of arguments
+fn plan_literals<'a>(parts: &'a mut [PlanPart<'_>]) -> Vec<&'a mut json::Value> {
+    let mut literals = Literals { values: Vec::new() };
+    for part in parts {
+        match part {
+            PlanPart::Query(query_request) => literals.query_request(query_request),
+            PlanPart::Mutation(mutation_request) => {
+                for operation in &mut mutation_request.operations {
+                    match operation {
+                        ndc::models::MutationOperation::Procedure {
+                            arguments, fields, ..
+                        } => {
+                            literals.values.extend(arguments.values_mut());
+                            literals.fields(fields);
+                        }
+                    }
+                }
+            }
+            PlanPart::TypeName(_) => {}
+        }
+    }
+    literals.values
+}
+
+struct Literals<'a> {
+    values: Vec<&'a mut json::Value>,
+}
+
+impl<'a> Literals<'a> {
+    fn query_request(&mut self, query_request: &'a mut ndc::models::QueryRequest) {
+        for argument in query_request.arguments.values_mut() {
+            if let ndc::models::Argument::Literal { value } = argument {
+                self.values.push(value);
+            }
+        }
+        self.query(&mut query_request.query);
+    }
+
+    fn query(&mut self, query: &'a mut ndc::models::Query) {
+        if let Some(predicate) = &mut query.predicate {
+            self.expression(predicate);
+        }
+        self.fields(&mut query.fields);
+    }
+
+    fn fields(&mut self, fields: &'a mut Option<IndexMap<String, ndc::models::Field>>) {
+        for field in fields.iter_mut().flat_map(|fields| fields.values_mut()) {
+            if let ndc::models::Field::Relationship {
+                query, arguments, ..
+            } = field
+            {
+                self.relationship_arguments(arguments);
+                self.query(query);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &'a mut ndc::models::Expression) {
+        match expression {
+            ndc::models::Expression::And { expressions }
+            | ndc::models::Expression::Or { expressions } => {
+                for expression in expressions {
+                    self.expression(expression);
+                }
+            }
+            ndc::models::Expression::Not { expression } => self.expression(expression),
+            ndc::models::Expression::UnaryComparisonOperator { .. } => {}
+            ndc::models::Expression::BinaryComparisonOperator { value, .. } => {
+                self.comparison_value(value);
+            }
+            ndc::models::Expression::BinaryArrayComparisonOperator { values, .. } => {
+                for value in values {
+                    self.comparison_value(value);
+                }
+            }
+            ndc::models::Expression::Exists {
+                in_collection,
+                predicate,
+            } => {
+                match in_collection {
+                    ndc::models::ExistsInCollection::Related { arguments, .. }
+                    | ndc::models::ExistsInCollection::Unrelated { arguments, .. } => {
+                        self.relationship_arguments(arguments);
+                    }
+                }
+                self.expression(predicate);
+            }
+        }
+    }
+
+    fn comparison_value(&mut self, value: &'a mut ndc::models::ComparisonValue) {
+        if let ndc::models::ComparisonValue::Scalar { value } = value {
+            self.values.push(value);
+        }
+    }
+
+    fn relationship_arguments(
+        &mut self,
+        arguments: &'a mut BTreeMap<String, ndc::models::RelationshipArgument>,
+    ) {
+        for argument in arguments.values_mut() {
+            if let ndc::models::RelationshipArgument::Literal { value } = argument {
+                self.values.push(value);
+            }
+        }
+    }
+}
+
+/// The session variables which the permissions of the fields of the
+/// operation reference, for the given role
+fn operation_session_variables(
+    operation: &NormalizedOperation<'_>,
+    role: &Role,
+) -> Vec<SessionVariable> {
+    let mut session_variables = Vec::new();
+    selection_set_session_variables(&operation.selection_set, role, &mut session_variables);
+    session_variables
+}
+
+fn selection_set_session_variables(
+    selection_set: &gql::normalized_ast::SelectionSet<'_, GDS>,
+    role: &Role,
+    session_variables: &mut Vec<SessionVariable>,
+) {
+    for field in selection_set.fields.values() {
+        for field_call in field.field_calls.values() {
+            if let Some(NamespaceAnnotation::Filter(filter)) = field_call.info.namespaced {
+                filter_session_variables(filter, session_variables);
+            }
+            // The permissions of the model of a relay node are only known
+            // once its ID is decoded
+            if let Annotation::Output(OutputAnnotation::RootField(
+                RootFieldAnnotation::RelayNode { typename_mappings },
+            )) = field_call.info.generic
+            {
+                for typename_mapping in typename_mappings.values() {
+                    if let Some(permission) = typename_mapping.model_select_permissions.get(role) {
+                        filter_session_variables(&permission.filter, session_variables);
+                    }
+                }
+            }
+        }
+        selection_set_session_variables(&field.selection_set, role, session_variables);
+    }
+}
+
+fn filter_session_variables(
+    filter: &FilterPermission,
+    session_variables: &mut Vec<SessionVariable>,
+) {
+    if let FilterPermission::Filter(predicate) = filter {
+        predicate_session_variables(predicate, session_variables);
+    }
+}
+
+fn predicate_session_variables(
+    predicate: &ModelPredicate,
+    session_variables: &mut Vec<SessionVariable>,
+) {
+    match predicate {
+        ModelPredicate::BinaryFieldComparison {
+            value: ValueExpression::SessionVariable(session_variable),
+            ..
+        } => {
+            if !session_variables.contains(session_variable) {
+                session_variables.push(session_variable.clone());
+            }
+        }
+        ModelPredicate::UnaryFieldComparison { .. }
+        | ModelPredicate::BinaryFieldComparison { .. } => {}
+        ModelPredicate::Relationship { predicate, .. } => {
+            if let Some(predicate) = predicate {
+                predicate_session_variables(predicate, session_variables);
+            }
+        }
+        ModelPredicate::And(predicates) | ModelPredicate::Or(predicates) => {
+            for predicate in predicates {
+                predicate_session_variables(predicate, session_variables);
+            }
+        }
+        ModelPredicate::Not(predicate) => predicate_session_variables(predicate, session_variables),
+    }
+}
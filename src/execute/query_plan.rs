@@ -17,6 +17,8 @@ use lang_graphql as gql;
 use lang_graphql::ast::common as ast;
 use open_dds::ndc_client as ndc;
 
+pub mod merge;
+
 pub type QueryPlan<'n, 's> = IndexMap<ast::Alias, NodeQueryPlan<'n, 's>>;
 
 /// Query plan of individual root field or node
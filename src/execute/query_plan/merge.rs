@@ -0,0 +1,236 @@
+//! A pass over the query plan of a query which merges the NDC requests of its
+//! root fields, so that root fields on the same data connector don't each cost
+//! a round-trip.
+//!
+//! Root fields with identical requests are sent a single request, and the row
+//! set of the response is fanned out to each of them. If the data connector
+//! supports variables, root fields whose requests only differ in the scalar
+//! values that their predicates compare against, or in the values of their
+//! arguments, are sent a single request with a set of variables per distinct
+//! request.
+
+use std::collections::BTreeMap;
+
+use serde_json as json;
+
+use crate::metadata::resolved;
+use lang_graphql::ast::common as ast;
+use open_dds::ndc_client as ndc;
+
+use super::{NDCQueryExecution, NodeQueryPlan, QueryPlan};
+
+/// The prefix of the names of the variables which the values of merged
+/// requests are replaced with
+const VARIABLE_PREFIX: &str = "__merged_";
+
+/// A request which is sent to a data connector on behalf of several root
+/// fields
+#[derive(Debug)]
+pub struct MergedQuery<'s> {
+    pub query: ndc::models::QueryRequest,
+    pub data_connector: &'s resolved::data_connector::DataConnector,
+    /// For each row set of the response, the root fields that it is the
+    /// response of
+    pub row_set_fields: Vec<Vec<ast::Alias>>,
+}
+
+/// Merges the requests of the root fields of the query plan. Only requests
+/// which are shared by more than one root field are returned; the other root
+/// fields are executed on their own.
+pub fn merge_root_queries<'s>(query_plan: &QueryPlan<'_, 's>) -> Vec<MergedQuery<'s>> {
+    let mut groups: Vec<QueryGroup<'s>> = Vec::new();
+    for (alias, field_plan) in query_plan {
+        let ndc_query = match field_plan {
+            NodeQueryPlan::NDCQueryExecution(ndc_query)
+            | NodeQueryPlan::RelayNodeSelect(Some(ndc_query)) => ndc_query,
+            _ => continue,
+        };
+        let NDCQueryExecution { execution_tree, .. } = ndc_query;
+        let query = &execution_tree.root_node.query;
+        let data_connector = execution_tree.root_node.data_connector;
+        // Requests which already have variables can't be parameterized
+        if query.variables.is_some() {
+            continue;
+        }
+        let (template, variables) = if data_connector.capabilities.supports_query_variables {
+            parameterize(query)
+        } else {
+            (query.clone(), BTreeMap::new())
+        };
+        let group = groups.iter_mut().find(|group| {
+            group.data_connector.name == data_connector.name && group.template == template
+        });
+        match group {
+            Some(group) => group.add(alias, variables),
+            None => groups.push(QueryGroup {
+                data_connector,
+                query: query.clone(),
+                template,
+                variable_sets: vec![(variables, vec![alias.clone()])],
+            }),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|group| group.field_count() > 1)
+        .map(QueryGroup::into_merged_query)
+        .collect()
+}
+
+/// The root fields whose requests are the same once their values are replaced
+/// with variables
+struct QueryGroup<'s> {
+    data_connector: &'s resolved::data_connector::DataConnector,
+    /// The request of the first root field of the group
+    query: ndc::models::QueryRequest,
+    template: ndc::models::QueryRequest,
+    /// The distinct sets of values of the requests, with the root fields
+    /// whose requests have them
+    variable_sets: Vec<(BTreeMap<String, json::Value>, Vec<ast::Alias>)>,
+}
+
+impl<'s> QueryGroup<'s> {
+    fn add(&mut self, alias: &ast::Alias, variables: BTreeMap<String, json::Value>) {
+        match self
+            .variable_sets
+            .iter_mut()
+            .find(|(existing_variables, _)| *existing_variables == variables)
+        {
+            Some((_, aliases)) => aliases.push(alias.clone()),
+            None => self.variable_sets.push((variables, vec![alias.clone()])),
+        }
+    }
+
+    fn field_count(&self) -> usize {
+        self.variable_sets
+            .iter()
+            .map(|(_, aliases)| aliases.len())
+            .sum()
+    }
+
+    fn into_merged_query(self) -> MergedQuery<'s> {
+        let (variable_sets, row_set_fields): (Vec<_>, Vec<_>) =
+            self.variable_sets.into_iter().unzip();
+        // The requests are identical, so there is no need for variables
+        let query = if variable_sets.len() == 1 {
+            self.query
+        } else {
+            ndc::models::QueryRequest {
+                variables: Some(variable_sets),
+                ..self.template
+            }
+        };
+        MergedQuery {
+            query,
+            data_connector: self.data_connector,
+            row_set_fields,
+        }
+    }
+}
+
+/// Replaces the scalar values which the predicates of a request compare
+/// against, and the literal values of its arguments, with variables. Returns
+/// the new request along with the values of the variables.
+fn parameterize(
+    query: &ndc::models::QueryRequest,
+) -> (ndc::models::QueryRequest, BTreeMap<String, json::Value>) {
+    let mut template = query.clone();
+    let mut parameterizer = Parameterizer {
+        variables: BTreeMap::new(),
+    };
+    for argument in template.arguments.values_mut() {
+        if let ndc::models::Argument::Literal { value } = argument {
+            *argument = ndc::models::Argument::Variable {
+                name: parameterizer.variable(value.take()),
+            };
+        }
+    }
+    parameterizer.query(&mut template.query);
+    (template, parameterizer.variables)
+}
+
+struct Parameterizer {
+    variables: BTreeMap<String, json::Value>,
+}
+
+impl Parameterizer {
+    /// Adds a variable with the given value, returning its name
+    fn variable(&mut self, value: json::Value) -> String {
+        let name = format!("{VARIABLE_PREFIX}{}", self.variables.len());
+        self.variables.insert(name.clone(), value);
+        name
+    }
+
+    fn query(&mut self, query: &mut ndc::models::Query) {
+        if let Some(predicate) = &mut query.predicate {
+            self.expression(predicate);
+        }
+        for field in query
+            .fields
+            .iter_mut()
+            .flat_map(|fields| fields.values_mut())
+        {
+            if let ndc::models::Field::Relationship {
+                query, arguments, ..
+            } = field
+            {
+                self.relationship_arguments(arguments);
+                self.query(query);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &mut ndc::models::Expression) {
+        match expression {
+            ndc::models::Expression::And { expressions }
+            | ndc::models::Expression::Or { expressions } => {
+                for expression in expressions {
+                    self.expression(expression);
+                }
+            }
+            ndc::models::Expression::Not { expression } => self.expression(expression),
+            ndc::models::Expression::UnaryComparisonOperator { .. } => {}
+            ndc::models::Expression::BinaryComparisonOperator { value, .. } => {
+                self.comparison_value(value);
+            }
+            ndc::models::Expression::BinaryArrayComparisonOperator { values, .. } => {
+                for value in values {
+                    self.comparison_value(value);
+                }
+            }
+            ndc::models::Expression::Exists {
+                in_collection,
+                predicate,
+            } => {
+                match in_collection {
+                    ndc::models::ExistsInCollection::Related { arguments, .. }
+                    | ndc::models::ExistsInCollection::Unrelated { arguments, .. } => {
+                        self.relationship_arguments(arguments);
+                    }
+                }
+                self.expression(predicate);
+            }
+        }
+    }
+
+    fn comparison_value(&mut self, value: &mut ndc::models::ComparisonValue) {
+        if let ndc::models::ComparisonValue::Scalar { value: scalar } = value {
+            *value = ndc::models::ComparisonValue::Variable {
+                name: self.variable(scalar.take()),
+            };
+        }
+    }
+
+    fn relationship_arguments(
+        &mut self,
+        arguments: &mut BTreeMap<String, ndc::models::RelationshipArgument>,
+    ) {
+        for argument in arguments.values_mut() {
+            if let ndc::models::RelationshipArgument::Literal { value } = argument {
+                *argument = ndc::models::RelationshipArgument::Variable {
+                    name: self.variable(value.take()),
+                };
+            }
+        }
+    }
+}
@@ -14,8 +14,8 @@ use indexmap::IndexMap;
 use lang_graphql::ast::common::{TypeContainer, TypeName};
 use serde_json as json;
 use std::collections::{BTreeMap, HashMap};
-use tracing_util::SpanVisibility;
 
+use crate::connector::Connectors;
 use crate::schema::operations;
 use open_dds::ndc_client as ndc;
 
@@ -71,30 +71,26 @@ join_column: state_id; join_field_alias: __state_id
 ]
 */
 
-#[async_recursion]
+/// Executes the remote joins in `join_locations` and splices the responses of
+/// the target data connectors into `lhs_response`.
+#[async_recursion(?Send)]
 pub async fn execute_join_locations(
-    http_client: &reqwest::Client,
-    execution_span_attribute: String,
-    field_span_attribute: String,
+    connectors: &Connectors,
     lhs_response: &mut Vec<ndc::models::RowSet>,
     lhs_response_type: &ProcessResponseAs<'_>,
     join_locations: JoinLocations<(RemoteJoin<'async_recursion>, JoinId)>,
 ) -> Result<(), operations::Error> {
-    let tracer = tracing_util::global_tracer();
     for (key, location) in join_locations.locations {
         // collect the join column arguments from the LHS response, also get
         // the replacement tokens
         let mut arguments = Arguments::new();
-        let collect_arg_res =
-            tracer.in_span("collect_arguments", SpanVisibility::Internal, || {
-                collect_arguments(
-                    lhs_response,
-                    lhs_response_type,
-                    &key,
-                    &location,
-                    &mut arguments,
-                )
-            })?;
+        let collect_arg_res = collect_arguments(
+            lhs_response,
+            lhs_response_type,
+            &key,
+            &location,
+            &mut arguments,
+        )?;
         if let Some(CollectArgumentResult {
             mut join_node,
             sub_tree,
@@ -111,30 +107,18 @@ pub async fn execute_join_locations(
             join_node.target_ndc_ir.variables = Some(join_variables);
 
             // execute the remote query
-            let mut target_response = tracer
-                .in_span_async(
-                    "execute_remote_join_query",
-                    SpanVisibility::Internal,
-                    || {
-                        Box::pin(execute_ndc_query(
-                            http_client,
-                            join_node.target_ndc_ir,
-                            join_node.target_data_connector,
-                            execution_span_attribute.clone(),
-                            remote_alias.clone(),
-                        ))
-                    },
-                )
-                .await?;
+            let mut target_response = execute_ndc_query(
+                connectors,
+                join_node.target_ndc_ir,
+                join_node.target_data_connector,
+            )
+            .await?;
 
             // if there is a `location.rest`, recursively process the tree; which
             // will modify the `target_response` with all joins down the tree
             if !location.rest.locations.is_empty() {
                 execute_join_locations(
-                    http_client,
-                    execution_span_attribute.clone(),
-                    // TODO: is this field span correct?
-                    field_span_attribute.clone(),
+                    connectors,
                     &mut target_response,
                     // TODO: RHS CANNOT be command for now as we don't support
                     // it yet. Once we support it, we'll have to handle that
@@ -145,24 +129,22 @@ pub async fn execute_join_locations(
                 .await?;
             }
 
-            tracer.in_span("response_join", SpanVisibility::Internal, || {
-                // from `Vec<RowSet>` create `HashMap<ArgumentId, RowSet>`
-                let responses: HashMap<ArgumentId, ndc::models::RowSet> = argument_ids
-                    .iter()
-                    .zip(target_response)
-                    .map(|(arg_id, row_set)| (*arg_id, row_set))
-                    .collect();
-                // use the replacement tokens to lookup the argument id `responses`
-                // and substitute that value in `lhs_response`
-                replace_replacement_tokens(
-                    &key,
-                    &remote_alias,
-                    &location,
-                    lhs_response,
-                    replacement_tokens,
-                    responses,
-                )
-            })?;
+            // from `Vec<RowSet>` create `HashMap<ArgumentId, RowSet>`
+            let responses: HashMap<ArgumentId, ndc::models::RowSet> = argument_ids
+                .iter()
+                .zip(target_response)
+                .map(|(arg_id, row_set)| (*arg_id, row_set))
+                .collect();
+            // use the replacement tokens to lookup the argument id `responses`
+            // and substitute that value in `lhs_response`
+            replace_replacement_tokens(
+                &key,
+                &remote_alias,
+                &location,
+                lhs_response,
+                replacement_tokens,
+                responses,
+            )?;
         }
     }
     Ok(())
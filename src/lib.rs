@@ -1,23 +1,33 @@
+// `schema::Error` and `schema::operations::Error` are large, but they are
+// returned at most once per metadata load or request, so boxing them buys
+// nothing
+#![allow(clippy::result_large_err)]
+
+pub mod connector;
+pub mod engine;
+pub mod logging;
 pub mod metadata;
+pub mod persisted_queries;
 pub mod schema;
 pub mod utils;
-use execute::query_plan::NodeQueryPlan;
 use wasm_bindgen::prelude::*;
 use lang_graphql;
-use std::str::FromStr;
 pub mod execute;
 
-
-#[wasm_bindgen(module="/www/utils/log.js")]
-extern "C" {
-    fn log(message: &str);
-}
+use logging::log;
 
 // #[wasm_bindgen(module="/www/connector/query.ts")]
 // extern "C" {
 //     fn handle_query_request(query: &str);
 // }
 
+thread_local! {
+    /// The queries persisted by the requests to `handle_request`, which has
+    /// no engine to keep them
+    static PERSISTED_QUERIES: std::rc::Rc<persisted_queries::PersistedQueries> =
+        std::rc::Rc::new(persisted_queries::PersistedQueries::new());
+}
+
 #[wasm_bindgen]
 pub fn greet(name: &str) {
     let formatted_message = format!("Hello, {}!", name);
@@ -45,217 +55,271 @@ pub fn generate_ir<'n, 's>(
     Ok(ir)
 }
 
-pub fn execute_query_plan(query_plan: execute::query_plan::QueryPlan) -> indexmap::IndexMap<lang_graphql::ast::common::Alias, serde_json::Value> {
-    let mut response = indexmap::IndexMap::new();
-    for (alias, field_plan) in query_plan.into_iter() {
-        let field_response: serde_json::Value = match field_plan {
-            NodeQueryPlan::TypeName { type_name } => {
-                log(&"Processing TypeName");
-                let res = serde_json::to_value(type_name);
-                match res {
-                    Ok(r) => {
-                        r
-                    },
-                    Err(_) => {
-                        serde_json::Value::Null
-                    }
-                }
-            },
-            NodeQueryPlan::TypeField { selection_set, schema, type_name, role: namespace } => {
-                log(&"Processing TypeField");
-                match schema.get_type(&type_name) {
-                    Some(type_info) => {
-                        let named_type = lang_graphql::introspection::named_type(
-                            schema,
-                            &namespace,
-                            type_info,
-                            selection_set,
-                        );
-                        match named_type {
-                            Ok(named_type) => {
-                                match serde_json::to_value(named_type) {
-                                    Ok(named_type) => named_type,
-                                    _ => serde_json::Value::Null
-                                }
-                            },
-                            _ => {
-                                serde_json::Value::Null
-                            }
-                        }
-                    }
-                    None => serde_json::Value::Null,
-                }
-            },
-            NodeQueryPlan::SchemaField { role: namespace, selection_set, schema } => {
-                log(&"Processing SchemaField");
-                match lang_graphql::introspection::schema_type(schema, &namespace, selection_set) {
-                    Ok(schema_type) => {
-                        match serde_json::to_value(schema_type) {
-                            Ok(schema_type) => schema_type,
-                            _ => serde_json::Value::Null
-                        }
-                    },
-                    _ => serde_json::Value::Null
-                }
-            },
-            NodeQueryPlan::RelayNodeSelect(optional_query) => {
-                log(&format!("optional query: {:?}", optional_query));
-                serde_json::Value::Null
-            },
-            NodeQueryPlan::NDCQueryExecution(ndc_query) => {
-                let execute::query_plan::NDCQueryExecution {
-                    execution_tree,
-                    selection_set,
-                    execution_span_attribute,
-                    field_span_attribute,
-                    process_response_as,
-                } = ndc_query;
-                log(&format!("execution tree: {:?}", execution_tree));
-                log(&format!("selection set: {:?}", selection_set));
-                log(&format!("exectuion span attribute: {:?}", execution_span_attribute));
-                log(&format!("field span attributes {:?}", field_span_attribute));
-                log(&format!("Process response as: {:?}", process_response_as));
-                log(&format!("Query: {:?}", execution_tree.root_node.query));
-                match serde_json::to_string(&execution_tree.root_node.query) {
-                    Ok(res) => {
-                        log(&format!("Res: {:?}", res));
-                        // handle_query_request(res.as_str());
-                        serde_json::json!({"type": "query", "queryRequest": res})
-                    },
-                    _ => {
-                        log(&"Error");
-                        serde_json::Value::Null
-                    }
-                }
-            },
-            NodeQueryPlan::NDCMutationExecution(ndc_query) => {
-                log(&format!("Query: {:?}", ndc_query));
-                serde_json::Value::Null
-            }
-        };
-        response.insert(alias.clone(), field_response);
-    }
-    log(&format!("Response: {:?}", response));
-    response
-}
-
 // Who needs a standard library? pfffft. We don't need em. 
+/// Executes a GraphQL request. The session is resolved from the given
+/// headers-like object, e.g. `{ "x-hasura-role": "user", "x-hasura-user-id": "1" }`,
+/// with the `admin` role as the admin identity. NDC requests are sent over
+/// HTTP to the URLs of the data connectors in the metadata. The queries
+/// persisted by requests are kept across calls. The returned `Promise`
+/// resolves to the serialized response once all the NDC requests have been
+/// executed.
 #[wasm_bindgen]
-pub fn handle_request(raw_request: String, schema: String) -> String {
-    // log(&raw_request);
-    // log(&schema);
+pub async fn handle_request(raw_request: String, schema: String, headers: JsValue) -> String {
+    match engine::headers_from_js(headers) {
+        Ok(headers) => {
+            let identity = hasura_authn_core::Identity::admin(hasura_authn_core::Role::new("admin"));
+            let persisted_queries = PERSISTED_QUERIES.with(std::rc::Rc::clone);
+            handle_request_with_connectors(
+                raw_request,
+                schema,
+                &persisted_queries,
+                &connector::Connectors::new().with_http_fallback(),
+                &identity,
+                &headers,
+            )
+            .await
+        }
+        Err(e) => serialize_response(lang_graphql::http::Response::error_message(e)),
+    }
+}
 
-    let user_role = hasura_authn_core::Role::new("admin");
-    let mut session_variables_map = std::collections::HashMap::new();
-    session_variables_map.insert(
-        hasura_authn_core::SessionVariable::from_str("x-hasura-user-id").unwrap(),
-        hasura_authn_core::SessionVariableValue::new("123"),
-    );
-    // For instance, if we are dealing with a user that should have a default session with 'user_role'
-    let default_role_authorization = hasura_authn_core::RoleAuthorization {
-        role: user_role.clone(),
-        session_variables: session_variables_map,
-        allowed_session_variables_from_request: hasura_authn_core::SessionVariableList::All,
+/// Same as `handle_request`, but NDC requests are executed against the given
+/// connectors, the session is resolved from the headers using the given
+/// identity, and the requests may refer to one of the given persisted
+/// queries.
+pub async fn handle_request_with_connectors(
+    raw_request: String,
+    schema: String,
+    persisted_queries: &persisted_queries::PersistedQueries,
+    connectors: &connector::Connectors,
+    identity: &hasura_authn_core::Identity,
+    headers: &http::HeaderMap,
+) -> String {
+    let session = match hasura_authn_core::resolve_session(identity, headers) {
+        Ok(session) => session,
+        Err(e) => {
+            log(&format!("Session Error: {}", e));
+            return serialize_response(e.into());
+        }
     };
 
-    // Assemble the session
-    let dummy_client_provided_variables = std::collections::HashMap::new();
-    let session = default_role_authorization.build_session(dummy_client_provided_variables);
+    let gql_schema = schema::GDS::new(&schema).and_then(|gds| gds.build_schema());
+    match gql_schema {
+        Ok(schema) => {
+            execute_request(
+                &schema,
+                persisted_queries,
+                connectors,
+                &session,
+                &raw_request,
+            )
+            .await
+        }
+        Err(e) => {
+            log(&format!("Bad schema: {}", e));
+            serialize_response(lang_graphql::http::Response::error_message(format!(
+                "invalid metadata: {e}"
+            )))
+        }
+    }
+}
 
-    // log(&format!("Session: {:?}", user_session));
+/// Executes a GraphQL request, or a batch of requests, against an already
+/// built schema, on behalf of the given session, and returns the serialized
+/// GraphQL response. The requests may refer to one of the given persisted
+/// queries.
+pub async fn execute_request(
+    schema: &lang_graphql::schema::Schema<schema::GDS>,
+    persisted_queries: &persisted_queries::PersistedQueries,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: &str,
+) -> String {
+    execute_batch(raw_request, |raw_request| async {
+        match parse_request(persisted_queries, raw_request) {
+            Ok((raw_request, query)) => {
+                execute_query_internal(schema, connectors, session, raw_request, query)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log(&format!("Error: {}", e));
+                        e.into()
+                    })
+            }
+            Err(response) => response,
+        }
+    })
+    .await
+}
 
-    let gql_schema: Option<lang_graphql::schema::Schema<schema::GDS>> = match schema::GDS::new(&schema) {
-        Ok(graphql_schema) => {
-            match graphql_schema.build_schema() {
-                Ok(graphql_schema) => {
-                    Some(graphql_schema)
-                },
-                Err(_) => {
-                    log("Bad schema");
-                    None
-                }
+/// Same as `execute_request`, but the plan of each operation is taken from
+/// the plan cache, and the schema is the one of the cache. The requests may
+/// refer to one of the given persisted queries.
+pub async fn execute_request_with_plan_cache(
+    plan_cache: &execute::plan_cache::PlanCache,
+    persisted_queries: &persisted_queries::PersistedQueries,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: &str,
+) -> String {
+    execute_batch(raw_request, |raw_request| async {
+        match parse_request(persisted_queries, raw_request) {
+            Ok((raw_request, query)) => {
+                execute_cached_query_internal(plan_cache, connectors, session, raw_request, query)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log(&format!("Error: {}", e));
+                        e.into()
+                    })
             }
-        },
-        Err(_) => {
-            log("Bad schema");
-            None
+            Err(response) => response,
         }
-    };
+    })
+    .await
+}
 
-    if let Some(schema) = gql_schema {
-        match serde_json::from_str::<lang_graphql::http::RawRequest>(&raw_request) {
-            Ok(raw_request) => {
-                log(&format!("Parsed Request: {:?}", raw_request));
-                let parse_result = lang_graphql::parser::Parser::new(&raw_request.query).parse_executable_document();
-                match parse_result {
-                    Ok(executable_document) => {
-                        // log(&format!("Executable Document: {:?}", executable_document));
-                        let request = lang_graphql::http::Request {
-                            operation_name: raw_request.operation_name,
-                            query: executable_document,
-                            variables: raw_request.variables.unwrap_or_default(),
-                        };
+/// Executes a request, or each of the requests of a batch, and returns the
+/// serialized response, or the array of the responses of the batch in the
+/// order of its requests. The requests of a batch are executed one after the
+/// other, so that their mutations are executed in order, and a request which
+/// fails doesn't affect the others.
+async fn execute_batch<F, Fut>(raw_request: &str, execute: F) -> String
+where
+    F: Fn(serde_json::Value) -> Fut,
+    Fut: std::future::Future<Output = lang_graphql::http::Response>,
+{
+    let batch_request = match serde_json::from_str::<lang_graphql::http::BatchRequest>(raw_request)
+    {
+        Ok(batch_request) => batch_request,
+        Err(e) => {
+            log(&format!("Failed to parse request: {}", e));
+            return serialize_response(lang_graphql::http::Response::error_message(format!(
+                "invalid request: {e}"
+            )));
+        }
+    };
+    let response = match batch_request {
+        lang_graphql::http::BatchRequest::Single(raw_request) => {
+            lang_graphql::http::BatchResponse::Single(execute(raw_request).await)
+        }
+        lang_graphql::http::BatchRequest::Batch(raw_requests) => {
+            let mut responses = Vec::with_capacity(raw_requests.len());
+            for raw_request in raw_requests {
+                responses.push(execute(raw_request).await);
+            }
+            lang_graphql::http::BatchResponse::Batch(responses)
+        }
+    };
+    serialize(&response)
+}
 
-                        let normalized_request = lang_graphql::validation::normalize_request(&session.role, &schema, &request);
+/// Parses a request, and resolves the persisted query it refers to, if any.
+/// Returns the error response if either fails.
+fn parse_request(
+    persisted_queries: &persisted_queries::PersistedQueries,
+    raw_request: serde_json::Value,
+) -> Result<(lang_graphql::http::RawRequest, persisted_queries::Query), lang_graphql::http::Response>
+{
+    let raw_request = serde_json::from_value::<lang_graphql::http::RawRequest>(raw_request)
+        .map_err(|e| {
+            log(&format!("Failed to parse request: {}", e));
+            lang_graphql::http::Response::error_message(format!("invalid request: {e}"))
+        })?;
+    let query = persisted_queries.resolve(&raw_request).map_err(|e| {
+        log(&format!("Error: {}", e));
+        lang_graphql::http::Response::error(e.into())
+    })?;
+    Ok((raw_request, query))
+}
 
-                        match normalized_request {
-                            Ok(request) => {
-                                let ir = generate_ir(&schema, &session, &request);
-                                match ir {
-                                    Ok(ir) => {
-                                        log(&format!("IR: {:?}", ir));
-                                        let query_plan = execute::query_plan::generate_query_plan(&ir);
-                                        match query_plan {
-                                            Ok(query_plan) => {
-                                                log(&format!("Query Plan: {:?}", query_plan));
-                                                let query_response = execute_query_plan(query_plan);
-                                                let json_response = serde_json::to_value(&query_response);
+async fn execute_cached_query_internal(
+    plan_cache: &execute::plan_cache::PlanCache,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: lang_graphql::http::RawRequest,
+    query: persisted_queries::Query,
+) -> Result<lang_graphql::http::Response, schema::operations::Error> {
+    let prepared_plan = plan_cache.prepare(
+        session,
+        &query,
+        raw_request.operation_name,
+        raw_request.variables.unwrap_or_default(),
+    )?;
+    let query_plan = prepared_plan.query_plan()?;
+    let query_result = execute::operation::execute_query_plan(connectors, query_plan).await;
+    Ok(execute::operation::to_graphql_response(query_result))
+}
 
-                                                match json_response {
-                                                    Ok(json_response) => {
-                                                        json_response.to_string()
-                                                    },
-                                                    Err(_) => {
-                                                        log(&"Error");
-                                                        "{}".to_string()
-                                                    }
-                                                }
-                                            }, 
-                                            Err(_) => {
-                                                log(&"Error!");
-                                                "{}".to_string()
-                                            }
-                                        }
-                                    }, 
-                                    Err(_) => {
-                                        log("Error generating ir");
-                                        "{}".to_string()
-                                    }
-                                }
-                            }, 
-                            Err(_) => {
-                                log("Bad request");
-                                "{}".to_string()
-                            }
-                        }
+async fn execute_query_internal(
+    schema: &lang_graphql::schema::Schema<schema::GDS>,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: lang_graphql::http::RawRequest,
+    query: persisted_queries::Query,
+) -> Result<lang_graphql::http::Response, schema::operations::Error> {
+    let request = lang_graphql::http::Request {
+        operation_name: raw_request.operation_name,
+        query: query.to_document()?,
+        variables: raw_request.variables.unwrap_or_default(),
+    };
+    let normalized_request =
+        lang_graphql::validation::normalize_request(&session.role, schema, &request)?;
+    let ir = generate_ir(schema, session, &normalized_request)?;
+    let query_plan = execute::query_plan::generate_query_plan(&ir)?;
+    let query_result = execute::operation::execute_query_plan(connectors, query_plan).await;
+    Ok(execute::operation::to_graphql_response(query_result))
+}
 
-                    }
-                    Err(parse_error) => {
-                        log(&format!("Parsing Error: {:?}", parse_error));
-                        "{}".to_string()
-                    }
-                }
-            }
-            Err(e) => {
-                // Handle the error, perhaps log it
-                log(&format!("Failed to parse request: {}", e));
-                "{}".to_string()
+/// Explains how a GraphQL request, or each request of a batch, would be
+/// executed on behalf of the given session, without executing it. The `data`
+/// of the serialized response has the IR and the NDC requests of each root
+/// field. The requests may refer to one of the given persisted queries.
+pub async fn explain_request(
+    schema: &lang_graphql::schema::Schema<schema::GDS>,
+    persisted_queries: &persisted_queries::PersistedQueries,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: &str,
+) -> String {
+    execute_batch(raw_request, |raw_request| async {
+        match parse_request(persisted_queries, raw_request) {
+            Ok((raw_request, query)) => {
+                explain_query_internal(schema, connectors, session, raw_request, query)
+                    .await
+                    .unwrap_or_else(|e| e.into())
             }
+            Err(response) => response,
         }
-    } else {
-        log("Schema missing");
-        "{}".to_string()
-    }
+    })
+    .await
 }
 
-//     raw_request: gql::http::RawRequest,
\ No newline at end of file
+async fn explain_query_internal(
+    schema: &lang_graphql::schema::Schema<schema::GDS>,
+    connectors: &connector::Connectors,
+    session: &hasura_authn_core::Session,
+    raw_request: lang_graphql::http::RawRequest,
+    query: persisted_queries::Query,
+) -> Result<lang_graphql::http::Response, schema::operations::Error> {
+    let request = lang_graphql::http::Request {
+        operation_name: raw_request.operation_name,
+        query: query.to_document()?,
+        variables: raw_request.variables.unwrap_or_default(),
+    };
+    let normalized_request =
+        lang_graphql::validation::normalize_request(&session.role, schema, &request)?;
+    let ir = generate_ir(schema, session, &normalized_request)?;
+    let query_plan = execute::query_plan::generate_query_plan(&ir)?;
+    let explain_result = execute::explain::explain_query_plan(connectors, &ir, query_plan).await;
+    Ok(execute::operation::to_graphql_response(explain_result))
+}
+
+pub(crate) fn serialize_response(response: lang_graphql::http::Response) -> String {
+    serialize(&response)
+}
+
+fn serialize(response: &impl serde::Serialize) -> String {
+    serde_json::to_string(response).unwrap_or_else(|e| {
+        log(&format!("Failed to serialize response: {}", e));
+        r#"{"errors":[{"message":"internal error"}]}"#.to_string()
+    })
+}
@@ -0,0 +1,58 @@
+//! Logging for the engine.
+//!
+//! Messages are passed to the log sink set with `set_log_sink`. If none has
+//! been set, they are passed to the `log` function of `/www/utils/log.js` in
+//! the browser, and printed to stderr natively.
+
+use std::sync::RwLock;
+
+type LogSink = Box<dyn Fn(&str) + Send + Sync>;
+
+static LOG_SINK: RwLock<Option<LogSink>> = RwLock::new(None);
+
+#[cfg(target_arch = "wasm32")]
+mod js {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/www/utils/log.js")]
+    extern "C" {
+        pub fn log(message: &str);
+    }
+}
+
+/// Log a message to the current log sink
+pub fn log(message: &str) {
+    match LOG_SINK.read() {
+        Ok(sink) => match sink.as_ref() {
+            Some(sink) => sink(message),
+            None => default_log(message),
+        },
+        // A sink panicked while logging; fall back to the default one
+        Err(_) => default_log(message),
+    }
+}
+
+/// Set the function that log messages are passed to, replacing the default
+/// one
+pub fn set_log_sink(sink: impl Fn(&str) + Send + Sync + 'static) {
+    if let Ok(mut current_sink) = LOG_SINK.write() {
+        *current_sink = Some(Box::new(sink));
+    }
+}
+
+/// Restore the default log sink
+pub fn reset_log_sink() {
+    if let Ok(mut current_sink) = LOG_SINK.write() {
+        *current_sink = None;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_log(message: &str) {
+    js::log(message);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_log(message: &str) {
+    eprintln!("{message}");
+}
@@ -274,11 +274,7 @@ pub fn resolve_command_source(
         command_source.data_connector_name.clone(),
     );
     command.source = Some(CommandSource {
-        data_connector: DataConnector::new(
-            data_connector_name,
-            data_connector_context.url.clone(),
-            data_connector_context.headers,
-        )?,
+        data_connector: DataConnector::new(data_connector_name, data_connector_context)?,
         source: command_source.data_connector_command.clone(),
         type_mappings,
         argument_mappings,
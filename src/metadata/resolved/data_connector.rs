@@ -43,15 +43,23 @@ pub struct DataConnector {
     pub name: Qualified<DataConnectorName>,
     pub url: ResolvedDataConnectorUrl,
     pub headers: SerializableHeaderMap,
+    pub capabilities: DataConnectorCapabilities,
+}
+
+/// The capabilities of a data connector which the engine plans requests with
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DataConnectorCapabilities {
+    /// Whether the data connector supports queries with `variables`, which are
+    /// executed once per set of variables
+    pub supports_query_variables: bool,
 }
 
 impl DataConnector {
     pub(crate) fn new(
         name: Qualified<DataConnectorName>,
-        url: DataConnectorUrl,
-        headers: &IndexMap<String, SecretValue>,
+        context: &DataConnectorContext<'_>,
     ) -> Result<Self, Error> {
-        let url = match url {
+        let url = match context.url.clone() {
             DataConnectorUrl::SingleUrl(url) => ResolvedDataConnectorUrl::SingleUrl(
                 SerializableUrl::new(&url.value).map_err(|e| Error::InvalidDataConnectorUrl {
                     data_connector_name: name.clone(),
@@ -75,7 +83,7 @@ impl DataConnector {
                 })
             }
         };
-        let headers = SerializableHeaderMap::new(headers).map_err(|e| match e {
+        let headers = SerializableHeaderMap::new(context.headers).map_err(|e| match e {
             HeaderError::InvalidHeaderName { header_name } => Error::InvalidHeaderName {
                 data_connector: name.clone(),
                 header_name,
@@ -85,7 +93,15 @@ impl DataConnector {
                 header_name,
             },
         })?;
-        Ok(Self { name, url, headers })
+        let capabilities = DataConnectorCapabilities {
+            supports_query_variables: context.capabilities.capabilities.query.variables.is_some(),
+        };
+        Ok(Self {
+            name,
+            url,
+            headers,
+            capabilities,
+        })
     }
 }
 
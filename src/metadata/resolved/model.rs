@@ -917,11 +917,7 @@ pub fn resolve_model_source(
     )?;
 
     model.source = Some(ModelSource {
-        data_connector: DataConnector::new(
-            qualified_data_connector_name,
-            data_connector_context.url.clone(),
-            data_connector_context.headers,
-        )?,
+        data_connector: DataConnector::new(qualified_data_connector_name, data_connector_context)?,
         collection: model_source.collection.clone(),
         type_mappings,
         argument_mappings,
@@ -17,6 +17,11 @@ impl<T: Display> Qualified<T> {
     pub fn new(subgraph: String, name: T) -> Self {
         Qualified { subgraph, name }
     }
+
+    /// The name, without the subgraph it is defined in
+    pub fn name(&self) -> &T {
+        &self.name
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Hash, Eq)]
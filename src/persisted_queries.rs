@@ -0,0 +1,226 @@
+//! Persisted queries: queries which a request refers to by the SHA-256 hash in
+//! its `extensions.persistedQuery.sha256Hash`, instead of sending them in full.
+//!
+//! Queries are either preloaded from a manifest of hashes to queries, or, as
+//! in Apollo's automatic persisted queries, registered by the first request
+//! which carries both a query and its hash. A request which only carries a
+//! hash that isn't known gets a `PersistedQueryNotFound` error, upon which
+//! the client retries with the query. The parsed documents of the queries are
+//! kept along with them.
+//!
+//! As any client can register queries, only a bounded number of the
+//! registered queries is kept, evicting the least recently used ones. The
+//! queries of manifests are all kept.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use lang_graphql as gql;
+use lang_graphql::ast::executable::ExecutableDocument;
+use lang_graphql::ast::spanning::Positioned;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::utils::lru::Lru;
+
+/// The number of automatically persisted queries kept by default
+const AUTOMATIC_PERSISTED_QUERIES_CAPACITY: usize = 1000;
+
+/// The persisted queries of an engine, keyed by the hex encoded SHA-256 hash
+/// of the query
+pub struct PersistedQueries {
+    /// The queries loaded from manifests
+    manifest_queries: RefCell<HashMap<String, Rc<PersistedQuery>>>,
+    /// The queries registered by requests
+    automatic_queries: RefCell<Lru<String, Rc<PersistedQuery>>>,
+    /// Whether requests whose query isn't persisted are rejected. Queries are
+    /// then only persisted by loading a manifest.
+    persisted_only: Cell<bool>,
+}
+
+pub struct PersistedQuery {
+    pub query: String,
+    pub document: ExecutableDocument,
+}
+
+/// The query of a request, once the persisted query it refers to, if any, is
+/// resolved
+pub enum Query {
+    Text(String),
+    Persisted(Rc<PersistedQuery>),
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("PersistedQueryNotFound")]
+    NotFound,
+    #[error("the sha256Hash of the persisted query does not match the query")]
+    HashMismatch,
+    #[error("only persisted queries are allowed")]
+    NotPersisted,
+    #[error("the request has neither a query nor a persisted query")]
+    MissingQuery,
+    #[error("the hash '{hash}' of the manifest does not match its query")]
+    ManifestHashMismatch { hash: String },
+    #[error("parsing failed: {0}")]
+    ParseFailure(#[from] Positioned<gql::parser::Error>),
+}
+
+impl Error {
+    fn code(&self) -> Option<&'static str> {
+        match self {
+            Error::NotFound => Some("PERSISTED_QUERY_NOT_FOUND"),
+            Error::HashMismatch | Error::ManifestHashMismatch { .. } => {
+                Some("PERSISTED_QUERY_HASH_MISMATCH")
+            }
+            Error::NotPersisted => Some("PERSISTED_QUERY_REQUIRED"),
+            Error::MissingQuery | Error::ParseFailure(_) => None,
+        }
+    }
+}
+
+impl From<Error> for gql::http::GraphQLError {
+    fn from(error: Error) -> Self {
+        let locations = match &error {
+            Error::ParseFailure(positioned) => Some(vec![positioned.position.into()]),
+            _ => None,
+        };
+        let extensions = error.code().map(|code| gql::http::Extensions {
+            code: Some(code.to_string()),
+            details: serde_json::Value::Null,
+        });
+        gql::http::GraphQLError {
+            message: error.to_string(),
+            locations,
+            path: None,
+            extensions,
+        }
+    }
+}
+
+impl Query {
+    pub fn text(&self) -> &str {
+        match self {
+            Query::Text(query) => query,
+            Query::Persisted(persisted_query) => &persisted_query.query,
+        }
+    }
+
+    /// The parsed document of the query. Persisted queries are only parsed
+    /// once.
+    pub fn to_document(&self) -> gql::parser::Result<ExecutableDocument> {
+        match self {
+            Query::Text(query) => gql::parser::Parser::new(query).parse_executable_document(),
+            Query::Persisted(persisted_query) => Ok(persisted_query.document.clone()),
+        }
+    }
+}
+
+impl Default for PersistedQueries {
+    fn default() -> Self {
+        PersistedQueries::with_capacity(AUTOMATIC_PERSISTED_QUERIES_CAPACITY)
+    }
+}
+
+impl PersistedQueries {
+    pub fn new() -> Self {
+        PersistedQueries::default()
+    }
+
+    /// Keeps at most `capacity` of the queries registered by requests
+    pub fn with_capacity(capacity: usize) -> Self {
+        PersistedQueries {
+            manifest_queries: RefCell::new(HashMap::new()),
+            automatic_queries: RefCell::new(Lru::new(capacity)),
+            persisted_only: Cell::new(false),
+        }
+    }
+
+    /// Rejects the requests whose query isn't persisted, including those
+    /// which would register it
+    pub fn set_persisted_only(&self, persisted_only: bool) {
+        self.persisted_only.set(persisted_only);
+    }
+
+    /// The number of persisted queries
+    pub fn len(&self) -> usize {
+        self.manifest_queries.borrow().len() + self.automatic_queries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Persists the queries of a manifest of hashes to queries. None of them
+    /// are persisted if the hash of any doesn't match its query, or if any
+    /// can't be parsed.
+    pub fn load_manifest(&self, manifest: HashMap<String, String>) -> Result<(), Error> {
+        let persisted_queries = manifest
+            .into_iter()
+            .map(|(hash, query)| {
+                if query_hash(&query) != hash.to_lowercase() {
+                    return Err(Error::ManifestHashMismatch { hash });
+                }
+                Ok((hash.to_lowercase(), Rc::new(persist(query)?)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut automatic_queries = self.automatic_queries.borrow_mut();
+        for (hash, _) in &persisted_queries {
+            automatic_queries.remove(hash);
+        }
+        self.manifest_queries.borrow_mut().extend(persisted_queries);
+        Ok(())
+    }
+
+    /// Resolves the query of a request, persisting it if the request carries
+    /// both the query and its hash
+    pub fn resolve(&self, raw_request: &gql::http::RawRequest) -> Result<Query, Error> {
+        let hash = raw_request
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.persisted_query.as_ref())
+            .map(|persisted_query| persisted_query.sha256_hash.to_lowercase());
+        match (hash, &raw_request.query) {
+            (Some(hash), None) => self.get(&hash).map(Query::Persisted).ok_or(Error::NotFound),
+            (Some(hash), Some(query)) => {
+                if query_hash(query) != hash {
+                    return Err(Error::HashMismatch);
+                }
+                match self.get(&hash) {
+                    Some(persisted_query) => Ok(Query::Persisted(persisted_query)),
+                    None if self.persisted_only.get() => Err(Error::NotPersisted),
+                    None => {
+                        let persisted_query = Rc::new(persist(query.clone())?);
+                        self.automatic_queries
+                            .borrow_mut()
+                            .insert(hash, persisted_query.clone());
+                        Ok(Query::Persisted(persisted_query))
+                    }
+                }
+            }
+            // A query in full is only allowed if it is persisted
+            (None, Some(query)) if self.persisted_only.get() => self
+                .get(&query_hash(query))
+                .map(Query::Persisted)
+                .ok_or(Error::NotPersisted),
+            (None, Some(query)) => Ok(Query::Text(query.clone())),
+            (None, None) => Err(Error::MissingQuery),
+        }
+    }
+
+    fn get(&self, hash: &str) -> Option<Rc<PersistedQuery>> {
+        let manifest_query = self.manifest_queries.borrow().get(hash).cloned();
+        manifest_query.or_else(|| self.automatic_queries.borrow_mut().get(hash))
+    }
+}
+
+fn persist(query: String) -> Result<PersistedQuery, Error> {
+    let document = gql::parser::Parser::new(&query).parse_executable_document()?;
+    Ok(PersistedQuery { query, document })
+}
+
+/// The hex encoded SHA-256 hash of a query
+pub fn query_hash(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
@@ -88,6 +88,7 @@ impl gql_schema::SchemaContext for GDS {
             } => Ok(gql_schema::TypeInfo::Scalar(gql_schema::Scalar {
                 name: graphql_type_name.clone(),
                 description: None,
+                specified_by_url: None,
             })),
             types::TypeId::InputObjectType {
                 gds_type_name,
@@ -1,6 +1,7 @@
 use gql::{ast::common as ast, http::GraphQLError};
 use lang_graphql as gql;
 use open_dds::{
+    data_connector::DataConnectorName,
     relationships::RelationshipName,
     session_variables::SessionVariable,
     types::{CustomTypeName, FieldName},
@@ -74,6 +75,11 @@ pub enum InternalDeveloperError {
 
     #[error("unexpected response from data connector: {summary}")]
     BadGDCResponse { summary: String },
+
+    #[error("no connector has been registered for the data connector {data_connector_name}")]
+    ConnectorNotFound {
+        data_connector_name: Qualified<DataConnectorName>,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -141,7 +147,7 @@ pub enum Error {
     #[error("parsing failed: {0}")]
     ParseFailure(#[from] gql::ast::spanning::Positioned<gql::parser::Error>),
     #[error("validation failed: {0}")]
-    ValidationFailed(#[from] gql::validation::Error),
+    ValidationFailed(#[from] gql::validation::Errors),
 
     #[error("The global ID {encoded_value:} couldn't be decoded due to {decoding_error:}")]
     ErrorInDecodingGlobalId {
@@ -175,21 +181,53 @@ impl Error {
 impl From<Error> for GraphQLError {
     fn from(error: Error) -> Self {
         let details = error.get_details();
+        let locations = match &error {
+            Error::ParseFailure(positioned) => Some(vec![positioned.position.into()]),
+            _ => None,
+        };
         match error {
             Error::InternalError(_internal) => GraphQLError {
                 message: "internal error".into(),
+                locations: None,
                 path: None,
                 extensions: None, // Internal errors showing up in the API response is not desirable. Hence, extensions are masked for internal errors
             },
             e => GraphQLError {
                 message: e.to_string(),
+                locations,
                 path: None,
-                extensions: details.map(|details| gql::http::Extensions { details }),
+                extensions: details.map(|details| gql::http::Extensions {
+                    code: None,
+                    details,
+                }),
             },
         }
     }
 }
 
+/// Each validation error is reported on its own, with its locations in the
+/// document and the path of the field that it is found on
+impl From<Error> for gql::http::Response {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::ValidationFailed(errors) => gql::http::Response::errors(
+                errors
+                    .into_iter()
+                    .map(|error| GraphQLError {
+                        message: format!("validation failed: {error}"),
+                        locations: (!error.locations.is_empty())
+                            .then(|| error.locations.into_iter().map(Into::into).collect()),
+                        path: (!error.path.is_empty())
+                            .then(|| error.path.iter().map(ToString::to_string).collect()),
+                        extensions: None,
+                    })
+                    .collect(),
+            ),
+            e => gql::http::Response::error(e.into()),
+        }
+    }
+}
+
 // Convert NDC errors
 impl From<open_dds::ndc_client::apis::Error> for Error {
     fn from(ndc_error: open_dds::ndc_client::apis::Error) -> Error {
@@ -209,7 +247,7 @@ impl From<open_dds::ndc_client::apis::Error> for Error {
     }
 }
 
-fn render_ndc_error(error: &open_dds::ndc_client::apis::Error) -> String {
+pub(crate) fn render_ndc_error(error: &open_dds::ndc_client::apis::Error) -> String {
     match error {
         open_dds::ndc_client::apis::Error::Reqwest(err) => match err.status() {
             Some(code) => format!("request to connector failed with status code {0}", code),
@@ -1 +1,2 @@
-pub mod json_ext;
\ No newline at end of file
+pub mod json_ext;
+pub(crate) mod lru;
@@ -0,0 +1,44 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+/// A map of at most `capacity` entries, which evicts the least recently used
+/// entry when full
+pub(crate) struct Lru<K, V> {
+    /// `None` if the capacity is 0, which `LruCache` doesn't allow
+    entries: Option<LruCache<K, V>>,
+}
+
+impl<K: Hash + Eq, V: Clone> Lru<K, V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Lru {
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.as_ref().map_or(0, LruCache::len)
+    }
+
+    pub(crate) fn get<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.entries.as_mut()?.get(key).cloned()
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        if let Some(entries) = &mut self.entries {
+            entries.put(key, value);
+        }
+    }
+
+    pub(crate) fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.entries.as_mut()?.pop(key)
+    }
+}
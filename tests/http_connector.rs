@@ -0,0 +1,267 @@
+//! Tests the HTTP connector against a stub data connector, which serves canned
+//! responses on a local port and records the requests it receives.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use open_dds::data_connector::DataConnectorName;
+use open_dds::ndc_client as ndc;
+use serde_json::json;
+
+use wasm_engine::connector::http::HttpConnector;
+use wasm_engine::connector::{Connector, ConnectorRef, Connectors};
+use wasm_engine::engine::Engine;
+use wasm_engine::metadata::resolved::data_connector::{
+    DataConnector, DataConnectorCapabilities, ResolvedDataConnectorUrl, ResolvedReadWriteUrls,
+    SerializableHeaderMap, SerializableUrl,
+};
+use wasm_engine::metadata::resolved::subgraph::Qualified;
+
+const METADATA: &str = include_str!("../www/metadata.json");
+
+#[derive(Debug, Clone)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// A data connector which responds to requests for `path` with the status and
+/// body returned by `respond`
+struct StubServer {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl StubServer {
+    fn start(respond: fn(&str) -> (u16, String)) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded_requests = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut stream);
+                let (status, body) = respond(&request.path);
+                recorded_requests.lock().unwrap().push(request);
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        StubServer { address, requests }
+    }
+
+    fn url(&self, path: &str) -> SerializableUrl {
+        SerializableUrl::new(&format!("http://{}{path}", self.address)).unwrap()
+    }
+
+    fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &mut impl Read) -> RecordedRequest {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap().to_string();
+    let path = parts.next().unwrap().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        headers.push((name.to_lowercase(), value.trim().to_string()));
+    }
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .map_or(0, |(_, value)| value.parse().unwrap());
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8(body).unwrap(),
+    }
+}
+
+fn respond(path: &str) -> (u16, String) {
+    match path {
+        "/read/capabilities" => (
+            200,
+            json!({ "versions": "^0.1.0", "capabilities": { "query": {} } }).to_string(),
+        ),
+        "/read/query" => (
+            200,
+            json!([{ "rows": [{ "Title": "Balls to the Wall" }] }]).to_string(),
+        ),
+        "/write/mutation" => (
+            200,
+            json!({ "operation_results": [{ "affected_rows": 1 }] }).to_string(),
+        ),
+        "/read/schema" => (
+            500,
+            json!({ "message": "schema unavailable", "details": { "retry": true } }).to_string(),
+        ),
+        _ => (404, "not found".to_string()),
+    }
+}
+
+fn data_connector(server: &StubServer) -> DataConnector {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-connector-token", "secret".parse().unwrap());
+    DataConnector {
+        name: Qualified::new(
+            "unknown_namespace".to_string(),
+            DataConnectorName("turso_connector".to_string()),
+        ),
+        url: ResolvedDataConnectorUrl::ReadWriteUrls(ResolvedReadWriteUrls {
+            read: server.url("/read"),
+            write: server.url("/write/"),
+        }),
+        headers: SerializableHeaderMap(headers),
+        capabilities: DataConnectorCapabilities {
+            supports_query_variables: true,
+        },
+    }
+}
+
+fn query_request() -> ndc::models::QueryRequest {
+    serde_json::from_value(json!({
+        "collection": "Album",
+        "query": { "fields": { "Title": { "type": "column", "column": "Title" } } },
+        "arguments": {},
+        "collection_relationships": {}
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_read_and_write_urls() {
+    let server = StubServer::start(respond);
+    let connector = HttpConnector::new(&data_connector(&server));
+
+    let capabilities = connector.capabilities().await.unwrap();
+    assert_eq!(capabilities.versions, "^0.1.0");
+
+    let response = connector.query(query_request()).await.unwrap();
+    assert_eq!(
+        serde_json::to_value(response).unwrap(),
+        json!([{ "rows": [{ "Title": "Balls to the Wall" }] }])
+    );
+
+    let mutation_request = serde_json::from_value(json!({
+        "operations": [{ "type": "procedure", "name": "sync", "arguments": {} }],
+        "collection_relationships": {}
+    }))
+    .unwrap();
+    let response = connector.mutation(mutation_request).await.unwrap();
+    assert_eq!(response.operation_results[0].affected_rows, 1);
+
+    let requests = server.requests();
+    assert_eq!(
+        requests
+            .iter()
+            .map(|request| (request.method.as_str(), request.path.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("GET", "/read/capabilities"),
+            ("POST", "/read/query"),
+            ("POST", "/write/mutation"),
+        ]
+    );
+    for request in &requests {
+        assert!(request
+            .headers
+            .contains(&("x-connector-token".to_string(), "secret".to_string())));
+    }
+    assert_eq!(
+        serde_json::from_str::<ndc::models::QueryRequest>(&requests[1].body).unwrap(),
+        query_request()
+    );
+}
+
+#[tokio::test]
+async fn test_error_responses() {
+    let server = StubServer::start(respond);
+    let mut data_connector = data_connector(&server);
+
+    match HttpConnector::new(&data_connector).schema().await {
+        Err(ndc::apis::Error::ConnectorError(error)) => {
+            assert_eq!(error.status, reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(error.error_response.message, "schema unavailable");
+            assert_eq!(error.error_response.details, json!({ "retry": true }));
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    // Error bodies which aren't NDC error responses become the message
+    data_connector.url = ResolvedDataConnectorUrl::SingleUrl(server.url("/missing"));
+    match HttpConnector::new(&data_connector).schema().await {
+        Err(ndc::apis::Error::ConnectorError(error)) => {
+            assert_eq!(error.status, reqwest::StatusCode::NOT_FOUND);
+            assert_eq!(error.error_response.message, "not found");
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_engine_with_http_fallback() {
+    let server = StubServer::start(respond);
+    let metadata = METADATA.replace(
+        "http://localhost:8101",
+        &format!("http://{}/read", server.address),
+    );
+    let engine =
+        Engine::with_connectors(&metadata, Connectors::new().with_http_fallback()).unwrap();
+    let response = engine
+        .execute_with_headers(
+            r#"{"query": "query { album { Title } }"}"#,
+            &http::HeaderMap::new(),
+        )
+        .await;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({ "data": { "album": [{ "Title": "Balls to the Wall" }] } })
+    );
+    assert_eq!(server.requests()[0].path, "/read/query");
+}
+
+#[test]
+fn test_http_fallback_connector_is_reused() {
+    let server = StubServer::start(respond);
+    let connectors = Connectors::new().with_http_fallback();
+    let http_connector = |data_connector| match connectors.get(data_connector) {
+        Ok(ConnectorRef::Http(http_connector)) => http_connector,
+        _ => panic!("expected an HTTP connector"),
+    };
+    let data_connector = data_connector(&server);
+    let first = http_connector(&data_connector);
+    assert!(Rc::ptr_eq(&first, &http_connector(&data_connector)));
+
+    // the connector is rebuilt when the URL of the data connector changes
+    let moved_data_connector = DataConnector {
+        url: ResolvedDataConnectorUrl::SingleUrl(server.url("/moved")),
+        ..data_connector.clone()
+    };
+    let moved = http_connector(&moved_data_connector);
+    assert!(!Rc::ptr_eq(&first, &moved));
+    assert!(moved.is_for(&moved_data_connector));
+}
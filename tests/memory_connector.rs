@@ -0,0 +1,537 @@
+//! Tests for the in-memory connector. The connector is built from the schema
+//! of the data connector in the metadata of the browser build, and loaded with
+//! the rows of `www/chinook.sqlite`, so that it can be checked against the
+//! golden responses of the SQLite connector in `tests/sqlite/query`.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use hasura_authn_core::{Identity, Role};
+use indexmap::IndexMap;
+use lang_graphql as gql;
+use open_dds::data_connector::DataConnectorName;
+use open_dds::ndc_client as ndc;
+use serde_json::json;
+
+use wasm_engine::connector::memory::{MemoryConnector, Row};
+use wasm_engine::connector::{Connector, Connectors};
+use wasm_engine::engine::Engine;
+use wasm_engine::metadata::resolved::subgraph::Qualified;
+use wasm_engine::persisted_queries::{self, query_hash, PersistedQueries};
+
+const METADATA: &str = include_str!("../www/metadata.json");
+
+fn metadata_schema() -> ndc::models::SchemaResponse {
+    let metadata: serde_json::Value = serde_json::from_str(METADATA).unwrap();
+    let data_connector = metadata
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|object| object["kind"] == "DataConnector")
+        .unwrap();
+    serde_json::from_value(data_connector["definition"]["schema"].clone()).unwrap()
+}
+
+fn to_json_value(value: rusqlite::types::ValueRef) -> serde_json::Value {
+    match value {
+        rusqlite::types::ValueRef::Null | rusqlite::types::ValueRef::Blob(_) => {
+            serde_json::Value::Null
+        }
+        rusqlite::types::ValueRef::Integer(integer) => integer.into(),
+        rusqlite::types::ValueRef::Real(real) => real.into(),
+        rusqlite::types::ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+    }
+}
+
+/// A connector loaded with the rows of the Chinook database
+fn chinook_connector() -> MemoryConnector {
+    let connector = MemoryConnector::new(metadata_schema());
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("www/chinook.sqlite");
+    let database =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .unwrap();
+    for collection in &connector.schema_response().collections {
+        let mut statement = database
+            .prepare(&format!("SELECT * FROM \"{}\"", collection.name))
+            .unwrap();
+        let columns = statement
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let rows = statement
+            .query_map([], |row| {
+                columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| Ok((column.clone(), to_json_value(row.get_ref(index)?))))
+                    .collect::<Result<Row, _>>()
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        connector.insert_rows(&collection.name, rows).unwrap();
+    }
+    connector
+}
+
+#[test]
+fn test_sqlite_query_fixtures() {
+    let connector = chinook_connector();
+    let test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sqlite/query");
+    let mut test_paths = fs::read_dir(test_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    test_paths.sort();
+    assert!(!test_paths.is_empty());
+    for test_path in test_paths {
+        let request: ndc::models::QueryRequest =
+            serde_json::from_str(&fs::read_to_string(test_path.join("request.json")).unwrap())
+                .unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(test_path.join("response.json")).unwrap())
+                .unwrap();
+        let response = futures::executor::block_on(connector.query(request)).unwrap();
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            expected,
+            "{}",
+            test_path.display()
+        );
+    }
+}
+
+/// The metadata of the browser build, with the schema and the capabilities of
+/// the data connector replaced by the ones of the memory connector
+fn chinook_metadata() -> String {
+    let mut metadata: serde_json::Value = serde_json::from_str(METADATA).unwrap();
+    let data_connector = metadata
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|object| object["kind"] == "DataConnector")
+        .unwrap();
+    data_connector["definition"]["schema"] =
+        serde_json::to_value(MemoryConnector::new(metadata_schema()).schema_response()).unwrap();
+    data_connector["definition"]["capabilities"] =
+        serde_json::to_value(MemoryConnector::capabilities_response()).unwrap();
+    metadata.to_string()
+}
+
+fn chinook_connectors(connector: impl Connector + 'static) -> Connectors {
+    let mut connectors = Connectors::new();
+    connectors.register(
+        Qualified::new(
+            "unknown_namespace".to_string(),
+            DataConnectorName("turso_connector".to_string()),
+        ),
+        connector,
+    );
+    connectors
+}
+
+/// An engine over the metadata of the browser build, with the schema and the
+/// capabilities of the data connector replaced by the ones of the connector
+fn chinook_engine(connector: impl Connector + 'static) -> Engine {
+    Engine::with_connectors(&chinook_metadata(), chinook_connectors(connector)).unwrap()
+}
+
+#[test]
+fn test_metadata_with_connector_schema() {
+    let engine = chinook_engine(chinook_connector());
+    let response = futures::executor::block_on(engine.execute_with_headers(
+        r#"{"query": "query { artistByArtistid(ArtistId: 1) { Name Albums { Title } } }"}"#,
+        &http::HeaderMap::new(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({
+            "data": {
+                "artistByArtistid": {
+                    "Name": "AC/DC",
+                    "Albums": [
+                        { "Title": "For Those About To Rock We Salute You" },
+                        { "Title": "Let There Be Rock" }
+                    ]
+                }
+            }
+        })
+    );
+}
+
+/// Records the query requests it passes on to the connector
+struct RecordingConnector {
+    connector: MemoryConnector,
+    requests: Rc<RefCell<Vec<ndc::models::QueryRequest>>>,
+}
+
+#[async_trait(?Send)]
+impl Connector for RecordingConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        self.connector.capabilities().await
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        self.connector.schema().await
+    }
+
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        self.requests.borrow_mut().push(request.clone());
+        self.connector.query(request).await
+    }
+
+    async fn mutation(
+        &self,
+        request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        self.connector.mutation(request).await
+    }
+}
+
+#[test]
+fn test_root_fields_merged_with_variables() {
+    let requests = Rc::new(RefCell::new(Vec::new()));
+    let engine = chinook_engine(RecordingConnector {
+        connector: chinook_connector(),
+        requests: requests.clone(),
+    });
+    let response = futures::executor::block_on(engine.execute_with_headers(
+        r#"{"query": "query { a: artistByArtistid(ArtistId: 1) { Name } b: artistByArtistid(ArtistId: 2) { Name } c: artistByArtistid(ArtistId: 1) { Name } d: artistByArtistid(ArtistId: 1000) { Name } album(limit: 1) { Title } }"}"#,
+        &http::HeaderMap::new(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({
+            "data": {
+                "a": { "Name": "AC/DC" },
+                "b": { "Name": "Accept" },
+                "c": { "Name": "AC/DC" },
+                "d": null,
+                "album": [{ "Title": "For Those About To Rock We Salute You" }]
+            }
+        })
+    );
+    let requests = requests.borrow();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].collection, "Artist");
+    assert_eq!(requests[0].variables.as_ref().map(Vec::len), Some(3));
+    assert_eq!(requests[1].collection, "Album");
+    assert!(requests[1].variables.is_none());
+}
+
+fn mutation(
+    connector: &MemoryConnector,
+    request: serde_json::Value,
+) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+    let request = serde_json::from_value(request).unwrap();
+    futures::executor::block_on(connector.mutation(request))
+}
+
+#[test]
+fn test_procedures() {
+    let connector = MemoryConnector::new(metadata_schema());
+    let genre = |id: i64, name: &str| -> Row {
+        IndexMap::from([
+            ("GenreId".to_string(), json!(id)),
+            ("Name".to_string(), json!(name)),
+        ])
+    };
+    let fields = json!({ "Name": { "type": "column", "column": "Name" } });
+
+    let response = mutation(
+        &connector,
+        json!({
+            "operations": [{
+                "type": "procedure",
+                "name": "insert_Genre",
+                "arguments": { "objects": [{ "GenreId": 1, "Name": "Rock" }, { "GenreId": 2, "Name": "Jazz" }] },
+                "fields": fields
+            }],
+            "collection_relationships": {}
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(response).unwrap(),
+        json!({
+            "operation_results": [{
+                "affected_rows": 2,
+                "returning": [{ "__value": [{ "Name": "Rock" }, { "Name": "Jazz" }] }]
+            }]
+        })
+    );
+
+    let response = mutation(
+        &connector,
+        json!({
+            "operations": [
+                {
+                    "type": "procedure",
+                    "name": "update_Genre_by_pk",
+                    "arguments": { "GenreId": 2, "_set": { "Name": "Blues" } },
+                    "fields": fields
+                },
+                {
+                    "type": "procedure",
+                    "name": "delete_Genre_by_pk",
+                    "arguments": { "GenreId": 1 }
+                }
+            ],
+            "collection_relationships": {}
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(response).unwrap(),
+        json!({
+            "operation_results": [
+                { "affected_rows": 1, "returning": [{ "__value": { "Name": "Blues" } }] },
+                { "affected_rows": 1 }
+            ]
+        })
+    );
+    assert_eq!(connector.rows("Genre").unwrap(), vec![genre(2, "Blues")]);
+
+    // A failed request leaves the collections unchanged
+    let error = mutation(
+        &connector,
+        json!({
+            "operations": [
+                {
+                    "type": "procedure",
+                    "name": "insert_Genre",
+                    "arguments": { "objects": [{ "GenreId": 3, "Name": "Pop" }] }
+                },
+                {
+                    "type": "procedure",
+                    "name": "insert_Genre",
+                    "arguments": { "objects": [{ "GenreId": 2, "Name": "Jazz" }] }
+                }
+            ],
+            "collection_relationships": {}
+        }),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        ndc::apis::Error::ConnectorError(error) if error.status == reqwest::StatusCode::CONFLICT
+    ));
+    assert_eq!(connector.rows("Genre").unwrap(), vec![genre(2, "Blues")]);
+}
+
+#[test]
+fn test_plans_reused_across_variable_values() {
+    let requests = Rc::new(RefCell::new(Vec::new()));
+    let engine = chinook_engine(RecordingConnector {
+        connector: chinook_connector(),
+        requests: requests.clone(),
+    });
+    let execute = |variables: serde_json::Value| {
+        let request = json!({
+            "query": "query ($id: Int, $limit: Int) { artistByArtistid(ArtistId: $id) { Name } album(limit: $limit) { Title } }",
+            "variables": variables
+        });
+        let response = futures::executor::block_on(
+            engine.execute_with_headers(&request.to_string(), &http::HeaderMap::new()),
+        );
+        serde_json::from_str::<serde_json::Value>(&response).unwrap()
+    };
+
+    assert_eq!(
+        execute(json!({ "id": 1, "limit": 1 })),
+        json!({
+            "data": {
+                "artistByArtistid": { "Name": "AC/DC" },
+                "album": [{ "Title": "For Those About To Rock We Salute You" }]
+            }
+        })
+    );
+    assert_eq!(engine.plan_cache().len(), 1);
+
+    // The value of the id is substituted in the cached plan
+    assert_eq!(
+        execute(json!({ "id": 2, "limit": 1 })),
+        json!({
+            "data": {
+                "artistByArtistid": { "Name": "Accept" },
+                "album": [{ "Title": "For Those About To Rock We Salute You" }]
+            }
+        })
+    );
+    assert_eq!(engine.plan_cache().len(), 1);
+    let artist_requests = requests
+        .borrow()
+        .iter()
+        .filter(|request| request.collection == "Artist")
+        .map(|request| serde_json::to_value(&request.query.predicate).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(artist_requests.len(), 2);
+    assert_ne!(artist_requests[0], artist_requests[1]);
+
+    // The limit isn't a value of an NDC request, so it is part of the key
+    assert_eq!(
+        execute(json!({ "id": 3, "limit": 2 })),
+        json!({
+            "data": {
+                "artistByArtistid": { "Name": "Aerosmith" },
+                "album": [
+                    { "Title": "For Those About To Rock We Salute You" },
+                    { "Title": "Balls to the Wall" }
+                ]
+            }
+        })
+    );
+    assert_eq!(engine.plan_cache().len(), 2);
+
+    // A variable of a different type of value isn't substituted
+    let response = execute(json!({ "id": "1", "limit": 1 }));
+    assert!(response["errors"].is_array());
+    assert_eq!(engine.plan_cache().len(), 2);
+}
+
+#[test]
+fn test_persisted_queries() {
+    let engine = chinook_engine(chinook_connector());
+    let query = "query { artistByArtistid(ArtistId: 1) { Name } }";
+    let hash = wasm_engine::persisted_queries::query_hash(query);
+    let execute = |request: serde_json::Value| {
+        let response = futures::executor::block_on(
+            engine.execute_with_headers(&request.to_string(), &http::HeaderMap::new()),
+        );
+        serde_json::from_str::<serde_json::Value>(&response).unwrap()
+    };
+    let persisted_query =
+        |hash: &str| json!({ "persistedQuery": { "version": 1, "sha256Hash": hash } });
+    let expected = json!({ "data": { "artistByArtistid": { "Name": "AC/DC" } } });
+
+    // The client retries with the query when the hash isn't known
+    let response = execute(json!({ "extensions": persisted_query(&hash) }));
+    assert_eq!(response["errors"][0]["message"], "PersistedQueryNotFound");
+    assert_eq!(
+        response["errors"][0]["extensions"]["code"],
+        "PERSISTED_QUERY_NOT_FOUND"
+    );
+    assert_eq!(
+        execute(json!({ "query": query, "extensions": persisted_query(&hash) })),
+        expected
+    );
+    assert_eq!(engine.persisted_queries().len(), 1);
+    assert_eq!(
+        execute(json!({ "extensions": persisted_query(&hash) })),
+        expected
+    );
+
+    // A query isn't persisted under the hash of another
+    let other_query = "query { artistByArtistid(ArtistId: 2) { Name } }";
+    let response = execute(json!({ "query": other_query, "extensions": persisted_query(&hash) }));
+    assert_eq!(
+        response["errors"][0]["extensions"]["code"],
+        "PERSISTED_QUERY_HASH_MISMATCH"
+    );
+    assert_eq!(engine.persisted_queries().len(), 1);
+
+    // Only the queries of the manifest are served once persisted queries are
+    // required
+    let manifest_query = "query { artistByArtistid(ArtistId: 3) { Name } }";
+    let manifest_hash = wasm_engine::persisted_queries::query_hash(manifest_query);
+    assert!(engine
+        .load_persisted_queries([(manifest_hash.clone(), other_query.to_string())].into())
+        .is_err());
+    engine
+        .load_persisted_queries([(manifest_hash.clone(), manifest_query.to_string())].into())
+        .unwrap();
+    engine.persisted_queries().set_persisted_only(true);
+    assert_eq!(
+        execute(json!({ "extensions": persisted_query(&manifest_hash) })),
+        json!({ "data": { "artistByArtistid": { "Name": "Aerosmith" } } })
+    );
+    assert_eq!(execute(json!({ "query": query })), expected);
+    for request in [
+        json!({ "query": other_query }),
+        json!({ "query": other_query, "extensions": persisted_query(&wasm_engine::persisted_queries::query_hash(other_query)) }),
+    ] {
+        let response = execute(request);
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "PERSISTED_QUERY_REQUIRED"
+        );
+    }
+    assert_eq!(engine.persisted_queries().len(), 2);
+}
+
+#[test]
+fn test_persisted_queries_are_bounded() {
+    let persisted_queries = PersistedQueries::with_capacity(1);
+    let persisted_request = |query: &str, with_query: bool| {
+        let mut request = json!({ "extensions": { "persistedQuery": { "version": 1, "sha256Hash": query_hash(query) } } });
+        if with_query {
+            request["query"] = json!(query);
+        }
+        serde_json::from_value::<gql::http::RawRequest>(request).unwrap()
+    };
+    let queries = [
+        "query { artistByArtistid(ArtistId: 1) { Name } }",
+        "query { artistByArtistid(ArtistId: 2) { Name } }",
+        "query { artistByArtistid(ArtistId: 3) { Name } }",
+    ];
+    for query in &queries[..2] {
+        persisted_queries
+            .resolve(&persisted_request(query, true))
+            .unwrap();
+    }
+    // The least recently used query registered by a request is evicted
+    assert_eq!(persisted_queries.len(), 1);
+    assert!(matches!(
+        persisted_queries.resolve(&persisted_request(queries[0], false)),
+        Err(persisted_queries::Error::NotFound)
+    ));
+    assert!(persisted_queries
+        .resolve(&persisted_request(queries[1], false))
+        .is_ok());
+
+    // The queries of manifests are kept
+    persisted_queries
+        .load_manifest([(query_hash(queries[2]), queries[2].to_string())].into())
+        .unwrap();
+    persisted_queries
+        .resolve(&persisted_request(queries[0], true))
+        .unwrap();
+    assert_eq!(persisted_queries.len(), 2);
+    assert!(persisted_queries
+        .resolve(&persisted_request(queries[2], false))
+        .is_ok());
+}
+
+#[test]
+fn test_persisted_queries_across_requests() {
+    let metadata = chinook_metadata();
+    let connectors = chinook_connectors(chinook_connector());
+    let persisted_queries = PersistedQueries::new();
+    let identity = Identity::admin(Role::new("admin"));
+    let execute = |request: serde_json::Value| {
+        let response = futures::executor::block_on(wasm_engine::handle_request_with_connectors(
+            request.to_string(),
+            metadata.clone(),
+            &persisted_queries,
+            &connectors,
+            &identity,
+            &http::HeaderMap::new(),
+        ));
+        serde_json::from_str::<serde_json::Value>(&response).unwrap()
+    };
+    let query = "query { artistByArtistid(ArtistId: 1) { Name } }";
+    let extensions = json!({ "persistedQuery": { "version": 1, "sha256Hash": query_hash(query) } });
+    let expected = json!({ "data": { "artistByArtistid": { "Name": "AC/DC" } } });
+    assert_eq!(
+        execute(json!({ "query": query, "extensions": extensions })),
+        expected
+    );
+    assert_eq!(execute(json!({ "extensions": extensions })), expected);
+}
@@ -0,0 +1,689 @@
+//! Runs GraphQL requests through the whole pipeline, from parsing the request
+//! to executing the query plan, against the metadata of the browser build.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use hasura_authn_core::{Identity, Role, RoleAuthorization, Session, SessionVariableList};
+use indexmap::IndexMap;
+use open_dds::data_connector::DataConnectorName;
+use open_dds::ndc_client as ndc;
+use serde_json::json;
+
+use wasm_engine::connector::{Connector, Connectors};
+use wasm_engine::engine::Engine;
+use wasm_engine::execute::query_plan::{generate_query_plan, NodeQueryPlan};
+use wasm_engine::metadata::resolved::subgraph::Qualified;
+use wasm_engine::schema::GDS;
+
+const METADATA: &str = include_str!("../www/metadata.json");
+
+/// A connector which returns the same rows for every query, and records the
+/// requests it receives
+struct StubConnector {
+    rows: Vec<IndexMap<String, ndc::models::RowFieldValue>>,
+    requests: Rc<RefCell<Vec<ndc::models::QueryRequest>>>,
+}
+
+#[async_trait(?Send)]
+impl Connector for StubConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        Ok(ndc::models::CapabilitiesResponse {
+            versions: "^0.1.0".to_string(),
+            capabilities: ndc::models::Capabilities {
+                explain: Some(ndc::models::LeafCapability {}),
+                query: ndc::models::QueryCapabilities {
+                    aggregates: None,
+                    variables: None,
+                },
+                relationships: None,
+            },
+        })
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+
+    async fn query(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        self.requests.borrow_mut().push(request);
+        Ok(ndc::models::QueryResponse(vec![ndc::models::RowSet {
+            aggregates: None,
+            rows: Some(self.rows.clone()),
+        }]))
+    }
+
+    async fn mutation(
+        &self,
+        _request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+
+    async fn explain(
+        &self,
+        request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::ExplainResponse, ndc::apis::Error> {
+        Ok(ndc::models::ExplainResponse {
+            details: [("collection".to_string(), request.collection)].into(),
+        })
+    }
+}
+
+fn admin_session() -> Session {
+    RoleAuthorization {
+        role: Role::new("admin"),
+        session_variables: HashMap::new(),
+        allowed_session_variables_from_request: SessionVariableList::All,
+    }
+    .build_session(HashMap::new())
+}
+
+fn album_rows() -> Vec<IndexMap<String, ndc::models::RowFieldValue>> {
+    ["For Those About To Rock We Salute You", "Balls to the Wall"]
+        .into_iter()
+        .map(|title| {
+            IndexMap::from([(
+                "Title".to_string(),
+                ndc::models::RowFieldValue(json!(title)),
+            )])
+        })
+        .collect()
+}
+
+fn engine_with_stub_connector() -> (Engine, Rc<RefCell<Vec<ndc::models::QueryRequest>>>) {
+    let requests = Rc::new(RefCell::new(Vec::new()));
+    let mut connectors = Connectors::new();
+    connectors.register(
+        Qualified::new(
+            "unknown_namespace".to_string(),
+            DataConnectorName("turso_connector".to_string()),
+        ),
+        StubConnector {
+            rows: album_rows(),
+            requests: requests.clone(),
+        },
+    );
+    let engine = Engine::with_connectors(METADATA, connectors).unwrap();
+    (engine, requests)
+}
+
+#[test]
+fn test_generate_query_plan() {
+    let schema = GDS::new(METADATA).unwrap().build_schema().unwrap();
+    let session = admin_session();
+    let query = lang_graphql::parser::Parser::new("query { album(limit: 2) { Title } }")
+        .parse_executable_document()
+        .unwrap();
+    let request = lang_graphql::http::Request {
+        operation_name: None,
+        query,
+        variables: HashMap::new(),
+    };
+    let normalized_request =
+        lang_graphql::validation::normalize_request(&session.role, &schema, &request).unwrap();
+    let ir = wasm_engine::generate_ir(&schema, &session, &normalized_request).unwrap();
+    let query_plan = generate_query_plan(&ir).unwrap();
+
+    let album_plan = query_plan
+        .get(&lang_graphql::ast::common::Alias::new(
+            lang_graphql::ast::common::Name::new("album").unwrap(),
+        ))
+        .unwrap();
+    match album_plan {
+        NodeQueryPlan::NDCQueryExecution(execution) => {
+            let query_request = &execution.execution_tree.root_node.query;
+            assert_eq!(query_request.collection, "Album");
+            assert_eq!(query_request.query.limit, Some(2));
+        }
+        plan => panic!("unexpected query plan: {plan:?}"),
+    }
+}
+
+#[test]
+fn test_execute_with_session() {
+    let (engine, requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { album(limit: 2) { Title } }"}"#,
+        &admin_session(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({
+            "data": {
+                "album": [
+                    { "Title": "For Those About To Rock We Salute You" },
+                    { "Title": "Balls to the Wall" }
+                ]
+            }
+        })
+    );
+    assert_eq!(requests.borrow().len(), 1);
+}
+
+#[test]
+fn test_execute_with_unauthorized_role() {
+    let (mut engine, requests) = engine_with_stub_connector();
+    engine.set_identity(Identity::Specific {
+        default_role: Role::new("admin"),
+        allowed_roles: HashMap::from([(
+            Role::new("admin"),
+            RoleAuthorization {
+                role: Role::new("admin"),
+                session_variables: HashMap::new(),
+                allowed_session_variables_from_request: SessionVariableList::All,
+            },
+        )]),
+    });
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-hasura-role", "user".parse().unwrap());
+    let response = futures::executor::block_on(engine.execute_with_headers(
+        r#"{"query": "query { album { Title } }"}"#,
+        &headers,
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({ "errors": [{ "message": "cannot be authorized as role: user" }] })
+    );
+    assert!(requests.borrow().is_empty());
+}
+
+#[test]
+fn test_parse_error_response() {
+    let (engine, _requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { album { Title }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("data").is_none());
+    assert!(response["errors"][0]["locations"].is_array());
+}
+
+#[test]
+fn test_batched_requests() {
+    let (engine, requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"[
+            {"query": "query { album { Title } }"},
+            {"query": "query { album { Title }"},
+            {"variables": {}},
+            {"query": "query { __typename }"}
+        ]"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    let responses = response.as_array().unwrap();
+    assert_eq!(responses.len(), 4);
+    assert_eq!(
+        responses[0],
+        json!({
+            "data": {
+                "album": [
+                    { "Title": "For Those About To Rock We Salute You" },
+                    { "Title": "Balls to the Wall" }
+                ]
+            }
+        })
+    );
+    // The requests which fail don't affect the others
+    assert!(responses[1]["errors"][0]["locations"].is_array());
+    assert_eq!(
+        responses[2]["errors"][0]["message"],
+        "the request has neither a query nor a persisted query"
+    );
+    assert_eq!(responses[3], json!({ "data": { "__typename": "Query" } }));
+    assert_eq!(requests.borrow().len(), 1);
+
+    // A batch of a single request is answered with an array
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"[{"query": "query { __typename }"}]"#,
+        &admin_session(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!([{ "data": { "__typename": "Query" } }])
+    );
+}
+
+#[test]
+fn test_skip_and_include() {
+    let (engine, requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{
+            "query": "query ($withId: Boolean!) { __typename @skip(if: true) album { AlbumId @include(if: $withId) ...AlbumFields @include(if: false) ... on album @skip(if: $withId) { Title } } } fragment AlbumFields on album { AlbumId }",
+            "variables": { "withId": false }
+        }"#,
+        &admin_session(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap(),
+        json!({
+            "data": {
+                "album": [
+                    { "Title": "For Those About To Rock We Salute You" },
+                    { "Title": "Balls to the Wall" }
+                ]
+            }
+        })
+    );
+    // The skipped fields are not fetched
+    let requests = requests.borrow();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0]
+            .query
+            .fields
+            .as_ref()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        vec!["Title"]
+    );
+}
+
+#[test]
+fn test_invalid_directives() {
+    let (engine, requests) = engine_with_stub_connector();
+    for (query, message) in [
+        (
+            "query { album { Title @cached } }",
+            "unknown directive: cached",
+        ),
+        (
+            "query { album { Title @skip } }",
+            "required argument if not found on directive skip",
+        ),
+        (
+            "query { album { Title @skip(if: true, unless: false) } }",
+            "expected arguments unless on directive skip are not found",
+        ),
+        (
+            "query { album { Title @skip(if: \"yes\") } }",
+            "expected a value of type BOOLEAN but found a value of type STRING",
+        ),
+        (
+            "query ($skip: Boolean) { album { Title @skip(if: $skip) } }",
+            "the variable skip of type Boolean cannot be used at a location of type Boolean!",
+        ),
+        (
+            "query { album { Title @skip(if: false) @skip(if: true) } }",
+            "directive skip is used more than once at the same location",
+        ),
+        (
+            "query @include(if: true) { album { Title } }",
+            "directive include is not allowed on QUERY",
+        ),
+        (
+            "query { album { Title Nope @skip(if: true) } }",
+            "no such field on type album: Nope",
+        ),
+        (
+            "query { album { Title ...Missing @include(if: false) } }",
+            "fragment not defined in the document: Missing",
+        ),
+        (
+            "query { album { Title @deprecated } }",
+            "directive deprecated is not allowed on FIELD",
+        ),
+    ] {
+        let response = futures::executor::block_on(
+            engine.execute_with_session(&json!({ "query": query }).to_string(), &admin_session()),
+        );
+        let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+        assert_eq!(
+            response["errors"][0]["message"],
+            format!("validation failed: {message}"),
+            "{query}"
+        );
+    }
+    assert!(requests.borrow().is_empty());
+}
+
+#[test]
+fn test_validation_errors() {
+    let (engine, requests) = engine_with_stub_connector();
+    let query = "query {\n  album {\n    Title @cached\n    Composer\n  }\n  album2: album(limit: true) { Title }\n}";
+    let response = futures::executor::block_on(
+        engine.execute_with_session(&json!({ "query": query }).to_string(), &admin_session()),
+    );
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("data").is_none());
+    assert_eq!(
+        response["errors"],
+        json!([
+            {
+                "message": "validation failed: unknown directive: cached",
+                "locations": [{ "line": 3, "column": 11 }],
+                "path": ["album", "Title"]
+            },
+            {
+                "message": "validation failed: no such field on type album: Composer",
+                "locations": [{ "line": 4, "column": 5 }],
+                "path": ["album", "Composer"]
+            },
+            {
+                "message": "validation failed: expected a value of type INTEGER but found a value of type BOOLEAN",
+                "locations": [{ "line": 6, "column": 17 }],
+                "path": ["album2"]
+            }
+        ])
+    );
+    assert!(requests.borrow().is_empty());
+}
+
+#[test]
+fn test_introspect_directives() {
+    let (engine, _requests) = engine_with_stub_connector();
+    let query = "query { __schema { directives { name locations isRepeatable args { name } } } }";
+    let response = futures::executor::block_on(
+        engine.execute_with_session(&json!({ "query": query }).to_string(), &admin_session()),
+    );
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert_eq!(
+        response["data"]["__schema"]["directives"],
+        json!([
+            {
+                "name": "deprecated",
+                "locations": [
+                    "FIELD_DEFINITION",
+                    "ARGUMENT_DEFINITION",
+                    "INPUT_FIELD_DEFINITION",
+                    "ENUM_VALUE"
+                ],
+                "isRepeatable": false,
+                "args": [{ "name": "reason" }]
+            },
+            {
+                "name": "include",
+                "locations": ["FIELD", "FRAGMENT_SPREAD", "INLINE_FRAGMENT"],
+                "isRepeatable": false,
+                "args": [{ "name": "if" }]
+            },
+            {
+                "name": "skip",
+                "locations": ["FIELD", "FRAGMENT_SPREAD", "INLINE_FRAGMENT"],
+                "isRepeatable": false,
+                "args": [{ "name": "if" }]
+            },
+            {
+                "name": "specifiedBy",
+                "locations": ["SCALAR"],
+                "isRepeatable": false,
+                "args": [{ "name": "url" }]
+            }
+        ])
+    );
+}
+
+#[test]
+fn test_log_sink() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let sink_messages = messages.clone();
+    wasm_engine::logging::set_log_sink(move |message| {
+        sink_messages.lock().unwrap().push(message.to_string());
+    });
+    let (engine, _requests) = engine_with_stub_connector();
+    futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { album { Name } }"}"#,
+        &admin_session(),
+    ));
+    wasm_engine::logging::reset_log_sink();
+    assert!(messages
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|message| message.starts_with("Error: ")));
+}
+
+#[test]
+fn test_register_connector() {
+    let (mut engine, requests) = engine_with_stub_connector();
+    let registered_requests = Rc::new(RefCell::new(Vec::new()));
+    engine.register_connector(
+        DataConnectorName("turso_connector".to_string()),
+        StubConnector {
+            rows: album_rows(),
+            requests: registered_requests.clone(),
+        },
+    );
+    futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { album { Title } }"}"#,
+        &admin_session(),
+    ));
+    // Connectors registered for the qualified name take precedence
+    assert_eq!(requests.borrow().len(), 1);
+    assert!(registered_requests.borrow().is_empty());
+
+    let mut engine = Engine::with_connectors(METADATA, Connectors::new()).unwrap();
+    engine.register_connector(
+        DataConnectorName("turso_connector".to_string()),
+        StubConnector {
+            rows: album_rows(),
+            requests: registered_requests.clone(),
+        },
+    );
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { album { Title } }"}"#,
+        &admin_session(),
+    ));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&response).unwrap()["data"]["album"][1],
+        json!({ "Title": "Balls to the Wall" })
+    );
+    assert_eq!(registered_requests.borrow().len(), 1);
+}
+
+#[test]
+fn test_explain() {
+    let (engine, requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.explain_with_session(
+        r#"{"query": "query { __typename album(limit: 2) { Title } }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("errors").is_none(), "{response}");
+
+    let typename = &response["data"]["__typename"];
+    assert!(typename["ir"]["QueryRootField"]["TypeName"].is_object());
+    assert!(typename["plan"].is_null());
+
+    let album = &response["data"]["album"];
+    assert!(album["ir"]["QueryRootField"]["ModelSelectMany"].is_object());
+    assert_eq!(album["plan"]["type"], "query");
+    assert_eq!(album["plan"]["root_node"]["request"]["collection"], "Album");
+    assert_eq!(album["plan"]["root_node"]["request"]["query"]["limit"], 2);
+    assert_eq!(
+        album["plan"]["root_node"]["connector_explain"],
+        json!({ "collection": "Album" })
+    );
+    assert_eq!(album["plan"]["remote_joins"], json!({}));
+    // Nothing is executed
+    assert!(requests.borrow().is_empty());
+}
+
+/// A connector whose capabilities can't be fetched, which counts how often
+/// they are asked for
+struct UnavailableCapabilitiesConnector {
+    capabilities_calls: Rc<Cell<usize>>,
+}
+
+#[async_trait(?Send)]
+impl Connector for UnavailableCapabilitiesConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        self.capabilities_calls.set(self.capabilities_calls.get() + 1);
+        Err(ndc::apis::Error::ConnectorError(ndc::apis::ConnectorError {
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            error_response: ndc::apis::ErrorResponse {
+                message: "capabilities unavailable".to_string(),
+                details: serde_json::Value::Null,
+            },
+        }))
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+
+    async fn query(
+        &self,
+        _request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        unimplemented!("not called when explaining")
+    }
+
+    async fn mutation(
+        &self,
+        _request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+}
+
+/// A connector which advertises explain, but fails to explain any request
+struct FailingExplainConnector;
+
+#[async_trait(?Send)]
+impl Connector for FailingExplainConnector {
+    async fn capabilities(&self) -> Result<ndc::models::CapabilitiesResponse, ndc::apis::Error> {
+        Ok(ndc::models::CapabilitiesResponse {
+            versions: "^0.1.0".to_string(),
+            capabilities: ndc::models::Capabilities {
+                explain: Some(ndc::models::LeafCapability {}),
+                query: ndc::models::QueryCapabilities {
+                    aggregates: None,
+                    variables: None,
+                },
+                relationships: None,
+            },
+        })
+    }
+
+    async fn schema(&self) -> Result<ndc::models::SchemaResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+
+    async fn query(
+        &self,
+        _request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::QueryResponse, ndc::apis::Error> {
+        unimplemented!("not called when explaining")
+    }
+
+    async fn mutation(
+        &self,
+        _request: ndc::models::MutationRequest,
+    ) -> Result<ndc::models::MutationResponse, ndc::apis::Error> {
+        unimplemented!("not called by the engine")
+    }
+
+    async fn explain(
+        &self,
+        _request: ndc::models::QueryRequest,
+    ) -> Result<ndc::models::ExplainResponse, ndc::apis::Error> {
+        Err(ndc::apis::Error::ConnectorError(ndc::apis::ConnectorError {
+            status: reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            error_response: ndc::apis::ErrorResponse {
+                message: "explain failed".to_string(),
+                details: serde_json::Value::Null,
+            },
+        }))
+    }
+}
+
+#[test]
+fn test_explain_with_connector_error() {
+    let mut engine = Engine::with_connectors(METADATA, Connectors::new()).unwrap();
+    engine.register_connector(
+        DataConnectorName("turso_connector".to_string()),
+        FailingExplainConnector,
+    );
+    let response = futures::executor::block_on(engine.explain_with_session(
+        r#"{"query": "query { __typename album(limit: 2) { Title } }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("errors").is_none(), "{response}");
+    assert!(response["data"]["__typename"]["ir"].is_object());
+    let root_node = &response["data"]["album"]["plan"]["root_node"];
+    assert_eq!(root_node["request"]["collection"], "Album");
+    assert_eq!(
+        root_node["connector_explain"],
+        json!({
+            "error": "connector returned status code 500 Internal Server Error with message: explain failed"
+        })
+    );
+}
+
+#[test]
+fn test_explain_without_capabilities() {
+    let capabilities_calls = Rc::new(Cell::new(0));
+    let mut engine = Engine::with_connectors(METADATA, Connectors::new()).unwrap();
+    engine.register_connector(
+        DataConnectorName("turso_connector".to_string()),
+        UnavailableCapabilitiesConnector {
+            capabilities_calls: capabilities_calls.clone(),
+        },
+    );
+    let response = futures::executor::block_on(engine.explain_with_session(
+        r#"{"query": "query { a: album(limit: 1) { Title } b: album(limit: 2) { Title } }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("errors").is_none(), "{response}");
+    for alias in ["a", "b"] {
+        let root_node = &response["data"][alias]["plan"]["root_node"];
+        assert_eq!(root_node["request"]["collection"], "Album");
+        assert!(root_node["connector_explain"].is_null());
+    }
+    // The capabilities are fetched once for both root fields
+    assert_eq!(capabilities_calls.get(), 1);
+}
+
+#[test]
+fn test_identical_root_fields_are_fetched_once() {
+    let (engine, requests) = engine_with_stub_connector();
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { a: album(limit: 2) { Title } b: album(limit: 2) { Title } c: album(limit: 3) { Title } }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert_eq!(response["data"]["a"], response["data"]["b"]);
+    assert_eq!(response["data"]["a"], response["data"]["c"]);
+    let requests = requests.borrow();
+    assert_eq!(
+        requests
+            .iter()
+            .map(|request| request.query.limit)
+            .collect::<Vec<_>>(),
+        vec![Some(2), Some(3)]
+    );
+    assert!(requests.iter().all(|request| request.variables.is_none()));
+}
+
+#[test]
+fn test_merged_request_falls_back_to_separate_requests() {
+    let (engine, requests) = engine_with_stub_connector();
+    // The requests only differ in the value of the filter, so they are merged
+    // into a request with two sets of variables. The stub connector returns a
+    // single row set for it, so the root fields are executed separately.
+    let response = futures::executor::block_on(engine.execute_with_session(
+        r#"{"query": "query { a: album(where: { AlbumId: { _eq: 1 } }) { Title } b: album(where: { AlbumId: { _eq: 2 } }) { Title } }"}"#,
+        &admin_session(),
+    ));
+    let response = serde_json::from_str::<serde_json::Value>(&response).unwrap();
+    assert!(response.get("errors").is_none(), "{response}");
+    assert_eq!(response["data"]["a"], response["data"]["b"]);
+    let requests = requests.borrow();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].variables.as_ref().map(Vec::len), Some(2));
+    assert!(requests[1..]
+        .iter()
+        .all(|request| request.variables.is_none()));
+}
@@ -0,0 +1,248 @@
+//! Golden tests for the SQLite connector. Each directory under
+//! `tests/sqlite/query` and `tests/sqlite/mutation` holds an NDC
+//! `request.json`, which is compiled to SQL and executed against
+//! `www/chinook.sqlite`. The resulting `QueryResponse` or `MutationResponse` is
+//! compared against `response.json`. Mutations are executed against a copy of
+//! the database, and rolled back.
+//!
+//! Run with `REGENERATE_GOLDENFILES=1` to regenerate the expected responses.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use goldenfile::Mint;
+use open_dds::ndc_client as ndc;
+
+use wasm_engine::connector::sqlite::{self, Configuration, SqlStatement};
+
+fn test_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("sqlite")
+}
+
+fn configuration() -> Configuration {
+    let configuration = include_str!("../www/configuration.json");
+    Configuration::from_json(configuration).unwrap()
+}
+
+fn open_database() -> rusqlite::Connection {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("www/chinook.sqlite");
+    rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap()
+}
+
+/// A writable copy of the database, shared by the mutation tests
+fn open_database_copy() -> rusqlite::Connection {
+    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("www/chinook.sqlite");
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("chinook.sqlite");
+    fs::copy(source, &path).unwrap();
+    rusqlite::Connection::open(path).unwrap()
+}
+
+fn to_sql_value(value: &serde_json::Value) -> rusqlite::types::Value {
+    match value {
+        serde_json::Value::Null => rusqlite::types::Value::Null,
+        serde_json::Value::Bool(value) => rusqlite::types::Value::Integer(i64::from(*value)),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => rusqlite::types::Value::Integer(integer),
+            None => rusqlite::types::Value::Real(number.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(string) => rusqlite::types::Value::Text(string.clone()),
+        value => rusqlite::types::Value::Text(value.to_string()),
+    }
+}
+
+fn query_data(connection: &rusqlite::Connection, statement: &SqlStatement) -> String {
+    let params = statement.params.iter().map(to_sql_value);
+    connection
+        .query_row(&statement.sql, rusqlite::params_from_iter(params), |row| {
+            row.get(0)
+        })
+        .unwrap_or_else(|e| panic!("failed to execute {}: {e}", statement.sql))
+}
+
+fn mutation_data(connection: &rusqlite::Connection, statement: &SqlStatement) -> Vec<String> {
+    let params = statement.params.iter().map(to_sql_value);
+    let mut prepared = connection.prepare(&statement.sql).unwrap();
+    let rows = prepared
+        .query_map(rusqlite::params_from_iter(params), |row| row.get(0))
+        .and_then(|rows| rows.collect::<Result<Vec<String>, _>>());
+    rows.unwrap_or_else(|e| panic!("failed to execute {}: {e}", statement.sql))
+}
+
+fn test_query(connection: &rusqlite::Connection, test_path: &Path) {
+    let request: ndc::models::QueryRequest =
+        serde_json::from_str(&fs::read_to_string(test_path.join("request.json")).unwrap()).unwrap();
+    let statements = sqlite::query::plan_query(&configuration(), &request).unwrap();
+    let row_sets = statements
+        .iter()
+        .map(|statement| sqlite::query::parse_row_set(&query_data(connection, statement)).unwrap())
+        .collect();
+    let response = ndc::models::QueryResponse(row_sets);
+
+    let mut mint = Mint::new(test_path);
+    let mut expected = mint.new_goldenfile("response.json").unwrap();
+    writeln!(
+        expected,
+        "{}",
+        serde_json::to_string_pretty(&response).unwrap()
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_queries() {
+    let connection = open_database();
+    let mut test_paths = fs::read_dir(test_dir().join("query"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    test_paths.sort();
+    assert!(!test_paths.is_empty());
+    for test_path in test_paths {
+        test_query(&connection, &test_path);
+    }
+}
+
+fn test_mutation(connection: &mut rusqlite::Connection, test_path: &Path) {
+    let request: ndc::models::MutationRequest =
+        serde_json::from_str(&fs::read_to_string(test_path.join("request.json")).unwrap()).unwrap();
+    let plans = sqlite::mutation::plan_mutation(&configuration(), &request).unwrap();
+    let transaction = connection.transaction().unwrap();
+    let operation_results = plans
+        .iter()
+        .map(|plan| {
+            let rows = plan
+                .statements
+                .iter()
+                .flat_map(|statement| mutation_data(&transaction, statement))
+                .collect::<Vec<_>>();
+            plan.operation_results(&rows).unwrap()
+        })
+        .collect();
+    transaction.rollback().unwrap();
+    let response = ndc::models::MutationResponse { operation_results };
+
+    let mut mint = Mint::new(test_path);
+    let mut expected = mint.new_goldenfile("response.json").unwrap();
+    writeln!(
+        expected,
+        "{}",
+        serde_json::to_string_pretty(&response).unwrap()
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_mutations() {
+    let mut connection = open_database_copy();
+    let mut test_paths = fs::read_dir(test_dir().join("mutation"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    test_paths.sort();
+    assert!(!test_paths.is_empty());
+    for test_path in test_paths {
+        test_mutation(&mut connection, &test_path);
+    }
+}
+
+#[test]
+fn test_unknown_procedure() {
+    let request: ndc::models::MutationRequest = serde_json::from_value(serde_json::json!({
+        "operations": [{ "type": "procedure", "name": "truncate_Album", "arguments": {} }],
+        "collection_relationships": {}
+    }))
+    .unwrap();
+    assert!(matches!(
+        sqlite::mutation::plan_mutation(&configuration(), &request),
+        Err(sqlite::Error::ProcedureNotFound { procedure }) if procedure == "truncate_Album"
+    ));
+}
+
+#[test]
+fn test_by_pk_procedure_without_primary_key() {
+    let mut configuration = configuration();
+    configuration
+        .object_fields
+        .get_mut("Album")
+        .unwrap()
+        .primary_keys
+        .clear();
+    for procedure in ["update_Album_by_pk", "delete_Album_by_pk"] {
+        let request: ndc::models::MutationRequest = serde_json::from_value(serde_json::json!({
+            "operations": [{ "type": "procedure", "name": procedure, "arguments": { "AlbumId": 1 } }],
+            "collection_relationships": {}
+        }))
+        .unwrap();
+        assert!(matches!(
+            sqlite::mutation::plan_mutation(&configuration, &request),
+            Err(sqlite::Error::ProcedureNotFound { procedure: name }) if name == procedure
+        ));
+    }
+    let schema = sqlite::schema::schema_response(&configuration);
+    assert!(!schema
+        .procedures
+        .iter()
+        .any(|procedure| procedure.name == "update_Album_by_pk"));
+}
+
+#[test]
+fn test_schema_procedures() {
+    let schema = sqlite::schema::schema_response(&configuration());
+    let update_album = schema
+        .procedures
+        .iter()
+        .find(|procedure| procedure.name == "update_Album_by_pk")
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&update_album.arguments).unwrap(),
+        serde_json::json!({
+            "AlbumId": { "type": { "type": "named", "name": "Int" } },
+            "_set": { "type": { "type": "named", "name": "Album_set_object" } }
+        })
+    );
+    assert!(schema.object_types.contains_key("Album_insert_object"));
+    // Every table of the database has a primary key
+    assert_eq!(schema.procedures.len(), 3 * schema.collections.len());
+}
+
+#[test]
+fn test_unknown_column() {
+    let request: ndc::models::QueryRequest = serde_json::from_value(serde_json::json!({
+        "collection": "Album",
+        "query": { "fields": { "Name": { "type": "column", "column": "Name" } } },
+        "arguments": {},
+        "collection_relationships": {}
+    }))
+    .unwrap();
+    assert!(matches!(
+        sqlite::query::plan_query(&configuration(), &request),
+        Err(sqlite::Error::ColumnNotFound { collection, column })
+            if collection == "Album" && column == "Name"
+    ));
+}
+
+#[test]
+fn test_unknown_operator() {
+    let request: ndc::models::QueryRequest = serde_json::from_value(serde_json::json!({
+        "collection": "Album",
+        "query": {
+            "fields": { "Title": { "type": "column", "column": "Title" } },
+            "where": {
+                "type": "binary_comparison_operator",
+                "column": { "type": "column", "name": "Title", "path": [] },
+                "operator": { "type": "other", "name": "_regex" },
+                "value": { "type": "scalar", "value": "^A" }
+            }
+        },
+        "arguments": {},
+        "collection_relationships": {}
+    }))
+    .unwrap();
+    assert!(matches!(
+        sqlite::query::plan_query(&configuration(), &request),
+        Err(sqlite::Error::UnsupportedComparisonOperator { operator }) if operator == "_regex"
+    ));
+}
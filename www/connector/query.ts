@@ -1076,6 +1076,33 @@ export async function do_query(
   return perform_query(state, query_plans);
 }
 
+export async function do_mutation(
+  configuration: Configuration,
+  state: State,
+  mutation: MutationRequest
+): Promise<MutationResponse> {
+  // TODO: Plan the procedures of the collections as INSERT, UPDATE and DELETE
+  throw new NotSupported("Mutations not implemented yet!", {});
+}
+
+/**
+ * The connector to register on the engine with `engine.registerConnector`,
+ * using the credentials in `configuration.json`. The engine plans the NDC
+ * requests and awaits the responses, so the connector only executes them.
+ */
+export function turso_connector() {
+  const configuration = config as Configuration;
+  const state: State = {
+    client: get_turso_client(configuration.credentials),
+  };
+  return {
+    query: (request: QueryRequest): Promise<QueryResponse> =>
+      do_query(configuration, state, request),
+    mutation: (request: MutationRequest): Promise<MutationResponse> =>
+      do_mutation(configuration, state, request),
+  };
+}
+
 const log = (...args: string[]) => console.log(...args);
 const error = (...args: any[]) => console.error(...args);
 
@@ -2,8 +2,10 @@ import * as React from 'react';
 import {GraphiQL} from 'graphiql';
 import {createRoot} from 'react-dom/client';
 import metadata from "./metadata.json";
-import init, { greet, handle_request } from "wasm_engine";
-import { handle_query_request } from './connector/query';
+import init, { greet, Engine } from "wasm_engine";
+import { turso_connector } from "./connector/query";
+
+let engine: Engine;
 
 async function graphQLFetcher(graphQLParams: any) {
 
@@ -22,25 +24,15 @@ async function graphQLFetcher(graphQLParams: any) {
     // console.log(res);
     // return Promise.resolve(res);
 
-    let response = {data: JSON.parse(handle_request(JSON.stringify(graphQLParams), JSON.stringify(metadata)))};
+    let response = JSON.parse(await engine.execute(JSON.stringify(graphQLParams), {"x-hasura-role": "admin", "x-hasura-user-id": "123"}));
     console.log(response);
-    let final_result: any = {};
-    console.log("OKOK");
-    for (let [k, v] of Object.entries(response.data)){
-        console.log("H");
-        if ((v as any).type === "query"){
-            console.log("HERE NOW");
-            console.log(k, v);
-            let new_v = await handle_query_request((v as any).queryRequest);
-        } else {
-            final_result[k] = v;
-        }
-    }
     return Promise.resolve(response);
 }
 
 init().then((_) => {
     const h = greet("Hello");
+    engine = new Engine(JSON.stringify(metadata));
+    engine.registerConnector("turso_connector", turso_connector());
     const container = document.getElementById('graphiql');
     const root = createRoot(container); // Create a root
     root.render(React.createElement(GraphiQL, { fetcher: graphQLFetcher })); // Use the root to render